@@ -0,0 +1,31 @@
+//! 任意字节组成的 C 字符串：转换结果必须与 `str::from_utf8` 一致
+
+#![no_main]
+
+use std::ffi::CString;
+
+use libfuzzer_sys::fuzz_target;
+use vimo_ffi::{cstr_to_str, cstr_to_str_strip_bom, u8ptr_to_str_n, FfiError};
+
+fuzz_target!(|data: &[u8]| {
+    // 带长度的版本直接读取原始字节，允许内部 NUL
+    let with_len = unsafe { u8ptr_to_str_n(data.as_ptr(), data.len()) };
+    assert_eq!(with_len.ok(), std::str::from_utf8(data).ok());
+
+    // 以 NUL 结尾的版本：把内部 NUL 换掉，保证整段字节都被读取
+    let bytes: Vec<u8> = data.iter().map(|&b| if b == 0 { 1 } else { b }).collect();
+    let expected = std::str::from_utf8(&bytes).ok();
+    let c_string = CString::new(bytes.clone()).unwrap();
+
+    match unsafe { cstr_to_str(c_string.as_ptr()) } {
+        Ok(s) => assert_eq!(Some(s), expected),
+        Err(err) => {
+            assert_eq!(err, FfiError::InvalidUtf8);
+            assert!(expected.is_none());
+        }
+    }
+
+    let stripped = unsafe { cstr_to_str_strip_bom(c_string.as_ptr()) }.ok();
+    let expected_stripped = expected.map(|s| s.strip_prefix('\u{feff}').unwrap_or(s));
+    assert_eq!(stripped, expected_stripped);
+});
@@ -0,0 +1,25 @@
+//! `str_to_cstring` → `cstr_to_str` 往返必须保持内容不变
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use vimo_ffi::{cstr_to_str, str_to_cstring, vimo_ffi_free_string, FfiError};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(s) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    match str_to_cstring(s) {
+        Ok(ptr) => {
+            assert!(!s.contains('\0'));
+            let back = unsafe { cstr_to_str(ptr) }.unwrap();
+            assert_eq!(back, s);
+            unsafe { vimo_ffi_free_string(ptr) };
+        }
+        Err(err) => {
+            assert_eq!(err, FfiError::StringContainsNull);
+            assert!(s.contains('\0'));
+        }
+    }
+});
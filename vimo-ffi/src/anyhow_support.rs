@@ -0,0 +1,170 @@
+//! `anyhow` 集成
+//!
+//! `anyhow::Error` 的 `Display` 只输出最外层消息，这里统一用 `{:#}` 渲染完整的 context 链。
+//! 泛型的 `ffi_boundary`、`set_error_from` 等遇到 `anyhow::Error` 时同样如此。
+
+use std::ffi::c_char;
+
+use crate::error::write_error;
+use crate::message_limit::cap_message;
+use crate::translate::translate;
+use crate::{FfiError, FfiErrorCode, Severity};
+
+/// 渲染 `anyhow::Error`：完整 context 链，启用 `backtrace` feature 时附带 anyhow 捕获的调用栈
+pub(crate) fn format_anyhow(err: &anyhow::Error) -> (String, bool) {
+    let msg = format!("{:#}", err);
+    #[cfg(feature = "backtrace")]
+    {
+        use std::backtrace::BacktraceStatus;
+
+        let backtrace = err.backtrace();
+        if backtrace.status() == BacktraceStatus::Captured {
+            return (format!("{}\n\nbacktrace:\n{}", msg, backtrace), true);
+        }
+    }
+    (msg, false)
+}
+
+/// `anyhow::Error` 对应的错误码：错误链根部是 `FfiError` 时取其错误码
+pub(crate) fn anyhow_code(err: &anyhow::Error) -> i32 {
+    match err.downcast_ref::<FfiError>() {
+        Some(e) => e.code(),
+        None => FfiErrorCode::Unknown as i32,
+    }
+}
+
+/// `anyhow::Error` 对应的严重级别：错误链根部是 `FfiError` 时取其严重级别
+pub(crate) fn anyhow_severity(err: &anyhow::Error) -> Severity {
+    match err.downcast_ref::<FfiError>() {
+        Some(e) => e.severity(),
+        None => Severity::Error,
+    }
+}
+
+/// 设置 FFI 错误输出指针（`anyhow::Error`，包含完整 context 链）
+///
+/// # Safety
+/// 同 `set_error`
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// // "failed to load config: failed to open file: permission denied"
+/// unsafe { set_error_anyhow(out_error, &err) };
+/// ```
+pub unsafe fn set_error_anyhow(out_error: *mut *mut c_char, err: &anyhow::Error) {
+    let message = format_anyhow(err).0;
+    write_error(out_error, &cap_message(translate(anyhow_code(err), &message)));
+}
+
+/// 将 `anyhow::Result` 转换为 `FfiError`，保留完整的 context 链
+///
+/// 错误链根部是 `FfiError` 时保留其错误码，外层 context 通过 `FfiError::context` 附加；
+/// 其它错误转换为 `Custom`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// // Err(Custom("failed to load config: permission denied"))
+/// let result = map_anyhow_to_ffi(config::load().context("failed to load config"));
+/// ```
+pub fn map_anyhow_to_ffi<T>(result: anyhow::Result<T>) -> Result<T, FfiError> {
+    result.map_err(|err| {
+        let msg = format!("{:#}", err);
+        let Some(root) = err.downcast_ref::<FfiError>() else {
+            return FfiError::Custom(msg);
+        };
+        let context = msg
+            .strip_suffix(root.to_string().as_str())
+            .and_then(|outer| outer.strip_suffix(": "));
+        match context {
+            Some(context) => root.clone().context(context),
+            None => root.clone(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use std::ffi::CString;
+    use std::ptr;
+
+    fn load() -> anyhow::Result<bool> {
+        Err(anyhow::anyhow!("permission denied"))
+            .context("failed to open file")
+            .context("failed to load config")
+    }
+
+    #[test]
+    fn test_set_error_anyhow() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        unsafe { set_error_anyhow(&mut error_ptr, &load().unwrap_err()) };
+        let msg = unsafe { CString::from_raw(error_ptr) };
+        assert!(msg
+            .to_str()
+            .unwrap()
+            .starts_with("failed to load config: failed to open file: permission denied"));
+    }
+
+    #[test]
+    fn test_ffi_boundary_anyhow() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let result = crate::ffi_boundary_anyhow(&mut error_ptr, false, load);
+        assert!(!result);
+        let msg = unsafe { CString::from_raw(error_ptr) };
+        let msg = msg.to_str().unwrap();
+        assert!(msg.contains("failed to load config"));
+        assert!(msg.contains("failed to open file"));
+        assert!(msg.contains("permission denied"));
+    }
+
+    #[test]
+    fn test_generic_boundary_formats_chain() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let rc = crate::ffi_boundary_code(&mut error_ptr, || load().map(|_| ()));
+        assert_eq!(rc, FfiErrorCode::Unknown as i32);
+        let msg = unsafe { CString::from_raw(error_ptr) };
+        assert!(msg
+            .to_str()
+            .unwrap()
+            .starts_with("failed to load config: failed to open file: permission denied"));
+
+        // 根部是 FfiError 时保留错误码
+        let rc = crate::ffi_boundary_code(ptr::null_mut(), || {
+            Err(anyhow::Error::new(FfiError::Timeout).context("sync"))
+        });
+        assert_eq!(rc, FfiErrorCode::Timeout as i32);
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let err = anyhow::Error::new(FfiError::NullPointer).context("argument 'path'");
+        unsafe { crate::set_error_from(&mut error_ptr, &err) };
+        let msg = unsafe { CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "argument 'path': null pointer");
+    }
+
+    #[test]
+    fn test_map_anyhow_to_ffi() {
+        let err = map_anyhow_to_ffi(load()).unwrap_err();
+        assert_eq!(
+            err,
+            FfiError::custom("failed to load config: failed to open file: permission denied")
+        );
+
+        let result: anyhow::Result<()> = Err(FfiError::NullPointer.into());
+        let err = map_anyhow_to_ffi(result.context("argument 'title'")).unwrap_err();
+        assert_eq!(err.code(), FfiErrorCode::NullPointer as i32);
+        assert_eq!(err.to_string(), "argument 'title': null pointer");
+
+        let result: anyhow::Result<()> = Err(FfiError::InvalidUtf8.into());
+        assert_eq!(map_anyhow_to_ffi(result), Err(FfiError::InvalidUtf8));
+    }
+
+    #[test]
+    fn test_anyhow_code() {
+        let err = anyhow::Error::new(FfiError::NullPointer).context("argument 'title'");
+        assert_eq!(anyhow_code(&err), FfiErrorCode::NullPointer as i32);
+        assert_eq!(anyhow_code(&anyhow::anyhow!("plain")), FfiErrorCode::Unknown as i32);
+    }
+}
@@ -0,0 +1,27 @@
+//! 错误与 panic 的调用栈捕获
+//!
+//! 捕获调用栈开销较大，即使启用了 `backtrace` feature 也默认关闭，
+//! 需要通过 `set_backtrace_capture(true)` 在运行时打开。
+
+use std::backtrace::Backtrace;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static CAPTURE_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 运行时开关：边界函数写出错误时是否附带调用栈
+pub fn set_backtrace_capture(enabled: bool) {
+    CAPTURE_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// 当前是否捕获调用栈
+pub fn backtrace_capture_enabled() -> bool {
+    CAPTURE_ENABLED.load(Ordering::Relaxed)
+}
+
+/// 开关打开时，将当前调用栈附加到错误消息之后
+pub(crate) fn append_backtrace(message: &str) -> Option<String> {
+    if !backtrace_capture_enabled() {
+        return None;
+    }
+    Some(format!("{}\n\nbacktrace:\n{}", message, Backtrace::force_capture()))
+}
@@ -0,0 +1,148 @@
+//! 边界上下文对象
+//!
+//! `ffi_boundary_ctx_obj` 不使用 out 参数，而是把 `&mut FfiContext` 交给闭包：
+//! 闭包通过上下文报告错误、输出日志，边界返回后把错误写入线程局部的最近一次错误
+//! （见 `vimo_ffi_last_error_message`）。适合 Rust 到 Rust 的 FFI 调用，out 参数在这里并不方便。
+
+use crate::error::{code_of, message_of, severity_of};
+#[cfg(feature = "metrics")]
+use crate::FfiBoundaryMetrics;
+use crate::{FfiErrorCode, Severity};
+
+/// 传给 `ffi_boundary_ctx_obj` 闭包的上下文
+#[derive(Debug)]
+pub struct FfiContext {
+    /// 闭包报告的错误消息，`None` 表示成功
+    ///
+    /// 可以直接赋值，此时错误码为 `FfiErrorCode::Custom`；需要保留错误码时用 `set_error`。
+    pub error_slot: Option<String>,
+    /// 日志回调，`log` 和边界写出错误时调用
+    pub log_fn: Option<fn(&str)>,
+    /// 边界使用的计数器（`GLOBAL_METRICS`）
+    #[cfg(feature = "metrics")]
+    pub metrics_ref: &'static FfiBoundaryMetrics,
+    error_code: i32,
+    severity: Severity,
+}
+
+impl FfiContext {
+    pub(crate) fn new() -> Self {
+        Self {
+            error_slot: None,
+            log_fn: None,
+            #[cfg(feature = "metrics")]
+            metrics_ref: &crate::GLOBAL_METRICS,
+            error_code: FfiErrorCode::Custom as i32,
+            severity: Severity::Error,
+        }
+    }
+
+    /// 报告错误，错误码和严重级别取自 `err`（非 `FfiError` 类型为 `FfiErrorCode::Unknown`）
+    ///
+    /// 多次调用时保留最后一次的错误。
+    pub fn set_error<E: std::fmt::Display + 'static>(&mut self, err: E) {
+        self.error_code = code_of(&err);
+        self.severity = severity_of(&err);
+        self.error_slot = Some(message_of(&err).into_owned());
+    }
+
+    /// 是否已经报告了错误
+    pub fn has_error(&self) -> bool {
+        self.error_slot.is_some()
+    }
+
+    /// 通过 `log_fn` 输出日志，未设置回调时什么也不做
+    pub fn log(&self, msg: &str) {
+        if let Some(log_fn) = self.log_fn {
+            log_fn(msg);
+        }
+    }
+
+    /// 取出报告的错误：（错误码，消息，严重级别）
+    pub(crate) fn take_error(&mut self) -> Option<(i32, String, Severity)> {
+        let message = self.error_slot.take()?;
+        Some((self.error_code, message, self.severity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ffi_boundary_ctx_obj, last_error, vimo_ffi_last_error_code, FfiError, LastError,
+    };
+    use std::cell::RefCell;
+
+    thread_local! {
+        static LOGGED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record(msg: &str) {
+        LOGGED.with(|logged| logged.borrow_mut().push(msg.to_owned()));
+    }
+
+    #[test]
+    fn test_ctx_success() {
+        let value = ffi_boundary_ctx_obj(|ctx| {
+            assert!(!ctx.has_error());
+            42
+        });
+        assert_eq!(value, 42);
+        assert_eq!(last_error(), None);
+    }
+
+    #[test]
+    fn test_ctx_error_recorded_after_return() {
+        let value = ffi_boundary_ctx_obj(|ctx| {
+            ctx.set_error(FfiError::NotFound("theme".into()));
+            assert!(ctx.has_error());
+            -1
+        });
+        // 返回值由闭包决定，错误写入最近一次错误
+        assert_eq!(value, -1);
+        assert_eq!(
+            last_error(),
+            Some(LastError {
+                code: FfiErrorCode::NotFound as i32,
+                message: "theme".into(),
+                severity: Severity::Error,
+                was_panic: false,
+                function: None,
+            })
+        );
+
+        // 直接写 error_slot 时错误码为 Custom
+        ffi_boundary_ctx_obj(|ctx| ctx.error_slot = Some("bad state".into()));
+        assert_eq!(vimo_ffi_last_error_code(), FfiErrorCode::Custom as i32);
+        assert_eq!(last_error().unwrap().message, "bad state");
+
+        // 非 FfiError 的错误为 Unknown
+        ffi_boundary_ctx_obj(|ctx| ctx.set_error("plain"));
+        assert_eq!(vimo_ffi_last_error_code(), FfiErrorCode::Unknown as i32);
+    }
+
+    #[test]
+    fn test_ctx_log_fn() {
+        ffi_boundary_ctx_obj(|ctx| {
+            ctx.log("ignored without log_fn");
+            ctx.log_fn = Some(record);
+            ctx.log("opening");
+            ctx.set_error(FfiError::Timeout);
+        });
+        let logged = LOGGED.with(|logged| logged.take());
+        assert_eq!(logged, ["opening", "operation timed out"]);
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_ctx_panic_returns_default() {
+        let value: i32 = ffi_boundary_ctx_obj(|ctx| {
+            ctx.set_error(FfiError::Timeout);
+            panic!("boom")
+        });
+        assert_eq!(value, 0);
+        let last = last_error().unwrap();
+        assert_eq!((last.code, last.was_panic), (FfiErrorCode::Panic as i32, true));
+        assert_eq!(last.message, "internal panic: boom");
+    }
+}
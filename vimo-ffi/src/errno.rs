@@ -0,0 +1,230 @@
+//! errno 风格的错误码
+//!
+//! 供习惯 POSIX 约定的 C 调用方使用：失败时只需要一个小整数，而不是错误文本。
+//!
+//! | 错误 | errno |
+//! |------|-------|
+//! | `NullPointer` / `NullArgument` | `EINVAL` |
+//! | `InvalidUtf8` / `InvalidUtf8At` | `EILSEQ` |
+//! | `StringContainsNull` | `EINVAL` |
+//! | `Io` | 按 `kind`：`NotFound` → `ENOENT`，`PermissionDenied` → `EACCES`，`AlreadyExists` → `EEXIST`，`InvalidInput` → `EINVAL`，`Interrupted` → `EINTR`，其余 `EIO` |
+//! | `Parse` | `EINVAL` |
+//! | `OutOfRange` | `ERANGE` |
+//! | `NotFound` | `ENOENT` |
+//! | `OutOfMemory` / `StackOverflow` | `ENOMEM` |
+//! | `Timeout` | `ETIMEDOUT` |
+//! | `Unavailable` | `EAGAIN` |
+//! | `Cancelled` | `ECANCELED` |
+//! | `AlreadyExists` | `EEXIST` |
+//! | `BufferTooSmall` | `ERANGE` |
+//! | `MisalignedPointer` | `EINVAL` |
+//! | `ReentrantCall` | `EDEADLK` |
+//! | `PermissionDenied` | `EACCES` |
+//! | `Custom` / `CustomCode` | `EIO` |
+//! | `Context` / `WithSeverity` | 同内层错误 |
+//! | panic | `ENOTRECOVERABLE` |
+//!
+//! 数值按目标平台取值（Linux、Apple、FreeBSD、Windows CRT），不依赖 libc。
+
+use crate::{FfiError, FfiErrorCode};
+
+pub const ENOENT: i32 = 2;
+pub const EINTR: i32 = 4;
+pub const EIO: i32 = 5;
+pub const ENOMEM: i32 = 12;
+pub const EACCES: i32 = 13;
+pub const EEXIST: i32 = 17;
+pub const EINVAL: i32 = 22;
+pub const ERANGE: i32 = 34;
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub const EILSEQ: i32 = 92;
+#[cfg(target_os = "freebsd")]
+pub const EILSEQ: i32 = 86;
+#[cfg(windows)]
+pub const EILSEQ: i32 = 42;
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "freebsd", windows)))]
+pub const EILSEQ: i32 = 84;
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+pub const EAGAIN: i32 = 35;
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "freebsd")))]
+pub const EAGAIN: i32 = 11;
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+pub const ETIMEDOUT: i32 = 60;
+#[cfg(windows)]
+pub const ETIMEDOUT: i32 = 138;
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "freebsd", windows)))]
+pub const ETIMEDOUT: i32 = 110;
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub const ECANCELED: i32 = 89;
+#[cfg(target_os = "freebsd")]
+pub const ECANCELED: i32 = 85;
+#[cfg(windows)]
+pub const ECANCELED: i32 = 105;
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "freebsd", windows)))]
+pub const ECANCELED: i32 = 125;
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+pub const EDEADLK: i32 = 11;
+#[cfg(windows)]
+pub const EDEADLK: i32 = 36;
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "freebsd", windows)))]
+pub const EDEADLK: i32 = 35;
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub const ENOTRECOVERABLE: i32 = 104;
+#[cfg(target_os = "freebsd")]
+pub const ENOTRECOVERABLE: i32 = 95;
+#[cfg(windows)]
+pub const ENOTRECOVERABLE: i32 = 127;
+#[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "freebsd", windows)))]
+pub const ENOTRECOVERABLE: i32 = 131;
+
+impl FfiError {
+    /// 对应的 errno 值（正数），映射见模块文档
+    pub fn to_errno(&self) -> i32 {
+        match self {
+            Self::NullPointer => EINVAL,
+            Self::NullArgument(_) => EINVAL,
+            Self::InvalidUtf8 => EILSEQ,
+            Self::InvalidUtf8At { .. } => EILSEQ,
+            Self::StringContainsNull => EINVAL,
+            Self::Io { kind, .. } => io_kind_to_errno(kind),
+            Self::Parse(_) => EINVAL,
+            Self::OutOfRange(_) => ERANGE,
+            Self::NotFound(_) => ENOENT,
+            Self::OutOfMemory => ENOMEM,
+            Self::StackOverflow => ENOMEM,
+            Self::Timeout => ETIMEDOUT,
+            Self::Unavailable(_) => EAGAIN,
+            Self::Cancelled => ECANCELED,
+            Self::AlreadyExists(_) => EEXIST,
+            Self::BufferTooSmall { .. } => ERANGE,
+            Self::MisalignedPointer { .. } => EINVAL,
+            Self::ReentrantCall(_) => EDEADLK,
+            Self::PermissionDenied(_) => EACCES,
+            Self::Custom(_) => EIO,
+            Self::CustomCode { .. } => EIO,
+            Self::Context { inner, .. } => inner.to_errno(),
+            Self::WithSeverity { inner, .. } => inner.to_errno(),
+        }
+    }
+}
+
+/// `std::io::ErrorKind` 名称对应的 errno
+fn io_kind_to_errno(kind: &str) -> i32 {
+    match kind {
+        "NotFound" => ENOENT,
+        "PermissionDenied" => EACCES,
+        "AlreadyExists" => EEXIST,
+        "InvalidInput" => EINVAL,
+        "Interrupted" => EINTR,
+        _ => EIO,
+    }
+}
+
+/// 稳定错误码对应的 errno，`0` 表示成功
+///
+/// 只有错误码可用时（如线程局部的最近一次错误），`Io` 的 `kind` 已经丢失，统一为 `EIO`。
+pub fn errno_from_code(code: i32) -> i32 {
+    match FfiErrorCode::from_i32(code) {
+        Some(FfiErrorCode::Ok) => 0,
+        Some(FfiErrorCode::Panic) => ENOTRECOVERABLE,
+        Some(FfiErrorCode::Unknown) | None => EIO,
+        Some(_) => FfiError::from_code(code, String::new()).to_errno(),
+    }
+}
+
+/// 当前线程最近一次边界调用的 errno，没有错误时返回 0
+#[no_mangle]
+pub extern "C" fn vimo_ffi_errno() -> i32 {
+    errno_from_code(crate::vimo_ffi_last_error_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffi_boundary_errno, Severity};
+    use std::ptr;
+
+    #[test]
+    fn test_to_errno_per_variant() {
+        let io = |kind: &str| FfiError::Io {
+            kind: kind.into(),
+            message: "io".into(),
+        };
+        let cases = [
+            (FfiError::NullPointer, EINVAL),
+            (FfiError::NullArgument("config".into()), EINVAL),
+            (FfiError::InvalidUtf8, EILSEQ),
+            (FfiError::invalid_utf8_with_context(3, [0; 16]), EILSEQ),
+            (FfiError::StringContainsNull, EINVAL),
+            (io("NotFound"), ENOENT),
+            (io("PermissionDenied"), EACCES),
+            (io("AlreadyExists"), EEXIST),
+            (io("InvalidInput"), EINVAL),
+            (io("Interrupted"), EINTR),
+            (io("BrokenPipe"), EIO),
+            (FfiError::Parse("bad".into()), EINVAL),
+            (FfiError::OutOfRange("big".into()), ERANGE),
+            (FfiError::NotFound("key".into()), ENOENT),
+            (FfiError::OutOfMemory, ENOMEM),
+            (FfiError::StackOverflow, ENOMEM),
+            (FfiError::Timeout, ETIMEDOUT),
+            (FfiError::Unavailable("busy".into()), EAGAIN),
+            (FfiError::Cancelled, ECANCELED),
+            (FfiError::AlreadyExists("key".into()), EEXIST),
+            (FfiError::BufferTooSmall { required: 64 }, ERANGE),
+            (
+                FfiError::MisalignedPointer {
+                    required: 8,
+                    address_low_bits: 4,
+                },
+                EINVAL,
+            ),
+            (FfiError::ReentrantCall("vimo_open".into()), EDEADLK),
+            (FfiError::PermissionDenied(None), EACCES),
+            (FfiError::custom("x"), EIO),
+            (FfiError::custom_with_code(1234, "x"), EIO),
+            (FfiError::NotFound("key".into()).context("lookup"), ENOENT),
+            (FfiError::OutOfRange("big".into()).with_severity(Severity::Warning), ERANGE),
+        ];
+        for (err, errno) in cases {
+            assert_eq!(err.to_errno(), errno, "{:?}", err);
+        }
+    }
+
+    #[test]
+    fn test_errno_constants() {
+        assert_eq!((ENOENT, EIO, EINVAL, ERANGE), (2, 5, 22, 34));
+        #[cfg(target_os = "linux")]
+        assert_eq!((EILSEQ, ECANCELED, ENOTRECOVERABLE), (84, 125, 131));
+        #[cfg(target_os = "linux")]
+        assert_eq!(EDEADLK, 35);
+    }
+
+    #[test]
+    fn test_vimo_ffi_errno() {
+        let rc = ffi_boundary_errno(ptr::null_mut(), || Err(FfiError::NotFound("theme".into())));
+        assert_eq!(rc, -ENOENT);
+        assert_eq!(vimo_ffi_errno(), ENOENT);
+
+        #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+        {
+            let rc = ffi_boundary_errno(ptr::null_mut(), || -> Result<(), FfiError> {
+                panic!("boom")
+            });
+            assert_eq!(rc, -ENOTRECOVERABLE);
+            assert_eq!(vimo_ffi_errno(), ENOTRECOVERABLE);
+        }
+
+        let rc = ffi_boundary_errno(ptr::null_mut(), || Err("not an FfiError"));
+        assert_eq!(rc, -EIO);
+
+        assert_eq!(ffi_boundary_errno(ptr::null_mut(), || Ok::<_, FfiError>(())), 0);
+        assert_eq!(vimo_ffi_errno(), 0);
+    }
+}
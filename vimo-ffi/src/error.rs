@@ -1,23 +1,438 @@
 //! FFI 错误处理工具
 
-use std::ffi::{c_char, CString};
+use std::any::Any;
+use std::borrow::Cow;
+use std::ffi::{c_char, CStr, CString};
+use std::ops::RangeInclusive;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
 
 use thiserror::Error;
 
+use crate::message_limit::cap_message;
+use crate::observer::notify_error;
+use crate::translate::translate;
+use crate::verbosity::{error_verbosity, with_detail, ErrorVerbosity};
+use crate::string::{sanitized_cstring, try_sanitized_cstring};
+use crate::truncate_at_char_boundary;
+
 /// FFI 通用错误类型
+///
+/// 后续版本会继续增加内置错误，crate 外匹配时需要保留 `_` 分支。
 #[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum FfiError {
     #[error("null pointer")]
     NullPointer,
 
+    /// 指定名称的参数为 null，错误码与 `NullPointer` 相同
+    #[error("null pointer: argument '{0}'")]
+    NullArgument(String),
+
     #[error("invalid UTF-8 string")]
     InvalidUtf8,
 
+    /// 非法 UTF-8，带第一个非法字节的偏移和附近的字节，错误码与 `InvalidUtf8` 相同
+    ///
+    /// 由 `FfiError::invalid_utf8_with_context` 构造，`Debug` 输出附近字节的十六进制。
+    #[error("invalid UTF-8 string at byte {byte_offset}")]
+    InvalidUtf8At {
+        byte_offset: usize,
+        context: Utf8Context,
+    },
+
     #[error("string contains null byte")]
     StringContainsNull,
 
+    /// I/O 错误，`kind` 为 `std::io::ErrorKind` 的名称
+    #[error("{message}")]
+    Io { kind: String, message: String },
+
+    /// 解析失败（数字、格式等）
+    #[error("{0}")]
+    Parse(String),
+
+    /// 数值超出允许范围
+    #[error("{0}")]
+    OutOfRange(String),
+
+    /// 请求的对象（键、文件、句柄等）不存在
+    #[error("{0}")]
+    NotFound(String),
+
+    /// 内存分配失败
+    #[error("out of memory")]
+    OutOfMemory,
+
+    /// 栈空间耗尽
+    ///
+    /// 真正的栈溢出会直接终止进程，`catch_unwind` 捕获不到，
+    /// 这个变体供递归深度检查等主动检测的代码在溢出前报告（见 `ffi_boundary` 文档）。
+    #[error("stack overflow")]
+    StackOverflow,
+
+    /// 操作超时，稍后重试可能成功
+    #[error("operation timed out")]
+    Timeout,
+
+    /// 资源暂时不可用（忙、被占用、服务未就绪），稍后重试可能成功
+    #[error("{0}")]
+    Unavailable(String),
+
+    /// 操作被调用方取消
+    #[error("operation cancelled")]
+    Cancelled,
+
+    /// 要创建的对象（键、文件、句柄等）已经存在
+    #[error("{0}")]
+    AlreadyExists(String),
+
+    /// 调用方提供的输出缓冲区不足，`required` 为所需的字节数
+    #[error("buffer too small: {required} bytes required")]
+    BufferTooSmall { required: usize },
+
+    /// 不可重入的导出函数在自身触发的回调中被再次调用（见 `ReentrancyGuard`）
+    #[error("reentrant call to '{0}'")]
+    ReentrantCall(String),
+
+    /// 没有访问文件、套接字等资源的权限，携带可选的描述（通常是 OS 错误）
+    ///
+    /// 由 `FfiError::permission_denied` 或 `FfiError::permission_denied_with_path` 构造。
+    /// `std::io::Error` 的转换仍然得到 `kind` 为 `"PermissionDenied"` 的 `Io`。
+    #[error("permission denied{}", DetailSuffix(.0))]
+    PermissionDenied(Option<String>),
+
+    /// 指针未按类型要求对齐，`address_low_bits` 为地址中低于对齐要求的部分
+    #[error(
+        "misaligned pointer: requires {required}-byte alignment, address is off by {address_low_bits}"
+    )]
+    MisalignedPointer {
+        required: usize,
+        address_low_bits: usize,
+    },
+
     #[error("{0}")]
     Custom(String),
+
+    /// 携带调用方自定义错误码的错误
+    #[error("{message}")]
+    CustomCode { code: i32, message: String },
+
+    /// 附加了调用点上下文的错误，错误码与内层错误相同
+    #[error("{context}: {inner}")]
+    Context { context: String, inner: Box<FfiError> },
+
+    /// 覆盖了默认严重级别的错误，错误码和消息与内层错误相同
+    #[error("{inner}")]
+    WithSeverity { severity: Severity, inner: Box<FfiError> },
+}
+
+/// `PermissionDenied` 的描述，有描述时显示为 `": 描述"`
+struct DetailSuffix<'a>(&'a Option<String>);
+
+impl std::fmt::Display for DetailSuffix<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(detail) => write!(f, ": {detail}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `InvalidUtf8At` 携带的出错位置附近的 16 个字节
+///
+/// 窗口从出错位置前 8 个字节开始（偏移不足 8 时从开头开始），
+/// `Debug` 输出为十六进制，出错的字节以 `[..]` 标出：`63 61 66 [e9] 21 00 ...`。
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Utf8Context {
+    bytes: [u8; 16],
+    error_index: usize,
+}
+
+impl Utf8Context {
+    /// 窗口中的字节
+    pub fn bytes(&self) -> &[u8; 16] {
+        &self.bytes
+    }
+}
+
+impl std::fmt::Debug for Utf8Context {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, byte) in self.bytes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            if i == self.error_index {
+                write!(f, "[{byte:02x}]")?;
+            } else {
+                write!(f, "{byte:02x}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// FFI 错误码
+///
+/// 数值是对 C 侧的稳定约定，发布后不得修改：
+///
+/// | 错误码 | 含义 |
+/// |--------|------|
+/// | 0 | 成功 |
+/// | -1 | 非 `FfiError` 类型的错误 |
+/// | -1000 | 边界处捕获到 panic |
+/// | 1 | `NullPointer` / `NullArgument` |
+/// | 2 | `InvalidUtf8` / `InvalidUtf8At` |
+/// | 3 | `StringContainsNull` |
+/// | 4 | `Io` |
+/// | 5 | `Parse` |
+/// | 6 | `OutOfRange` |
+/// | 7 | `NotFound` |
+/// | 8 | `OutOfMemory` |
+/// | 9 | `MisalignedPointer` |
+/// | 10 | `StackOverflow` |
+/// | 11 | `Timeout` |
+/// | 12 | `Unavailable` |
+/// | 13 | `Cancelled` |
+/// | 14 | `AlreadyExists` |
+/// | 15 | `BufferTooSmall` |
+/// | 16 | `ReentrantCall` |
+/// | 17 | `PermissionDenied` |
+/// | 100 | `Custom`（未指定错误码的自定义错误） |
+///
+/// `18..100` 保留给后续内置错误，大于 `100` 的错误码留给应用自定义错误码
+/// （见 `FfiError::custom_with_code`）。
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FfiErrorCode {
+    Ok = 0,
+    Unknown = -1,
+    Panic = -1000,
+    NullPointer = 1,
+    InvalidUtf8 = 2,
+    StringContainsNull = 3,
+    Io = 4,
+    Parse = 5,
+    OutOfRange = 6,
+    NotFound = 7,
+    OutOfMemory = 8,
+    MisalignedPointer = 9,
+    StackOverflow = 10,
+    Timeout = 11,
+    Unavailable = 12,
+    Cancelled = 13,
+    AlreadyExists = 14,
+    BufferTooSmall = 15,
+    ReentrantCall = 16,
+    PermissionDenied = 17,
+    Custom = 100,
+}
+
+impl FfiErrorCode {
+    /// 从整数错误码转换，未知错误码返回 None
+    pub fn from_i32(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(Self::Ok),
+            -1 => Some(Self::Unknown),
+            -1000 => Some(Self::Panic),
+            1 => Some(Self::NullPointer),
+            2 => Some(Self::InvalidUtf8),
+            3 => Some(Self::StringContainsNull),
+            4 => Some(Self::Io),
+            5 => Some(Self::Parse),
+            6 => Some(Self::OutOfRange),
+            7 => Some(Self::NotFound),
+            8 => Some(Self::OutOfMemory),
+            9 => Some(Self::MisalignedPointer),
+            10 => Some(Self::StackOverflow),
+            11 => Some(Self::Timeout),
+            12 => Some(Self::Unavailable),
+            13 => Some(Self::Cancelled),
+            14 => Some(Self::AlreadyExists),
+            15 => Some(Self::BufferTooSmall),
+            16 => Some(Self::ReentrantCall),
+            17 => Some(Self::PermissionDenied),
+            100 => Some(Self::Custom),
+            _ => None,
+        }
+    }
+
+    /// 全部内置错误码
+    // 启用 `phf` 时 from_name 查表，只有测试用到
+    #[cfg_attr(feature = "phf", allow(dead_code))]
+    const ALL: [Self; 21] = [
+        Self::Ok,
+        Self::Unknown,
+        Self::Panic,
+        Self::NullPointer,
+        Self::InvalidUtf8,
+        Self::StringContainsNull,
+        Self::Io,
+        Self::Parse,
+        Self::OutOfRange,
+        Self::NotFound,
+        Self::OutOfMemory,
+        Self::MisalignedPointer,
+        Self::StackOverflow,
+        Self::Timeout,
+        Self::Unavailable,
+        Self::Cancelled,
+        Self::AlreadyExists,
+        Self::BufferTooSmall,
+        Self::ReentrantCall,
+        Self::PermissionDenied,
+        Self::Custom,
+    ];
+
+    /// 稳定的名称（如 `"NULL_POINTER"`），与数值一样发布后不得修改
+    ///
+    /// 供配置文件等按名称而不是数值保存错误码的场景使用，见 `from_name`。
+    pub fn name(&self) -> &'static str {
+        self.c_name().to_str().unwrap_or_default()
+    }
+
+    /// 从 `name` 返回的名称还原错误码，区分大小写，未知名称返回 None
+    ///
+    /// 启用 `phf` feature 时查 `ERROR_CODE_VALUES`，否则依次比较全部内置名称。
+    pub fn from_name(name: &str) -> Option<Self> {
+        #[cfg(feature = "phf")]
+        return ERROR_CODE_VALUES.get(name).copied().and_then(Self::from_i32);
+        #[cfg(not(feature = "phf"))]
+        Self::ALL.into_iter().find(|code| code.name() == name)
+    }
+
+    /// `name` 的 C 字符串形式
+    pub(crate) fn c_name(&self) -> &'static CStr {
+        match self {
+            Self::Ok => c"OK",
+            Self::Unknown => c"UNKNOWN",
+            Self::Panic => c"PANIC",
+            Self::NullPointer => c"NULL_POINTER",
+            Self::InvalidUtf8 => c"INVALID_UTF8",
+            Self::StringContainsNull => c"STRING_CONTAINS_NULL",
+            Self::Io => c"IO",
+            Self::Parse => c"PARSE",
+            Self::OutOfRange => c"OUT_OF_RANGE",
+            Self::NotFound => c"NOT_FOUND",
+            Self::OutOfMemory => c"OUT_OF_MEMORY",
+            Self::MisalignedPointer => c"MISALIGNED_POINTER",
+            Self::StackOverflow => c"STACK_OVERFLOW",
+            Self::Timeout => c"TIMEOUT",
+            Self::Unavailable => c"UNAVAILABLE",
+            Self::Cancelled => c"CANCELLED",
+            Self::AlreadyExists => c"ALREADY_EXISTS",
+            Self::BufferTooSmall => c"BUFFER_TOO_SMALL",
+            Self::ReentrantCall => c"REENTRANT_CALL",
+            Self::PermissionDenied => c"PERMISSION_DENIED",
+            Self::Custom => c"CUSTOM",
+        }
+    }
+}
+
+/// 内置错误码到名称（见 `FfiErrorCode::name`）的编译期完美哈希表
+///
+/// 只含内置错误码，应用注册的错误码见 `error_code_name`。
+#[cfg(feature = "phf")]
+pub static ERROR_CODE_NAMES: phf::Map<i32, &'static str> = phf::phf_map! {
+    0i32 => "OK",
+    -1i32 => "UNKNOWN",
+    -1000i32 => "PANIC",
+    1i32 => "NULL_POINTER",
+    2i32 => "INVALID_UTF8",
+    3i32 => "STRING_CONTAINS_NULL",
+    4i32 => "IO",
+    5i32 => "PARSE",
+    6i32 => "OUT_OF_RANGE",
+    7i32 => "NOT_FOUND",
+    8i32 => "OUT_OF_MEMORY",
+    9i32 => "MISALIGNED_POINTER",
+    10i32 => "STACK_OVERFLOW",
+    11i32 => "TIMEOUT",
+    12i32 => "UNAVAILABLE",
+    13i32 => "CANCELLED",
+    14i32 => "ALREADY_EXISTS",
+    15i32 => "BUFFER_TOO_SMALL",
+    16i32 => "REENTRANT_CALL",
+    17i32 => "PERMISSION_DENIED",
+    100i32 => "CUSTOM",
+};
+
+/// 名称到内置错误码的编译期完美哈希表，`ERROR_CODE_NAMES` 的逆映射
+#[cfg(feature = "phf")]
+pub static ERROR_CODE_VALUES: phf::Map<&'static str, i32> = phf::phf_map! {
+    "OK" => 0,
+    "UNKNOWN" => -1,
+    "PANIC" => -1000,
+    "NULL_POINTER" => 1,
+    "INVALID_UTF8" => 2,
+    "STRING_CONTAINS_NULL" => 3,
+    "IO" => 4,
+    "PARSE" => 5,
+    "OUT_OF_RANGE" => 6,
+    "NOT_FOUND" => 7,
+    "OUT_OF_MEMORY" => 8,
+    "MISALIGNED_POINTER" => 9,
+    "STACK_OVERFLOW" => 10,
+    "TIMEOUT" => 11,
+    "UNAVAILABLE" => 12,
+    "CANCELLED" => 13,
+    "ALREADY_EXISTS" => 14,
+    "BUFFER_TOO_SMALL" => 15,
+    "REENTRANT_CALL" => 16,
+    "PERMISSION_DENIED" => 17,
+    "CUSTOM" => 100,
+};
+
+/// 错误码是否表示暂时性错误，见 `FfiError::is_transient`
+///
+/// 接收整数错误码（如 `vimo_ffi_last_error_code()` 的返回值），未知错误码返回 false。
+#[no_mangle]
+pub extern "C" fn vimo_ffi_error_is_transient(code: i32) -> bool {
+    matches!(
+        FfiErrorCode::from_i32(code),
+        Some(FfiErrorCode::Timeout | FfiErrorCode::Unavailable)
+    )
+}
+
+/// 错误码是否表示权限不足（`FfiErrorCode::PermissionDenied`）
+///
+/// 接收整数错误码（如 `vimo_ffi_last_error_code()` 的返回值）。
+/// 由 `std::io::Error` 转换来的 `Io` 错误码为 `Io`，不在此列，需要时用 errno（`EACCES`）区分。
+#[no_mangle]
+pub extern "C" fn vimo_ffi_error_is_permission_denied(code: i32) -> bool {
+    code == FfiErrorCode::PermissionDenied as i32
+}
+
+/// 错误域，配合 `FfiErrorCode` 构造 Apple 平台的 `NSError`
+pub const VIMO_FFI_ERROR_DOMAIN: &str = "ai.vimo.ffi";
+
+const ERROR_DOMAIN_CSTR: &CStr = c"ai.vimo.ffi";
+
+/// 返回错误域 `"ai.vimo.ffi"` 的静态 C 字符串，调用方不得释放
+///
+/// # 示例
+///
+/// ```swift
+/// let domain = String(cString: vimo_ffi_error_domain())
+/// let error = NSError(domain: domain, code: Int(vimo_ffi_last_error_code()))
+/// ```
+#[no_mangle]
+pub extern "C" fn vimo_ffi_error_domain() -> *const c_char {
+    ERROR_DOMAIN_CSTR.as_ptr()
+}
+
+/// 错误的严重级别，供宿主决定重试还是上报崩溃遥测
+///
+/// 数值对 C 侧稳定，`0` 表示没有错误（见 `vimo_ffi_last_error_severity`）。
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    /// 可恢复的失败，修正输入后重试即可
+    Warning = 1,
+    /// 普通错误（默认）
+    Error = 2,
+    /// 内部故障，如 panic，不应重试
+    Fatal = 3,
 }
 
 impl FfiError {
@@ -25,10 +440,388 @@ impl FfiError {
     pub fn custom(msg: impl Into<String>) -> Self {
         Self::Custom(msg.into())
     }
+
+    /// 创建带出错位置的 UTF-8 错误
+    ///
+    /// `context_bytes` 是从 `offset.saturating_sub(8)` 开始的 16 个字节，
+    /// 输入在窗口内结束时其余字节补 0。
+    ///
+    /// # 示例
+    ///
+    /// ```rust,ignore
+    /// let start = offset.saturating_sub(8);
+    /// let mut window = [0u8; 16];
+    /// let tail = &bytes[start..bytes.len().min(start + 16)];
+    /// window[..tail.len()].copy_from_slice(tail);
+    /// return Err(FfiError::invalid_utf8_with_context(offset, window));
+    /// ```
+    pub fn invalid_utf8_with_context(offset: usize, context_bytes: [u8; 16]) -> Self {
+        Self::InvalidUtf8At {
+            byte_offset: offset,
+            context: Utf8Context {
+                bytes: context_bytes,
+                error_index: offset.min(8),
+            },
+        }
+    }
+
+    /// 创建权限错误，描述取自 `std::io::Error::last_os_error()`
+    ///
+    /// 应在失败的系统调用之后立即调用，当前没有 OS 错误（errno 为 0）时描述为 None。
+    pub fn permission_denied() -> Self {
+        Self::PermissionDenied(last_os_error_description())
+    }
+
+    /// 创建带路径的权限错误，消息形如
+    /// `"permission denied: /var/lib/vimo/db: Permission denied (os error 13)"`
+    ///
+    /// OS 错误的取值同 `permission_denied`，没有 OS 错误时只包含路径。
+    pub fn permission_denied_with_path(path: &std::path::Path) -> Self {
+        let detail = match last_os_error_description() {
+            Some(os_error) => format!("{}: {os_error}", path.display()),
+            None => path.display().to_string(),
+        };
+        Self::PermissionDenied(Some(detail))
+    }
+
+    /// 创建带自定义错误码的错误
+    ///
+    /// `code` 应当 > 100。更小的值保留给内置错误（如 `0` 表示成功、`-1000` 表示 panic），
+    /// 传入时按 `FfiError::Custom` 处理，避免宿主把它误认为成功或 panic。
+    /// 建议先用 `register_error_code` 登记名称；未登记的错误码仍然可用。
+    /// 保留和未登记的错误码都会通过错误观察者提示（错误码为 `FfiErrorCode::Unknown`），
+    /// 启用 `log` 或 `tracing` feature 时同时输出一条警告。
+    pub fn custom_with_code(code: i32, msg: impl Into<String>) -> Self {
+        let custom = FfiErrorCode::Custom as i32;
+        if code <= custom {
+            if code < custom {
+                warn_custom_code(code, "is reserved for built-in errors, using CUSTOM");
+            }
+            return Self::Custom(msg.into());
+        }
+        if !crate::is_registered_error_code(code) {
+            warn_custom_code(code, "is not registered");
+        }
+        Self::CustomCode {
+            code,
+            message: msg.into(),
+        }
+    }
+
+    /// 创建指定严重级别的自定义错误
+    pub fn custom_with_severity(msg: impl Into<String>, severity: Severity) -> Self {
+        Self::custom(msg).with_severity(severity)
+    }
+
+    /// 覆盖错误的严重级别，错误码和消息保持不变
+    pub fn with_severity(self, severity: Severity) -> Self {
+        Self::WithSeverity {
+            severity,
+            inner: Box::new(self),
+        }
+    }
+
+    /// 稳定的数值错误码，供 C 侧分支判断（取值见 `FfiErrorCode`）
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::NullPointer => FfiErrorCode::NullPointer as i32,
+            Self::NullArgument(_) => FfiErrorCode::NullPointer as i32,
+            Self::InvalidUtf8 => FfiErrorCode::InvalidUtf8 as i32,
+            Self::InvalidUtf8At { .. } => FfiErrorCode::InvalidUtf8 as i32,
+            Self::StringContainsNull => FfiErrorCode::StringContainsNull as i32,
+            Self::Io { .. } => FfiErrorCode::Io as i32,
+            Self::Parse(_) => FfiErrorCode::Parse as i32,
+            Self::OutOfRange(_) => FfiErrorCode::OutOfRange as i32,
+            Self::NotFound(_) => FfiErrorCode::NotFound as i32,
+            Self::OutOfMemory => FfiErrorCode::OutOfMemory as i32,
+            Self::MisalignedPointer { .. } => FfiErrorCode::MisalignedPointer as i32,
+            Self::StackOverflow => FfiErrorCode::StackOverflow as i32,
+            Self::Timeout => FfiErrorCode::Timeout as i32,
+            Self::Unavailable(_) => FfiErrorCode::Unavailable as i32,
+            Self::Cancelled => FfiErrorCode::Cancelled as i32,
+            Self::AlreadyExists(_) => FfiErrorCode::AlreadyExists as i32,
+            Self::BufferTooSmall { .. } => FfiErrorCode::BufferTooSmall as i32,
+            Self::ReentrantCall(_) => FfiErrorCode::ReentrantCall as i32,
+            Self::PermissionDenied(_) => FfiErrorCode::PermissionDenied as i32,
+            Self::Custom(_) => FfiErrorCode::Custom as i32,
+            Self::CustomCode { code, .. } => *code,
+            Self::Context { inner, .. } => inner.code(),
+            Self::WithSeverity { inner, .. } => inner.code(),
+        }
+    }
+
+    /// 错误的严重级别
+    ///
+    /// 内置错误默认为 `Severity::Error`（`OutOfMemory` 和 `StackOverflow` 为 `Severity::Fatal`），
+    /// 可通过 `with_severity` 覆盖；边界处捕获的 panic 总是 `Severity::Fatal`。
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::WithSeverity { severity, .. } => *severity,
+            Self::Context { inner, .. } => inner.severity(),
+            Self::OutOfMemory | Self::StackOverflow => Severity::Fatal,
+            _ => Severity::Error,
+        }
+    }
+
+    /// 是否是暂时性错误（`Timeout`、`Unavailable`），调用方可以稍后重试
+    ///
+    /// `Context` 和 `WithSeverity` 取内层错误的分类。
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Timeout | Self::Unavailable(_) => true,
+            Self::Context { inner, .. } | Self::WithSeverity { inner, .. } => inner.is_transient(),
+            _ => false,
+        }
+    }
+
+    /// 在错误消息前附加上下文，错误码保持不变
+    ///
+    /// # 示例
+    ///
+    /// ```rust,ignore
+    /// let err = FfiError::NullPointer.context("argument 'title'");
+    /// assert_eq!(err.to_string(), "argument 'title': null pointer");
+    /// assert_eq!(err.code(), 1);
+    /// ```
+    pub fn context(self, ctx: impl Into<String>) -> Self {
+        Self::Context {
+            context: ctx.into(),
+            inner: Box::new(self),
+        }
+    }
+
+    /// 同 `context`，上下文延迟生成
+    pub fn with_context<F: FnOnce() -> String>(self, f: F) -> Self {
+        self.context(f())
+    }
+
+    /// 从错误码和消息重建错误，与 `code()` 互逆
+    ///
+    /// 无字段的内置错误忽略 `msg`；只携带消息的内置错误以 `msg` 作为消息；
+    /// 字段无法从消息中恢复的内置错误（如 `Io`、`MisalignedPointer`、`BufferTooSmall`）
+    /// 还原为保留原错误码的 `CustomCode`；
+    /// 大于 `100` 的错误码还原为保留原错误码的自定义错误；其余未知错误码按 `Custom` 处理。
+    pub fn from_code(code: i32, msg: impl Into<String>) -> Self {
+        match FfiErrorCode::from_i32(code) {
+            Some(FfiErrorCode::NullPointer) => Self::NullPointer,
+            Some(FfiErrorCode::InvalidUtf8) => Self::InvalidUtf8,
+            Some(FfiErrorCode::StringContainsNull) => Self::StringContainsNull,
+            Some(
+                FfiErrorCode::Io
+                | FfiErrorCode::MisalignedPointer
+                | FfiErrorCode::BufferTooSmall
+                | FfiErrorCode::ReentrantCall,
+            ) => Self::CustomCode {
+                code,
+                message: msg.into(),
+            },
+            Some(FfiErrorCode::Parse) => Self::Parse(msg.into()),
+            Some(FfiErrorCode::OutOfRange) => Self::OutOfRange(msg.into()),
+            Some(FfiErrorCode::NotFound) => Self::NotFound(msg.into()),
+            Some(FfiErrorCode::OutOfMemory) => Self::OutOfMemory,
+            Some(FfiErrorCode::StackOverflow) => Self::StackOverflow,
+            Some(FfiErrorCode::Timeout) => Self::Timeout,
+            Some(FfiErrorCode::Unavailable) => Self::Unavailable(msg.into()),
+            Some(FfiErrorCode::Cancelled) => Self::Cancelled,
+            Some(FfiErrorCode::AlreadyExists) => Self::AlreadyExists(msg.into()),
+            Some(FfiErrorCode::PermissionDenied) => {
+                let msg = msg.into();
+                let detail = match msg.strip_prefix("permission denied") {
+                    Some("") => None,
+                    Some(rest) if rest.starts_with(": ") => Some(rest[2..].to_owned()),
+                    _ => Some(msg),
+                };
+                Self::PermissionDenied(detail)
+            }
+            Some(FfiErrorCode::Custom) => Self::Custom(msg.into()),
+            _ if code > FfiErrorCode::Custom as i32 => Self::custom_with_code(code, msg),
+            _ => Self::Custom(msg.into()),
+        }
+    }
+
+    /// 从 `set_error` 写出的错误文本重建错误
+    ///
+    /// 识别内置错误的标准 `Display` 文本（如 `"null pointer"`），其余文本还原为 `Custom`。
+    /// 文本中不含错误码，因此 `CustomCode` 的错误码无法恢复。
+    pub fn from_error_string(s: &str) -> Self {
+        [
+            Self::NullPointer,
+            Self::InvalidUtf8,
+            Self::StringContainsNull,
+            Self::OutOfMemory,
+            Self::StackOverflow,
+            Self::Timeout,
+            Self::Cancelled,
+        ]
+        .into_iter()
+        .find(|e| e.to_string() == s)
+        .unwrap_or_else(|| Self::custom(s))
+    }
+
+    /// 无字段变体的静态消息，与 `Display` 输出相同
+    pub(crate) fn static_message(&self) -> Option<&'static CStr> {
+        match self {
+            Self::NullPointer => Some(NULL_POINTER_MESSAGE),
+            Self::InvalidUtf8 => Some(INVALID_UTF8_MESSAGE),
+            Self::StringContainsNull => Some(STRING_CONTAINS_NULL_MESSAGE),
+            Self::OutOfMemory => Some(OUT_OF_MEMORY_MESSAGE),
+            Self::StackOverflow => Some(STACK_OVERFLOW_MESSAGE),
+            Self::Timeout => Some(TIMEOUT_MESSAGE),
+            Self::Cancelled => Some(CANCELLED_MESSAGE),
+            _ => None,
+        }
+    }
+}
+
+const NULL_POINTER_MESSAGE: &CStr = c"null pointer";
+const INVALID_UTF8_MESSAGE: &CStr = c"invalid UTF-8 string";
+const STRING_CONTAINS_NULL_MESSAGE: &CStr = c"string contains null byte";
+const OUT_OF_MEMORY_MESSAGE: &CStr = c"out of memory";
+const STACK_OVERFLOW_MESSAGE: &CStr = c"stack overflow";
+const TIMEOUT_MESSAGE: &CStr = c"operation timed out";
+const CANCELLED_MESSAGE: &CStr = c"operation cancelled";
+
+/// 当前线程最近一次 OS 错误的描述，errno 为 0 时为 None
+fn last_os_error_description() -> Option<String> {
+    let err = std::io::Error::last_os_error();
+    (err.raw_os_error() != Some(0)).then(|| err.to_string())
+}
+
+/// `NotFound`、`AlreadyExists`、`TimedOut` 转换为对应的内置错误，其余保留为 `Io`
+impl From<std::io::Error> for FfiError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::NotFound => Self::NotFound(err.to_string()),
+            std::io::ErrorKind::AlreadyExists => Self::AlreadyExists(err.to_string()),
+            std::io::ErrorKind::TimedOut => Self::Timeout,
+            kind => Self::Io {
+                kind: format!("{:?}", kind),
+                message: err.to_string(),
+            },
+        }
+    }
+}
+
+impl From<std::str::Utf8Error> for FfiError {
+    fn from(_: std::str::Utf8Error) -> Self {
+        Self::InvalidUtf8
+    }
+}
+
+impl From<std::ffi::NulError> for FfiError {
+    fn from(_: std::ffi::NulError) -> Self {
+        Self::StringContainsNull
+    }
+}
+
+impl From<std::num::ParseIntError> for FfiError {
+    fn from(err: std::num::ParseIntError) -> Self {
+        Self::Parse(format!("parse error: {}", err))
+    }
+}
+
+impl From<std::num::ParseFloatError> for FfiError {
+    fn from(err: std::num::ParseFloatError) -> Self {
+        Self::Parse(format!("parse error: {}", err))
+    }
+}
+
+impl From<std::num::TryFromIntError> for FfiError {
+    fn from(err: std::num::TryFromIntError) -> Self {
+        Self::OutOfRange(err.to_string())
+    }
+}
+
+/// `Result<T, FfiError>` 的上下文扩展
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// check_not_null(title).ctx("argument 'title'")?;
+/// ```
+pub trait FfiResultExt<T> {
+    /// 出错时附加上下文，见 `FfiError::context`
+    fn ctx(self, ctx: impl Into<String>) -> Result<T, FfiError>;
+
+    /// 出错时附加延迟生成的上下文，见 `FfiError::with_context`
+    fn with_ctx<F: FnOnce() -> String>(self, f: F) -> Result<T, FfiError>;
+}
+
+impl<T> FfiResultExt<T> for Result<T, FfiError> {
+    fn ctx(self, ctx: impl Into<String>) -> Result<T, FfiError> {
+        self.map_err(|e| e.context(ctx))
+    }
+
+    fn with_ctx<F: FnOnce() -> String>(self, f: F) -> Result<T, FfiError> {
+        self.map_err(|e| e.with_context(f))
+    }
+}
+
+/// 取任意错误值的错误码：`FfiError` 取其 `code()`，其它类型为 `FfiErrorCode::Unknown`
+///
+/// 启用 `anyhow` feature 时，`anyhow::Error` 取错误链根部 `FfiError` 的错误码。
+pub(crate) fn code_of<E: 'static>(err: &E) -> i32 {
+    #[cfg(feature = "anyhow")]
+    if let Some(err) = (err as &dyn Any).downcast_ref::<anyhow::Error>() {
+        return crate::anyhow_support::anyhow_code(err);
+    }
+    match (err as &dyn Any).downcast_ref::<FfiError>() {
+        Some(e) => e.code(),
+        None => FfiErrorCode::Unknown as i32,
+    }
+}
+
+/// 格式化错误时 panic 的兜底消息
+pub const FORMAT_PANIC_MESSAGE: &str = "error while formatting error";
+
+/// 在 `catch_unwind` 中格式化错误，格式化本身 panic 时返回 `FORMAT_PANIC_MESSAGE`
+///
+/// 用户错误类型的 `Display`/`Debug` 在边界的 `catch_unwind` 之外调用，
+/// 其中的 panic 不拦截就会展开穿过 FFI 边界。
+pub(crate) fn format_guarded(format: impl FnOnce() -> String) -> String {
+    catch_unwind(AssertUnwindSafe(format)).unwrap_or_else(|_| FORMAT_PANIC_MESSAGE.to_owned())
+}
+
+/// 取任意错误值的消息：无字段的 `FfiError` 借用静态消息，不分配
+///
+/// 启用 `anyhow` feature 时，`anyhow::Error` 以 `{:#}` 渲染完整的 context 链。
+/// `Display` 实现 panic 时为 `FORMAT_PANIC_MESSAGE`。
+pub(crate) fn message_of<E: std::fmt::Display + 'static>(err: &E) -> Cow<'_, str> {
+    #[cfg(feature = "anyhow")]
+    if (err as &dyn Any).is::<anyhow::Error>() {
+        return Cow::Owned(format_guarded(|| format!("{:#}", err)));
+    }
+    let static_message = (err as &dyn Any)
+        .downcast_ref::<FfiError>()
+        .and_then(FfiError::static_message);
+    match static_message.and_then(|msg| msg.to_str().ok()) {
+        Some(msg) => Cow::Borrowed(msg),
+        None => Cow::Owned(format_guarded(|| err.to_string())),
+    }
+}
+
+/// 取任意错误值的严重级别：`FfiError` 取其 `severity()`，其它类型为 `Severity::Error`
+pub(crate) fn severity_of<E: 'static>(err: &E) -> Severity {
+    #[cfg(feature = "anyhow")]
+    if let Some(err) = (err as &dyn Any).downcast_ref::<anyhow::Error>() {
+        return crate::anyhow_support::anyhow_severity(err);
+    }
+    match (err as &dyn Any).downcast_ref::<FfiError>() {
+        Some(e) => e.severity(),
+        None => Severity::Error,
+    }
 }
 
 /// 设置 FFI 错误输出指针
 ///
+/// 消息中的 NUL 字节会被替换为字面量 `\0`，保证总能写出完整的消息。
+/// 为消息分配内存失败时写出静态字符串 `"[allocation failure during error reporting]"`（`OOM_MESSAGE`），
+/// 它同样可以（也必须）交给 `vimo_ffi_free_string`，释放函数会识别并跳过它。
+///
+/// `*out_error` 应当是 null：已有的消息不会被释放，而是直接覆盖（旧消息泄漏）。
+/// 覆盖非 null 的值时通过错误观察者提示调用方忘了先置空（消息为 `OVERWRITE_WARNING_MESSAGE`），
+/// 启用 `log` feature 时同时输出一条 warn 日志。
+///
 /// # Safety
 /// `out_error` 必须是有效的可写指针，或者 null（会被忽略）
 ///
@@ -38,74 +831,795 @@ impl FfiError {
 /// unsafe { set_error(out_error, "something went wrong") };
 /// ```
 pub unsafe fn set_error(out_error: *mut *mut c_char, msg: &str) {
-    if out_error.is_null() {
-        return;
+    let code = FfiErrorCode::Unknown as i32;
+    notify_error(code, msg, false);
+    write_error(out_error, &cap_message(translate(code, msg)));
+}
+
+/// 同 `set_error`，但先用 `vimo_ffi_free_string` 释放 `*out_error` 中已有的消息
+///
+/// **所有权警告**：只有确定 `*out_error` 里是本库写出、且调用方既没有释放也没有
+/// 转交他人的消息时才能使用。C 侧未初始化的局部变量、已经释放过的指针、
+/// 或其它分配器分配的字符串都会导致释放非法指针或重复释放。拿不准时用 `set_error`。
+///
+/// # Safety
+/// 除 `set_error` 的要求外，`*out_error` 必须为 null，或是本库分配且仍归调用方所有的消息
+pub unsafe fn set_error_safe(out_error: *mut *mut c_char, msg: &str) {
+    if !out_error.is_null() {
+        crate::vimo_ffi_free_string(std::mem::replace(&mut *out_error, ptr::null_mut()));
+    }
+    set_error(out_error, msg);
+}
+
+/// `set_error_fmt` 的栈上缓冲区大小，更长的消息改用堆分配
+const FMT_STACK_BUF_LEN: usize = 256;
+
+/// 先写入栈上缓冲区、放不下时转到 `String` 的格式化目标
+struct StackWriter {
+    buf: [u8; FMT_STACK_BUF_LEN],
+    len: usize,
+    heap: Option<String>,
+}
+
+impl StackWriter {
+    fn as_str(&self) -> &str {
+        match &self.heap {
+            Some(s) => s,
+            // 只会整段追加 `&str`，边界总在字符边界上
+            None => std::str::from_utf8(&self.buf[..self.len]).unwrap_or_default(),
+        }
     }
-    if let Ok(c_string) = CString::new(msg) {
-        *out_error = c_string.into_raw();
+}
+
+impl std::fmt::Write for StackWriter {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        if let Some(heap) = &mut self.heap {
+            heap.push_str(s);
+        } else if let Some(dst) = self.buf.get_mut(self.len..self.len + s.len()) {
+            dst.copy_from_slice(s.as_bytes());
+            self.len += s.len();
+        } else {
+            let mut heap = String::with_capacity((self.len + s.len()) * 2);
+            heap.push_str(self.as_str());
+            heap.push_str(s);
+            self.heap = Some(heap);
+        }
+        Ok(())
     }
 }
 
-/// 设置 FFI 错误输出指针（从 Error trait）
+/// 设置 FFI 错误输出指针（从格式化参数）
+///
+/// 同 `set_error`，但不需要先 `format!` 出一个 `String`：
+/// 不超过 256 字节的消息在栈上格式化，只有写出的 C 字符串本身一次分配。
+/// 通常通过 `set_error!` 宏调用。
 ///
 /// # Safety
 /// 同 `set_error`
-pub unsafe fn set_error_from<E: std::fmt::Display>(out_error: *mut *mut c_char, err: &E) {
-    set_error(out_error, &err.to_string());
+pub unsafe fn set_error_fmt(out_error: *mut *mut c_char, args: std::fmt::Arguments<'_>) {
+    if let Some(msg) = args.as_str() {
+        return set_error(out_error, msg);
+    }
+    let mut writer = StackWriter {
+        buf: [0; FMT_STACK_BUF_LEN],
+        len: 0,
+        heap: None,
+    };
+    // 写入目标本身不会失败，只有某个参数的 Display 实现报错时才会到这里
+    let _ = std::fmt::Write::write_fmt(&mut writer, args);
+    set_error(out_error, writer.as_str());
 }
 
-/// 检查指针非空，否则返回错误
+/// 以 `format!` 的语法设置 FFI 错误输出指针，见 `set_error_fmt`
+///
+/// `out_error` 只求值一次。
 ///
 /// # 示例
 ///
 /// ```rust,ignore
-/// fn my_ffi(ptr: *const c_char, out_error: *mut *mut c_char) -> bool {
-///     if let Err(e) = check_not_null(ptr) {
-///         unsafe { set_error(out_error, &e.to_string()) };
-///         return false;
-///     }
-///     // ...
-/// }
+/// set_error!(out_error, "invalid offset {} for buffer of {} bytes", offset, len);
 /// ```
-pub fn check_not_null<T>(ptr: *const T) -> Result<(), FfiError> {
-    if ptr.is_null() {
-        Err(FfiError::NullPointer)
-    } else {
-        Ok(())
+#[macro_export]
+macro_rules! set_error {
+    ($out_error:expr, $($arg:tt)+) => {{
+        let out_error: *mut *mut ::std::ffi::c_char = $out_error;
+        // match 让参数中的临时值活到调用结束
+        match ::std::format_args!($($arg)+) {
+            args => unsafe { $crate::set_error_fmt(out_error, args) },
+        }
+    }};
+}
+
+/// 原样写出错误消息，不经过翻译（消息已经翻译或渲染过的路径使用）
+///
+/// # Safety
+/// 同 `set_error`
+pub(crate) unsafe fn write_error(out_error: *mut *mut c_char, msg: &str) {
+    if out_error.is_null() {
+        return;
     }
+    if !(*out_error).is_null() {
+        warn_overwrite();
+    }
+    *out_error = try_sanitized_cstring(msg).map_or(oom_message_ptr(), CString::into_raw);
 }
 
-/// 检查多个指针非空
+/// `custom_with_code` 收到保留或未登记的错误码时提示
+fn warn_custom_code(code: i32, problem: &str) {
+    let message = format!("custom error code {code} {problem}");
+    #[cfg(feature = "tracing")]
+    tracing::warn!(code, "{}", message);
+    #[cfg(feature = "log")]
+    log::warn!("[vimo-ffi] {}", message);
+    notify_error(FfiErrorCode::Unknown as i32, &message, false);
+}
+
+/// 覆盖非 null 的 `*out_error` 时通知错误观察者的消息
+pub const OVERWRITE_WARNING_MESSAGE: &str =
+    "out_error was not null, overwriting it leaks the previous message";
+
+/// `*out_error` 已有值时提示调用方：旧消息不会被释放
+///
+/// 通过错误观察者提示（错误码为 `FfiErrorCode::Unknown`），启用 `log` feature 时同时输出警告。
+fn warn_overwrite() {
+    #[cfg(feature = "log")]
+    log::warn!("[vimo-ffi] {}", OVERWRITE_WARNING_MESSAGE);
+    notify_error(FfiErrorCode::Unknown as i32, OVERWRITE_WARNING_MESSAGE, false);
+}
+
+/// 为错误消息分配内存失败时写出的静态消息
+pub static OOM_MESSAGE: &CStr = c"[allocation failure during error reporting]";
+
+/// `OOM_MESSAGE` 作为可写指针，释放函数据此识别并跳过它
+pub(crate) fn oom_message_ptr() -> *mut c_char {
+    OOM_MESSAGE.as_ptr() as *mut c_char
+}
+
+/// 错误消息是否为分配失败时写出的静态 `OOM_MESSAGE`
+///
+/// 静态消息同样可以交给 `vimo_ffi_free_string`（会被跳过），
+/// 调用方只有在需要自行管理内存（如不经释放函数直接丢弃）时才需要区分。
+#[no_mangle]
+pub extern "C" fn vimo_ffi_is_static_error(ptr: *const c_char) -> bool {
+    ptr == OOM_MESSAGE.as_ptr()
+}
+
+/// 将错误信息写入调用者提供的定长缓冲区
+///
+/// 适用于无法回调本库释放字符串的宿主。消息中的 NUL 与 `set_error` 一样转义为 `\0`，
+/// 消息过长时在字符边界处截断，并总是以 NUL 结尾。`buf` 为 null 或 `cap` 为 0 表示调用者不需要消息，不写入任何内容。
+///
+/// 返回完整消息（转义后）所需的缓冲区大小（含结尾 NUL），调用者可据此判断是否被截断。
+///
+/// # Safety
+/// `buf` 为 null，或指向至少 `cap` 字节的可写内存
 ///
 /// # 示例
 ///
 /// ```rust,ignore
-/// check_all_not_null(&[ptr1 as *const _, ptr2 as *const _])?;
+/// let mut buf = [0 as c_char; 64];
+/// let required = unsafe { set_error_buf(buf.as_mut_ptr(), buf.len(), "something went wrong") };
+/// let truncated = required > buf.len();
 /// ```
-pub fn check_all_not_null(ptrs: &[*const std::ffi::c_void]) -> Result<(), FfiError> {
-    for ptr in ptrs {
-        if ptr.is_null() {
-            return Err(FfiError::NullPointer);
-        }
+pub unsafe fn set_error_buf(buf: *mut c_char, cap: usize, msg: &str) -> usize {
+    let escaped;
+    let msg = if msg.contains('\0') {
+        escaped = msg.replace('\0', "\\0");
+        &escaped
+    } else {
+        msg
+    };
+    let required = msg.len() + 1;
+    if buf.is_null() || cap == 0 {
+        return required;
     }
-    Ok(())
+    let truncated = truncate_at_char_boundary(msg, cap - 1);
+    ptr::copy_nonoverlapping(truncated.as_ptr(), buf.cast::<u8>(), truncated.len());
+    *buf.add(truncated.len()) = 0;
+    required
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::ptr;
+/// 设置 FFI 错误输出指针（从 Error trait）
+///
+/// `ErrorVerbosity::Verbose` 时，`FfiError` 的消息后附加其 `Debug` 表示；
+/// 其它错误类型没有 `Debug` 约束，只输出 `Display` 文本。
+///
+/// # Safety
+/// 同 `set_error`
+pub unsafe fn set_error_from<E>(out_error: *mut *mut c_char, err: &E)
+where
+    E: std::fmt::Display + 'static,
+{
+    let code = code_of(err);
+    let msg = message_of(err).into_owned();
+    notify_error(code, &msg, false);
+    let detail = || (err as &dyn Any).downcast_ref::<FfiError>().map(|e| format!("{e:?}"));
+    let msg = with_detail(&msg, detail);
+    write_error(out_error, &cap_message(translate(code, &msg)));
+}
 
-    #[test]
-    fn test_set_error() {
-        let mut error_ptr: *mut c_char = ptr::null_mut();
-        unsafe { set_error(&mut error_ptr, "test error") };
-        assert!(!error_ptr.is_null());
+/// 设置 FFI 错误输出指针（从 `FfiError`）
+///
+/// 同 `set_error_from`，但无字段的错误（如 `NullPointer`）直接复制预先构造的静态 C 字符串，
+/// 只分配一次，不经过 `String` 中转。消息被翻译、脱敏或截断时按普通路径写出。
+///
+/// # Safety
+/// 同 `set_error`
+pub unsafe fn set_ffi_error(out_error: *mut *mut c_char, err: &FfiError) {
+    let verbose = error_verbosity() == ErrorVerbosity::Verbose;
+    let Some(message) = err.static_message().filter(|_| !verbose) else {
+        return set_error_from(out_error, err);
+    };
+    let code = err.code();
+    let text = message.to_str().unwrap_or_default();
+    notify_error(code, text, false);
+    if out_error.is_null() {
+        return;
+    }
+    match cap_message(translate(code, text)) {
+        // 翻译、脱敏和截断都没有改变消息时直接复制静态消息
+        Cow::Borrowed(rendered) if ptr::eq(rendered, text) => {
+            if !(*out_error).is_null() {
+                warn_overwrite();
+            }
+            *out_error = try_copy_cstr(message).map_or(oom_message_ptr(), CString::into_raw);
+        }
+        rendered => write_error(out_error, &rendered),
+    }
+}
+
+/// 复制 C 字符串，分配失败时返回 None
+fn try_copy_cstr(s: &CStr) -> Option<CString> {
+    let mut bytes = Vec::new();
+    bytes.try_reserve_exact(s.count_bytes() + 1).ok()?;
+    bytes.extend_from_slice(s.to_bytes_with_nul());
+    CString::from_vec_with_nul(bytes).ok()
+}
+
+/// 在手写的导出函数中提前返回：`Err` 时通过 `set_error_from` 写出错误并返回 `default`
+///
+/// 用于无法包进闭包的函数（例如失败时要做部分清理）。`Ok` 时求值为其中的值；
+/// `out_error` 只在出错时求值一次。省略 `default` 时返回 `()`。
+/// 不经过 `catch_unwind`，也不记录最近一次错误。
+///
+/// # 示例
+///
+/// ```rust
+/// use std::ffi::c_char;
+/// use vimo_ffi::{cstr_to_str, ffi_try, str_to_cstring, FfiError};
+///
+/// pub extern "C" fn log_line(line: *const c_char, out_error: *mut *mut c_char) {
+///     let line = ffi_try!(unsafe { cstr_to_str(line) }, out_error);
+///     println!("{line}");
+/// }
+///
+/// pub extern "C" fn is_empty(s: *const c_char, out_error: *mut *mut c_char) -> bool {
+///     ffi_try!(unsafe { cstr_to_str(s) }, out_error, false).is_empty()
+/// }
+///
+/// pub extern "C" fn parse_port(s: *const c_char, out_error: *mut *mut c_char) -> i32 {
+///     let s = ffi_try!(unsafe { cstr_to_str(s) }, out_error, -1);
+///     let port: u16 = ffi_try!(s.parse().map_err(FfiError::from), out_error, -1);
+///     port as i32
+/// }
+///
+/// pub extern "C" fn upper(s: *const c_char, out_error: *mut *mut c_char) -> *mut c_char {
+///     let s = ffi_try!(unsafe { cstr_to_str(s) }, out_error, std::ptr::null_mut());
+///     ffi_try!(str_to_cstring(&s.to_uppercase()), out_error, std::ptr::null_mut())
+/// }
+///
+/// let mut error: *mut c_char = std::ptr::null_mut();
+/// assert_eq!(parse_port(c"8080".as_ptr(), &mut error), 8080);
+/// assert_eq!(parse_port(c"http".as_ptr(), &mut error), -1);
+/// assert!(!error.is_null());
+/// # unsafe { vimo_ffi::vimo_ffi_free_string(error) };
+/// # log_line(c"hi".as_ptr(), std::ptr::null_mut());
+/// # assert!(is_empty(c"".as_ptr(), std::ptr::null_mut()));
+/// # unsafe { vimo_ffi::vimo_ffi_free_string(upper(c"a".as_ptr(), std::ptr::null_mut())) };
+/// ```
+#[macro_export]
+macro_rules! ffi_try {
+    ($result:expr, $out_error:expr $(,)?) => {
+        $crate::ffi_try!($result, $out_error, ())
+    };
+    ($result:expr, $out_error:expr, $default:expr $(,)?) => {
+        match $result {
+            ::core::result::Result::Ok(value) => value,
+            ::core::result::Result::Err(err) => {
+                let out_error: *mut *mut ::std::ffi::c_char = $out_error;
+                unsafe { $crate::set_error_from(out_error, &err) };
+                return $default;
+            }
+        }
+    };
+}
+
+/// 无条件失败：写出格式化的 `FfiError::Custom` 错误并返回 `default`
+///
+/// 格式参数同 `format!`，`out_error` 只求值一次。
+///
+/// # 示例
+///
+/// ```rust
+/// use std::ffi::c_char;
+/// use vimo_ffi::ffi_bail;
+///
+/// pub extern "C" fn open_slot(slot: i32, out_error: *mut *mut c_char) -> bool {
+///     if !(0..4).contains(&slot) {
+///         ffi_bail!(out_error, false, "no such slot: {}", slot);
+///     }
+///     true
+/// }
+///
+/// let mut error: *mut c_char = std::ptr::null_mut();
+/// assert!(!open_slot(7, &mut error));
+/// let msg = unsafe { std::ffi::CStr::from_ptr(error) };
+/// assert_eq!(msg.to_str().unwrap(), "no such slot: 7");
+/// # unsafe { vimo_ffi::vimo_ffi_free_string(error) };
+/// ```
+#[macro_export]
+macro_rules! ffi_bail {
+    ($out_error:expr, $default:expr, $($arg:tt)+) => {{
+        let out_error: *mut *mut ::std::ffi::c_char = $out_error;
+        let err = $crate::FfiError::custom(::std::format!($($arg)+));
+        unsafe { $crate::set_error_from(out_error, &err) };
+        return $default;
+    }};
+}
+
+/// `set_error_chain` 默认的错误链分隔符
+pub const ERROR_CHAIN_SEPARATOR: &str = ": caused by: ";
+
+/// 错误链最多展开的层数，防止 `source()` 成环时无限循环
+pub const ERROR_CHAIN_MAX_DEPTH: usize = 8;
+
+/// 将错误及其 `source()` 链渲染为一行文本
+///
+/// 最多渲染 `max_depth` 层，超出部分以 `...` 表示。
+/// 任一层的 `Display` 或 `source()` panic 时整条消息为 `FORMAT_PANIC_MESSAGE`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// // "failed to load config: caused by: permission denied (os error 13)"
+/// let msg = format_error_chain(&err, ERROR_CHAIN_SEPARATOR, ERROR_CHAIN_MAX_DEPTH);
+/// ```
+pub fn format_error_chain(
+    err: &(dyn std::error::Error + '_),
+    separator: &str,
+    max_depth: usize,
+) -> String {
+    format_guarded(|| render_error_chain(err, separator, max_depth))
+}
+
+fn render_error_chain(
+    err: &(dyn std::error::Error + '_),
+    separator: &str,
+    max_depth: usize,
+) -> String {
+    let mut msg = err.to_string();
+    let mut source = err.source();
+    let mut depth = 1;
+    while let Some(cause) = source {
+        msg.push_str(separator);
+        if depth >= max_depth {
+            msg.push_str("...");
+            break;
+        }
+        msg.push_str(&cause.to_string());
+        source = cause.source();
+        depth += 1;
+    }
+    msg
+}
+
+/// 设置 FFI 错误输出指针（包含完整的 `source()` 错误链）
+///
+/// `ErrorVerbosity::Verbose` 时，消息后附加错误的 `Debug` 表示。
+///
+/// # Safety
+/// 同 `set_error`
+pub unsafe fn set_error_chain<E: std::error::Error>(out_error: *mut *mut c_char, err: &E) {
+    set_error_chain_with(out_error, err, ERROR_CHAIN_SEPARATOR);
+}
+
+/// 同 `set_error_chain`，使用自定义分隔符
+///
+/// # Safety
+/// 同 `set_error`
+pub unsafe fn set_error_chain_with<E: std::error::Error>(
+    out_error: *mut *mut c_char,
+    err: &E,
+    separator: &str,
+) {
+    let msg = format_error_chain(err, separator, ERROR_CHAIN_MAX_DEPTH);
+    let code = FfiErrorCode::Unknown as i32;
+    notify_error(code, &msg, false);
+    let msg = with_detail(&msg, || Some(format_guarded(|| format!("{err:?}"))));
+    write_error(out_error, &cap_message(translate(code, &msg)));
+}
+
+/// 将任意错误的 `Result` 转换为 `FfiError`，消息包含完整的 `source()` 错误链
+///
+/// 错误本身就是 `FfiError` 时原样返回，保留错误码；其它错误转换为 `Custom`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// ffi_boundary(out_error, false, || {
+///     let config = map_err_to_ffi(std::fs::read_to_string(path))?;
+///     Ok(true)
+/// })
+/// ```
+pub fn map_err_to_ffi<T, E>(result: Result<T, E>) -> Result<T, FfiError>
+where
+    E: std::error::Error + 'static,
+{
+    result.map_err(|err| match (&err as &dyn Any).downcast_ref::<FfiError>() {
+        Some(e) => e.clone(),
+        None => {
+            let msg = format_error_chain(&err, ERROR_CHAIN_SEPARATOR, ERROR_CHAIN_MAX_DEPTH);
+            FfiError::Custom(msg)
+        }
+    })
+}
+
+/// 结构化的 FFI 错误，同时携带错误码、消息和上下文
+///
+/// 由 `set_error_struct` 分配，必须由调用者使用 `vimo_ffi_free_error` 释放。
+/// `message` 总是非 null，`context` 可能为 null。
+#[repr(C)]
+#[derive(Debug)]
+pub struct VimoError {
+    /// 稳定错误码，取值见 `FfiErrorCode`
+    pub code: i32,
+    /// 严重级别，取值见 `Severity`
+    pub severity: i32,
+    /// 错误消息
+    pub message: *mut c_char,
+    /// 出错时的上下文信息（如正在处理的参数），没有时为 null
+    pub context: *mut c_char,
+}
+
+/// 设置结构化错误输出指针
+///
+/// # Safety
+/// `out` 必须是有效的可写指针，或者 null（会被忽略）
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// unsafe { set_error_struct(out_error, &FfiError::NullPointer, Some("argument 'title'")) };
+/// ```
+pub unsafe fn set_error_struct(out: *mut *mut VimoError, err: &FfiError, context: Option<&str>) {
+    write_error_struct(out, err.code(), err.severity(), &err.to_string(), context);
+}
+
+/// 按错误码和消息写入结构化错误（panic 等没有 `FfiError` 的路径也可使用）
+///
+/// # Safety
+/// 同 `set_error_struct`
+pub(crate) unsafe fn write_error_struct(
+    out: *mut *mut VimoError,
+    code: i32,
+    severity: Severity,
+    message: &str,
+    context: Option<&str>,
+) {
+    if out.is_null() {
+        return;
+    }
+    let to_raw = |s: &str| sanitized_cstring(s).into_raw();
+    let message = try_sanitized_cstring(&cap_message(translate(code, message)));
+    let error = Box::new(VimoError {
+        code,
+        severity: severity as i32,
+        message: message.map_or(oom_message_ptr(), CString::into_raw),
+        context: context.map_or(ptr::null_mut(), to_raw),
+    });
+    *out = Box::into_raw(error);
+}
+
+/// 释放由本库分配的结构化错误（连同其中的字符串）
+///
+/// # Safety
+/// 指针必须是由 `set_error_struct` 或结构化边界函数写出的，或者 null（不做任何事）
+#[no_mangle]
+pub unsafe extern "C" fn vimo_ffi_free_error(err: *mut VimoError) {
+    if err.is_null() {
+        return;
+    }
+    let error = Box::from_raw(err);
+    if !error.message.is_null() && error.message != oom_message_ptr() {
+        drop(CString::from_raw(error.message));
+    }
+    if !error.context.is_null() {
+        drop(CString::from_raw(error.context));
+    }
+}
+
+/// 检查指针非空，否则返回错误
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// fn my_ffi(ptr: *const c_char, out_error: *mut *mut c_char) -> bool {
+///     if let Err(e) = check_not_null(ptr) {
+///         unsafe { set_error(out_error, &e.to_string()) };
+///         return false;
+///     }
+///     // ...
+/// }
+/// ```
+pub fn check_not_null<T>(ptr: *const T) -> Result<(), FfiError> {
+    if ptr.is_null() {
+        Err(FfiError::NullPointer)
+    } else {
+        Ok(())
+    }
+}
+
+/// 检查指针满足 `T` 的对齐要求
+///
+/// null 指针视为对齐（配合 `check_not_null` 使用，或直接用 `check_valid_ptr`）。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// check_aligned(values as *const u64)?;
+/// ```
+pub fn check_aligned<T>(ptr: *const T) -> Result<(), FfiError> {
+    let required = std::mem::align_of::<T>();
+    let address_low_bits = ptr as usize & (required - 1);
+    if address_low_bits == 0 {
+        Ok(())
+    } else {
+        Err(FfiError::MisalignedPointer {
+            required,
+            address_low_bits,
+        })
+    }
+}
+
+/// 检查指针非空且满足 `T` 的对齐要求
+pub fn check_valid_ptr<T>(ptr: *const T) -> Result<(), FfiError> {
+    check_not_null(ptr)?;
+    check_aligned(ptr)
+}
+
+/// 检查指针非空，错误消息带上参数名
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// // Err(NullArgument("config")): "null pointer: argument 'config'"
+/// check_not_null_named(config, "config")?;
+/// ```
+pub fn check_not_null_named<T>(ptr: *const T, name: &str) -> Result<(), FfiError> {
+    if ptr.is_null() {
+        Err(FfiError::NullArgument(name.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+/// 同 `check_not_null`，接受可变指针
+pub fn check_not_null_mut<T>(ptr: *mut T) -> Result<(), FfiError> {
+    check_not_null(ptr as *const T)
+}
+
+/// 检查 trait 对象的胖指针非空
+///
+/// 胖指针由数据指针和 vtable 指针两个字组成，这里只检查数据指针：
+/// vtable 指针由编译器在 unsizing 转换时填入，总是指向有效的 vtable，
+/// 即使数据指针为 null（如 `ptr::null::<T>() as *const dyn Any`）也是如此，
+/// 因此它非空并不能说明指针可以解引用。
+pub fn check_not_null_dyn(ptr: *const dyn std::any::Any) -> Result<(), FfiError> {
+    check_not_null(ptr as *const ())
+}
+
+/// 检查 `(ptr, len)` 形式的字节切片参数
+///
+/// 与 `u8ptr_to_str_n` 一致，`ptr` 为 null 时即使 `len` 为 0 也返回 `NullPointer`
+/// （`slice::from_raw_parts` 不接受 null）；`len` 超过 `isize::MAX` 时返回 `OutOfRange`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// check_slice_not_null(data, len)?;
+/// let bytes = unsafe { std::slice::from_raw_parts(data, len) };
+/// ```
+pub fn check_slice_not_null(ptr: *const u8, len: usize) -> Result<(), FfiError> {
+    check_not_null(ptr)?;
+    if len > isize::MAX as usize {
+        return Err(FfiError::OutOfRange(format!(
+            "slice length ({len}) exceeds isize::MAX"
+        )));
+    }
+    Ok(())
+}
+
+/// 可判空的裸指针，供 `check_ptrs!` 同时接受 `*const T` 和 `*mut T`
+pub trait NullablePtr {
+    fn is_null_ptr(&self) -> bool;
+}
+
+impl<T: ?Sized> NullablePtr for *const T {
+    fn is_null_ptr(&self) -> bool {
+        self.is_null()
+    }
+}
+
+impl<T: ?Sized> NullablePtr for *mut T {
+    fn is_null_ptr(&self) -> bool {
+        self.is_null()
+    }
+}
+
+/// 检查多个指针参数非空，错误中带上第一个为 null 的参数名
+///
+/// 参数必须是变量名，名称由宏自动取得；`*const T` 和 `*mut T` 可以混用，无需转换。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// // Err(NullArgument("out_buf")): "null pointer: argument 'out_buf'"
+/// check_ptrs!(config, out_buf, callback)?;
+/// ```
+#[macro_export]
+macro_rules! check_ptrs {
+    ($($ptr:ident),+ $(,)?) => {{
+        let mut result: ::core::result::Result<(), $crate::FfiError> = Ok(());
+        $(
+            if result.is_ok() && $crate::NullablePtr::is_null_ptr(&$ptr) {
+                result = Err($crate::FfiError::NullArgument(stringify!($ptr).to_string()));
+            }
+        )+
+        result
+    }};
+}
+
+/// 检查多个指针非空
+///
+/// 需要知道是哪个参数为 null 时使用 `check_ptrs!`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// check_all_not_null(&[ptr1 as *const _, ptr2 as *const _])?;
+/// ```
+pub fn check_all_not_null(ptrs: &[*const std::ffi::c_void]) -> Result<(), FfiError> {
+    for ptr in ptrs {
+        if ptr.is_null() {
+            return Err(FfiError::NullPointer);
+        }
+    }
+    Ok(())
+}
+
+/// 检查长度不超过上限（`len == max` 允许）
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// // Err(OutOfRange): "length of 'items' (12000) exceeds maximum (4096)"
+/// check_len(count, 4096, "items")?;
+/// ```
+pub fn check_len(len: usize, max: usize, what: &str) -> Result<(), FfiError> {
+    if len > max {
+        Err(FfiError::OutOfRange(format!(
+            "length of '{what}' ({len}) exceeds maximum ({max})"
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// 检查值落在闭区间内
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// // Err(OutOfRange): "'quality' (101) out of range [0, 100]"
+/// check_range(quality, 0..=100, "quality")?;
+/// ```
+pub fn check_range<T: PartialOrd + std::fmt::Display>(
+    value: T,
+    range: RangeInclusive<T>,
+    what: &str,
+) -> Result<(), FfiError> {
+    if range.contains(&value) {
+        Ok(())
+    } else {
+        Err(FfiError::OutOfRange(format!(
+            "'{what}' ({value}) out of range [{}, {}]",
+            range.start(),
+            range.end()
+        )))
+    }
+}
+
+/// 检查长度非零
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// // Err(OutOfRange): "length of 'items' must be nonzero"
+/// check_nonzero(count, "items")?;
+/// ```
+pub fn check_nonzero(len: usize, what: &str) -> Result<(), FfiError> {
+    if len == 0 {
+        Err(FfiError::OutOfRange(format!("length of '{what}' must be nonzero")))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_alloc::live_allocations;
+
+    fn assert_ffi_error_bounds<E: Clone + Send + Sync + std::error::Error + 'static>() {}
+
+    #[test]
+    fn test_ffi_error_bounds() {
+        // 错误值会跨线程传递（线程池、异步回调），新增变体不能破坏这些约束
+        assert_ffi_error_bounds::<FfiError>();
+    }
+
+    #[test]
+    fn test_set_error() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        unsafe { set_error(&mut error_ptr, "test error") };
+        assert!(!error_ptr.is_null());
 
         let error_str = unsafe { CString::from_raw(error_ptr) };
         assert_eq!(error_str.to_str().unwrap(), "test error");
     }
 
+    #[test]
+    fn test_set_error_with_nul() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        unsafe { set_error(&mut error_ptr, "bad byte \0 in header") };
+        assert!(!error_ptr.is_null());
+        let msg = unsafe { CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "bad byte \\0 in header");
+    }
+
+    #[test]
+    fn test_free_oom_message() {
+        // 静态的 OOM 消息可以安全地交给释放函数
+        unsafe { crate::vimo_ffi_free_string(oom_message_ptr()) };
+        let err = Box::into_raw(Box::new(VimoError {
+            code: FfiErrorCode::OutOfMemory as i32,
+            severity: Severity::Fatal as i32,
+            message: oom_message_ptr(),
+            context: ptr::null_mut(),
+        }));
+        unsafe { vimo_ffi_free_error(err) };
+        assert_eq!(OOM_MESSAGE.to_str(), Ok("[allocation failure during error reporting]"));
+
+        assert!(vimo_ffi_is_static_error(oom_message_ptr()));
+        let owned = sanitized_cstring("[allocation failure during error reporting]");
+        assert!(!vimo_ffi_is_static_error(owned.as_ptr()));
+        assert!(!vimo_ffi_is_static_error(ptr::null()));
+    }
+
+    #[test]
+    fn test_set_error_safe_frees_previous() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let before = live_allocations();
+        unsafe { set_error_safe(&mut error_ptr, "first") };
+        unsafe { set_error_safe(&mut error_ptr, "second") };
+        // 第一条消息已经释放，只剩第二条
+        assert_eq!(live_allocations(), before + 1);
+        let msg = unsafe { CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "second");
+
+        unsafe { set_error_safe(ptr::null_mut(), "ignored") };
+    }
+
     #[test]
     fn test_set_error_null_out() {
         // 不应该 panic
@@ -119,6 +1633,633 @@ mod tests {
         assert!(check_not_null(ptr::null::<i32>()).is_err());
     }
 
+    #[test]
+    fn test_check_aligned() {
+        let buf = [0u64; 2];
+        let base = buf.as_ptr() as *const u8;
+        let aligned = base as *const u32;
+        let misaligned = unsafe { base.add(1) } as *const u32;
+
+        assert_eq!(check_aligned(aligned), Ok(()));
+        let err = check_aligned(misaligned).unwrap_err();
+        assert_eq!(
+            err,
+            FfiError::MisalignedPointer {
+                required: 4,
+                address_low_bits: 1
+            }
+        );
+        assert!(err.to_string().contains("requires 4-byte alignment"));
+        assert_eq!(err.code(), FfiErrorCode::MisalignedPointer as i32);
+
+        assert_eq!(check_valid_ptr(aligned), Ok(()));
+        assert_eq!(check_valid_ptr(misaligned), Err(err));
+        assert_eq!(check_valid_ptr(ptr::null::<u32>()), Err(FfiError::NullPointer));
+        // u8 没有对齐要求
+        assert_eq!(check_aligned(unsafe { base.add(1) }), Ok(()));
+    }
+
+    #[test]
+    fn test_check_not_null_named() {
+        let err = check_not_null_named(ptr::null::<u8>(), "config").unwrap_err();
+        assert_eq!(err, FfiError::NullArgument("config".into()));
+        assert_eq!(err.to_string(), "null pointer: argument 'config'");
+        assert_eq!(err.code(), FfiErrorCode::NullPointer as i32);
+
+        let mut val = 1u8;
+        assert!(check_not_null_named(&val as *const u8, "config").is_ok());
+        assert!(check_not_null_mut(&mut val as *mut u8).is_ok());
+        assert_eq!(check_not_null_mut(ptr::null_mut::<u8>()), Err(FfiError::NullPointer));
+    }
+
+    #[test]
+    fn test_check_fat_pointers() {
+        let val = 42i32;
+        assert_eq!(check_not_null_dyn(&val as &dyn Any as *const dyn Any), Ok(()));
+        // vtable 指针有效，数据指针为 null
+        let null_dyn = ptr::null::<i32>() as *const dyn Any;
+        assert_eq!(check_not_null_dyn(null_dyn), Err(FfiError::NullPointer));
+
+        let bytes = [1u8, 2, 3];
+        assert_eq!(check_slice_not_null(bytes.as_ptr(), bytes.len()), Ok(()));
+        assert_eq!(check_slice_not_null(bytes.as_ptr(), 0), Ok(()));
+        assert_eq!(check_slice_not_null(ptr::null(), 0), Err(FfiError::NullPointer));
+        let err = check_slice_not_null(bytes.as_ptr(), usize::MAX).unwrap_err();
+        assert_eq!(err.code(), FfiErrorCode::OutOfRange as i32);
+    }
+
+    #[test]
+    fn test_check_ptrs_macro() {
+        let mut buf = [0u8; 4];
+        let config: *const i32 = &7;
+        let out_buf: *mut u8 = buf.as_mut_ptr();
+        let callback: *const std::ffi::c_void = ptr::null();
+        assert_eq!(
+            crate::check_ptrs!(config, out_buf, callback),
+            Err(FfiError::NullArgument("callback".into()))
+        );
+        assert_eq!(crate::check_ptrs!(config, out_buf), Ok(()));
+
+        let config: *const i32 = ptr::null();
+        let err = crate::check_ptrs!(config, out_buf, callback).unwrap_err();
+        assert_eq!(err.to_string(), "null pointer: argument 'config'");
+    }
+
+    #[test]
+    fn test_check_len() {
+        assert_eq!(check_len(0, 4096, "items"), Ok(()));
+        assert_eq!(check_len(4096, 4096, "items"), Ok(()));
+        let err = check_len(4097, 4096, "items").unwrap_err();
+        assert_eq!(err.code(), FfiErrorCode::OutOfRange as i32);
+        assert_eq!(
+            check_len(12000, 4096, "items").unwrap_err().to_string(),
+            "length of 'items' (12000) exceeds maximum (4096)"
+        );
+    }
+
+    #[test]
+    fn test_check_range() {
+        assert_eq!(check_range(0, 0..=100, "quality"), Ok(()));
+        assert_eq!(check_range(100, 0..=100, "quality"), Ok(()));
+        assert_eq!(
+            check_range(101, 0..=100, "quality").unwrap_err().to_string(),
+            "'quality' (101) out of range [0, 100]"
+        );
+        assert_eq!(
+            check_range(-1, 0..=100, "quality").unwrap_err().code(),
+            FfiErrorCode::OutOfRange as i32
+        );
+        assert_eq!(check_range(0.5, 0.0..=1.0, "ratio"), Ok(()));
+        // NaN 不落在任何区间内
+        assert!(check_range(f64::NAN, 0.0..=1.0, "ratio").is_err());
+    }
+
+    #[test]
+    fn test_check_nonzero() {
+        assert_eq!(check_nonzero(1, "items"), Ok(()));
+        assert_eq!(
+            check_nonzero(0, "items"),
+            Err(FfiError::OutOfRange("length of 'items' must be nonzero".into()))
+        );
+    }
+
+    #[test]
+    fn test_check_helpers_compose() {
+        fn validate(len: usize, quality: i32) -> Result<(), FfiError> {
+            check_nonzero(len, "items")?;
+            check_len(len, 16, "items")?;
+            check_range(quality, 0..=100, "quality")?;
+            Ok(())
+        }
+        assert_eq!(validate(16, 100), Ok(()));
+        assert_eq!(validate(0, 50).unwrap_err().code(), FfiErrorCode::OutOfRange as i32);
+        assert!(validate(17, 50).is_err());
+        assert!(validate(1, 101).is_err());
+    }
+
+    fn take_error(ptr: *mut c_char) -> String {
+        assert!(!ptr.is_null());
+        unsafe { CString::from_raw(ptr) }.into_string().unwrap()
+    }
+
+    #[test]
+    fn test_ffi_try_return_types() {
+        fn unit(input: Result<i32, FfiError>, out_error: *mut *mut c_char, out: &mut i32) {
+            *out = crate::ffi_try!(input, out_error);
+        }
+        fn boolean(input: Result<i32, FfiError>, out_error: *mut *mut c_char) -> bool {
+            crate::ffi_try!(input, out_error, false) > 0
+        }
+        fn int(input: Result<i32, FfiError>, out_error: *mut *mut c_char) -> i32 {
+            crate::ffi_try!(input, out_error, -1) * 2
+        }
+        fn pointer(input: Result<i32, FfiError>, out_error: *mut *mut c_char) -> *const i32 {
+            let _ = crate::ffi_try!(input, out_error, ptr::null());
+            &7
+        }
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let mut out = 0;
+        unit(Ok(5), &mut error_ptr, &mut out);
+        assert_eq!(out, 5);
+        assert!(error_ptr.is_null());
+        unit(Err(FfiError::InvalidUtf8), &mut error_ptr, &mut out);
+        assert_eq!(out, 5);
+        assert_eq!(take_error(error_ptr), "invalid UTF-8 string");
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        assert!(boolean(Ok(1), &mut error_ptr));
+        assert!(!boolean(Err(FfiError::NullPointer), &mut error_ptr));
+        assert_eq!(take_error(error_ptr), "null pointer");
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        assert_eq!(int(Ok(21), &mut error_ptr), 42);
+        assert_eq!(int(Err(FfiError::custom("bad")), &mut error_ptr), -1);
+        assert_eq!(take_error(error_ptr), "bad");
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        assert!(!pointer(Ok(0), &mut error_ptr).is_null());
+        assert!(pointer(Err(FfiError::NullPointer), &mut error_ptr).is_null());
+        assert_eq!(take_error(error_ptr), "null pointer");
+    }
+
+    #[test]
+    fn test_ffi_try_evaluates_out_error_once() {
+        fn run(input: Result<(), String>, out_error: &mut *mut c_char, evals: &mut u32) -> bool {
+            crate::ffi_try!(
+                input,
+                {
+                    *evals += 1;
+                    out_error as *mut *mut c_char
+                },
+                false
+            );
+            true
+        }
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let mut evals = 0;
+        assert!(run(Ok(()), &mut error_ptr, &mut evals));
+        assert_eq!(evals, 0);
+        assert!(!run(Err("failed".into()), &mut error_ptr, &mut evals));
+        assert_eq!(evals, 1);
+        assert_eq!(take_error(error_ptr), "failed");
+    }
+
+    #[test]
+    fn test_ffi_bail() {
+        fn bail(slot: i32, out_error: &mut *mut c_char, evals: &mut u32) -> *mut u8 {
+            crate::ffi_bail!(
+                {
+                    *evals += 1;
+                    out_error as *mut *mut c_char
+                },
+                ptr::null_mut(),
+                "no such slot: {}",
+                slot
+            );
+        }
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let mut evals = 0;
+        assert!(bail(7, &mut error_ptr, &mut evals).is_null());
+        assert_eq!(evals, 1);
+        assert_eq!(take_error(error_ptr), "no such slot: 7");
+    }
+
+    #[test]
+    fn test_set_error_fmt() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        unsafe { set_error_fmt(&mut error_ptr, format_args!("error at {}: {:?}", 42, "key")) };
+        assert_eq!(take_error(error_ptr), "error at 42: \"key\"");
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        crate::set_error!(&mut error_ptr, "offset {} out of {}", 7, 3);
+        assert_eq!(take_error(error_ptr), "offset 7 out of 3");
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        crate::set_error!(&mut error_ptr, "plain literal");
+        assert_eq!(take_error(error_ptr), "plain literal");
+
+        // 跨越栈缓冲区边界的多字节字符和长消息
+        let long = "é".repeat(200);
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        crate::set_error!(&mut error_ptr, "{}{}", "x", long);
+        assert_eq!(take_error(error_ptr), format!("x{long}"));
+
+        crate::set_error!(ptr::null_mut(), "ignored {}", 1);
+    }
+
+    #[test]
+    fn test_set_error_fmt_allocations() {
+        let count = |f: &dyn Fn(*mut *mut c_char)| {
+            let mut error_ptr: *mut c_char = ptr::null_mut();
+            let before = crate::test_alloc::allocation_count();
+            f(&mut error_ptr);
+            let allocations = crate::test_alloc::allocation_count() - before;
+            take_error(error_ptr);
+            allocations
+        };
+        let short = count(&|out| crate::set_error!(out, "error at {}", 42));
+        let formatted = count(&|out| unsafe { set_error(out, &format!("error at {}", 42)) });
+        let long = count(&|out| crate::set_error!(out, "{}", "x".repeat(300).as_str()));
+        assert_eq!(short, 1);
+        assert_eq!(formatted, 2);
+        // 超出栈缓冲区：格式化用的 String 加上输出本身
+        assert_eq!(long, 3);
+    }
+
+    #[test]
+    fn test_invalid_utf8_with_context() {
+        let mut window = [0u8; 16];
+        window[..11].copy_from_slice(b"new titl\xe9!?");
+        let err = FfiError::invalid_utf8_with_context(17, window);
+        assert_eq!(err.code(), FfiErrorCode::InvalidUtf8 as i32);
+        assert_eq!(err.to_string(), "invalid UTF-8 string at byte 17");
+        assert_eq!(
+            format!("{err:?}"),
+            "InvalidUtf8At { byte_offset: 17, context: \
+             6e 65 77 20 74 69 74 6c [e9] 21 3f 00 00 00 00 00 }"
+        );
+
+        // 偏移不足 8 时窗口从开头开始
+        let mut window = [0u8; 16];
+        window[..3].copy_from_slice(b"a\xffb");
+        let err = FfiError::invalid_utf8_with_context(1, window);
+        assert!(format!("{err:?}").contains("context: 61 [ff] 62 00"));
+    }
+
+    #[test]
+    fn test_static_messages_match_display() {
+        let unit = [
+            FfiError::NullPointer,
+            FfiError::InvalidUtf8,
+            FfiError::StringContainsNull,
+            FfiError::OutOfMemory,
+            FfiError::StackOverflow,
+            FfiError::Timeout,
+            FfiError::Cancelled,
+        ];
+        for err in unit {
+            let message = err.static_message().unwrap();
+            assert_eq!(message.to_str().unwrap(), err.to_string());
+        }
+        assert_eq!(FfiError::custom("x").static_message(), None);
+        assert_eq!(FfiError::NullPointer.context("arg").static_message(), None);
+    }
+
+    #[test]
+    fn test_set_ffi_error_matches_set_error_from() {
+        let errors = [
+            FfiError::NullPointer,
+            FfiError::InvalidUtf8,
+            FfiError::StringContainsNull,
+            FfiError::OutOfMemory,
+            FfiError::NotFound("key".into()),
+            FfiError::NullPointer.context("argument 'title'"),
+        ];
+        for err in errors {
+            let mut fast: *mut c_char = ptr::null_mut();
+            let mut slow: *mut c_char = ptr::null_mut();
+            unsafe { set_ffi_error(&mut fast, &err) };
+            unsafe { set_error_from(&mut slow, &err) };
+            assert_eq!(take_error(fast), take_error(slow));
+        }
+        unsafe { set_ffi_error(ptr::null_mut(), &FfiError::NullPointer) };
+    }
+
+    #[test]
+    fn test_set_ffi_error_single_allocation() {
+        let count = |f: &dyn Fn(*mut *mut c_char)| {
+            let mut error_ptr: *mut c_char = ptr::null_mut();
+            let before = crate::test_alloc::allocation_count();
+            f(&mut error_ptr);
+            let allocations = crate::test_alloc::allocation_count() - before;
+            take_error(error_ptr);
+            allocations
+        };
+        let set_from = count(&|out| unsafe { set_error_from(out, &FfiError::NullPointer) });
+        let specialized = count(&|out| unsafe { set_ffi_error(out, &FfiError::NullPointer) });
+        // set_error_from 先格式化为 String 再复制；静态消息只需要输出本身这一次分配
+        assert_eq!(set_from, 2);
+        assert_eq!(specialized, 1);
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(FfiError::Timeout.is_transient());
+        assert!(FfiError::Unavailable("busy".into()).is_transient());
+        assert!(FfiError::Timeout.context("connecting").is_transient());
+        assert!(FfiError::Timeout.with_severity(Severity::Warning).is_transient());
+        assert!(!FfiError::NullPointer.is_transient());
+        assert!(!FfiError::NotFound("key".into()).is_transient());
+        assert!(!FfiError::custom_with_code(1234, "app").is_transient());
+
+        assert!(vimo_ffi_error_is_transient(FfiErrorCode::Timeout as i32));
+        assert!(vimo_ffi_error_is_transient(FfiErrorCode::Unavailable as i32));
+        assert!(!vimo_ffi_error_is_transient(FfiErrorCode::Panic as i32));
+        assert!(!vimo_ffi_error_is_transient(0));
+        assert!(!vimo_ffi_error_is_transient(4321));
+    }
+
+    #[test]
+    fn test_error_domain() {
+        let domain = unsafe { CStr::from_ptr(vimo_ffi_error_domain()) };
+        assert_eq!(domain.to_str().unwrap(), VIMO_FFI_ERROR_DOMAIN);
+        assert_eq!(vimo_ffi_error_domain(), vimo_ffi_error_domain());
+    }
+
+    #[test]
+    fn test_error_code_values() {
+        // 错误码是稳定 ABI，改动这里意味着破坏 C 侧兼容
+        assert_eq!(FfiErrorCode::Ok as i32, 0);
+        assert_eq!(FfiErrorCode::Unknown as i32, -1);
+        assert_eq!(FfiErrorCode::Panic as i32, -1000);
+        assert_eq!(FfiErrorCode::NullPointer as i32, 1);
+        assert_eq!(FfiErrorCode::InvalidUtf8 as i32, 2);
+        assert_eq!(FfiErrorCode::StringContainsNull as i32, 3);
+        assert_eq!(FfiErrorCode::Io as i32, 4);
+        assert_eq!(FfiErrorCode::Parse as i32, 5);
+        assert_eq!(FfiErrorCode::OutOfRange as i32, 6);
+        assert_eq!(FfiErrorCode::NotFound as i32, 7);
+        assert_eq!(FfiErrorCode::OutOfMemory as i32, 8);
+        assert_eq!(FfiErrorCode::MisalignedPointer as i32, 9);
+        assert_eq!(FfiErrorCode::StackOverflow as i32, 10);
+        assert_eq!(FfiErrorCode::Timeout as i32, 11);
+        assert_eq!(FfiErrorCode::Unavailable as i32, 12);
+        assert_eq!(FfiErrorCode::Cancelled as i32, 13);
+        assert_eq!(FfiErrorCode::AlreadyExists as i32, 14);
+        assert_eq!(FfiErrorCode::BufferTooSmall as i32, 15);
+        assert_eq!(FfiErrorCode::ReentrantCall as i32, 16);
+        assert_eq!(FfiErrorCode::PermissionDenied as i32, 17);
+        assert_eq!(FfiErrorCode::Custom as i32, 100);
+
+        assert_eq!(FfiError::NullPointer.code(), 1);
+        assert_eq!(FfiError::InvalidUtf8.code(), 2);
+        assert_eq!(FfiError::StringContainsNull.code(), 3);
+        let io = FfiError::Io {
+            kind: "NotFound".into(),
+            message: "missing".into(),
+        };
+        assert_eq!(io.code(), 4);
+        assert_eq!(FfiError::Parse("x".into()).code(), 5);
+        assert_eq!(FfiError::OutOfRange("x".into()).code(), 6);
+        assert_eq!(FfiError::NotFound("x".into()).code(), 7);
+        assert_eq!(FfiError::OutOfMemory.code(), 8);
+        assert_eq!(FfiError::StackOverflow.code(), 10);
+        assert_eq!(FfiError::Timeout.code(), 11);
+        assert_eq!(FfiError::Unavailable("busy".into()).code(), 12);
+        assert_eq!(FfiError::Cancelled.code(), 13);
+        assert_eq!(FfiError::AlreadyExists("x".into()).code(), 14);
+        assert_eq!(FfiError::BufferTooSmall { required: 64 }.code(), 15);
+        assert_eq!(FfiError::ReentrantCall("vimo_open".into()).code(), 16);
+        assert_eq!(FfiError::PermissionDenied(None).code(), 17);
+        assert_eq!(FfiError::custom("x").code(), 100);
+        assert_eq!(FfiError::custom_with_code(1234, "x").code(), 1234);
+    }
+
+    #[test]
+    fn test_custom_with_code_remaps_reserved_codes() {
+        // 0 会被当作成功、-1000 会被当作 panic，保留的错误码一律按 Custom 处理
+        for code in [0, -1, FfiErrorCode::Panic as i32, FfiErrorCode::NullPointer as i32, 100] {
+            assert_eq!(FfiError::custom_with_code(code, "app"), FfiError::custom("app"));
+        }
+    }
+
+    #[test]
+    fn test_error_code_names() {
+        // 名称和数值一样是稳定约定
+        assert_eq!(FfiErrorCode::NullPointer.name(), "NULL_POINTER");
+        assert_eq!(FfiErrorCode::InvalidUtf8.name(), "INVALID_UTF8");
+        assert_eq!(FfiErrorCode::Panic.name(), "PANIC");
+        assert_eq!(FfiErrorCode::BufferTooSmall.name(), "BUFFER_TOO_SMALL");
+        assert_eq!(FfiErrorCode::ReentrantCall.name(), "REENTRANT_CALL");
+        assert_eq!(FfiErrorCode::PermissionDenied.name(), "PERMISSION_DENIED");
+
+        for code in FfiErrorCode::ALL {
+            assert_eq!(FfiErrorCode::from_i32(code as i32), Some(code));
+            assert_eq!(FfiErrorCode::from_name(code.name()), Some(code), "{}", code.name());
+        }
+        // ALL 覆盖了 from_i32 认识的所有错误码
+        let known = (-1000..=1000).filter(|&c| FfiErrorCode::from_i32(c).is_some()).count();
+        assert_eq!(known, FfiErrorCode::ALL.len());
+
+        assert_eq!(FfiErrorCode::from_name("null_pointer"), None);
+        assert_eq!(FfiErrorCode::from_name("NullPointer"), None);
+        assert_eq!(FfiErrorCode::from_name(""), None);
+    }
+
+    #[cfg(feature = "phf")]
+    #[test]
+    fn test_phf_maps_match_enum() {
+        assert_eq!(ERROR_CODE_NAMES.len(), FfiErrorCode::ALL.len());
+        assert_eq!(ERROR_CODE_VALUES.len(), FfiErrorCode::ALL.len());
+        for code in FfiErrorCode::ALL {
+            assert_eq!(ERROR_CODE_NAMES.get(&(code as i32)), Some(&code.name()));
+            assert_eq!(ERROR_CODE_VALUES.get(code.name()), Some(&(code as i32)));
+        }
+        assert_eq!(ERROR_CODE_NAMES.get(&101), None);
+        assert_eq!(ERROR_CODE_VALUES.get("null_pointer"), None);
+    }
+
+    #[test]
+    fn test_permission_denied() {
+        assert_eq!(FfiError::PermissionDenied(None).to_string(), "permission denied");
+        let err = FfiError::PermissionDenied(Some("socket /run/vimo.sock".into()));
+        assert_eq!(err.to_string(), "permission denied: socket /run/vimo.sock");
+
+        // 失败的系统调用之后立即构造，描述带上 OS 错误
+        let path = std::path::Path::new("/nonexistent-vimo-dir/config.toml");
+        let os_error = std::fs::File::open(path).unwrap_err();
+        let err = FfiError::permission_denied_with_path(path);
+        assert_eq!(
+            err.to_string(),
+            format!("permission denied: {}: {os_error}", path.display())
+        );
+        assert_eq!(err.code(), FfiErrorCode::PermissionDenied as i32);
+        assert!(vimo_ffi_error_is_permission_denied(err.code()));
+        assert!(!vimo_ffi_error_is_permission_denied(FfiErrorCode::Io as i32));
+        assert!(!vimo_ffi_error_is_permission_denied(0));
+
+        std::fs::File::open(path).unwrap_err();
+        assert!(matches!(FfiError::permission_denied(), FfiError::PermissionDenied(Some(_))));
+    }
+
+    #[test]
+    fn test_from_code_roundtrip() {
+        let errors = [
+            FfiError::NullPointer,
+            FfiError::InvalidUtf8,
+            FfiError::StringContainsNull,
+            FfiError::Parse("parse error: bad digit".into()),
+            FfiError::OutOfRange("too big".into()),
+            FfiError::NotFound("no such key: theme".into()),
+            FfiError::OutOfMemory,
+            FfiError::StackOverflow,
+            FfiError::Timeout,
+            FfiError::Unavailable("database is locked".into()),
+            FfiError::PermissionDenied(None),
+            FfiError::PermissionDenied(Some("/etc/vimo: Permission denied (os error 13)".into())),
+            FfiError::Cancelled,
+            FfiError::AlreadyExists("key exists: theme".into()),
+            FfiError::custom("my error"),
+            FfiError::custom_with_code(1234, "app error"),
+        ];
+        for err in errors {
+            assert_eq!(FfiError::from_code(err.code(), err.to_string()), err);
+        }
+
+        // Io 的 kind 无法从消息恢复，但错误码和消息保持不变
+        let io = FfiError::from(std::io::Error::other("disk on fire"));
+        let back = FfiError::from_code(io.code(), io.to_string());
+        assert_eq!((back.code(), back.to_string()), (io.code(), io.to_string()));
+        let small = FfiError::BufferTooSmall { required: 64 };
+        let back = FfiError::from_code(small.code(), small.to_string());
+        assert_eq!(back.code(), 15);
+        assert_eq!(back.to_string(), "buffer too small: 64 bytes required");
+        let reentrant = FfiError::ReentrantCall("vimo_open".into());
+        let back = FfiError::from_code(reentrant.code(), reentrant.to_string());
+        assert_eq!((back.code(), back.to_string()), (16, "reentrant call to 'vimo_open'".into()));
+
+        // 未知的保留错误码退化为 Custom
+        assert_eq!(FfiError::from_code(42, "???"), FfiError::custom("???"));
+    }
+
+    #[test]
+    fn test_from_error_string_roundtrip() {
+        let errors = [
+            FfiError::NullPointer,
+            FfiError::InvalidUtf8,
+            FfiError::StringContainsNull,
+            FfiError::OutOfMemory,
+            FfiError::StackOverflow,
+            FfiError::Timeout,
+            FfiError::Cancelled,
+            FfiError::custom("my error"),
+            FfiError::custom(""),
+        ];
+        for err in errors {
+            let mut error_ptr: *mut c_char = ptr::null_mut();
+            unsafe { set_error_from(&mut error_ptr, &err) };
+            let msg = unsafe { CString::from_raw(error_ptr) };
+            assert_eq!(FfiError::from_error_string(msg.to_str().unwrap()), err);
+        }
+
+        // 错误码不在文本中，CustomCode 只能还原为 Custom
+        let coded = FfiError::custom_with_code(1234, "app error");
+        assert_eq!(FfiError::from_error_string(&coded.to_string()), FfiError::custom("app error"));
+    }
+
+    #[test]
+    fn test_from_std_errors() {
+        fn io(kind: std::io::ErrorKind) -> Result<(), FfiError> {
+            Err(std::io::Error::new(kind, "no such file"))?
+        }
+        fn utf8() -> Result<String, FfiError> {
+            let bytes = vec![0xff];
+            Ok(std::str::from_utf8(&bytes)?.to_owned())
+        }
+        fn nul() -> Result<CString, FfiError> {
+            Ok(CString::new("a\0b")?)
+        }
+        fn parse() -> Result<i32, FfiError> {
+            Ok("12x".parse::<i32>()?)
+        }
+        fn narrow() -> Result<u8, FfiError> {
+            Ok(u8::try_from(300i32)?)
+        }
+
+        use std::io::ErrorKind;
+        assert_eq!(
+            io(ErrorKind::PermissionDenied).unwrap_err(),
+            FfiError::Io {
+                kind: "PermissionDenied".into(),
+                message: "no such file".into(),
+            }
+        );
+        assert_eq!(io(ErrorKind::NotFound).unwrap_err(), FfiError::NotFound("no such file".into()));
+        assert_eq!(
+            io(ErrorKind::AlreadyExists).unwrap_err(),
+            FfiError::AlreadyExists("no such file".into())
+        );
+        assert_eq!(io(ErrorKind::TimedOut).unwrap_err(), FfiError::Timeout);
+        assert_eq!(utf8().unwrap_err().code(), FfiErrorCode::InvalidUtf8 as i32);
+        assert_eq!(nul().unwrap_err().code(), FfiErrorCode::StringContainsNull as i32);
+        assert_eq!(parse().unwrap_err().code(), FfiErrorCode::Parse as i32);
+        assert_eq!(
+            parse().unwrap_err().to_string(),
+            "parse error: invalid digit found in string"
+        );
+        assert_eq!(narrow().unwrap_err().code(), FfiErrorCode::OutOfRange as i32);
+    }
+
+    #[test]
+    fn test_default_severity() {
+        assert_eq!(FfiError::NullPointer.severity(), Severity::Error);
+        assert_eq!(FfiError::Parse("bad".into()).severity(), Severity::Error);
+        assert_eq!(FfiError::custom("oops").severity(), Severity::Error);
+        assert_eq!(FfiError::OutOfMemory.severity(), Severity::Fatal);
+        assert_eq!(FfiError::StackOverflow.severity(), Severity::Fatal);
+        assert_eq!(severity_of(&"not an FfiError"), Severity::Error);
+    }
+
+    #[test]
+    fn test_custom_with_severity() {
+        let err = FfiError::custom_with_severity("retry later", Severity::Warning);
+        assert_eq!(err.severity(), Severity::Warning);
+        assert_eq!(err.code(), FfiErrorCode::Custom as i32);
+        assert_eq!(err.to_string(), "retry later");
+
+        // 上下文不改变严重级别，覆盖也不改变错误码
+        let err = err.context("upload");
+        assert_eq!(err.severity(), Severity::Warning);
+        let err = FfiError::custom_with_code(1234, "corrupt").with_severity(Severity::Fatal);
+        assert_eq!((err.code(), err.severity()), (1234, Severity::Fatal));
+    }
+
+    #[test]
+    fn test_error_context() {
+        let err = FfiError::NullPointer.context("argument 'title'");
+        assert_eq!(err.to_string(), "argument 'title': null pointer");
+        assert_eq!(err.code(), FfiErrorCode::NullPointer as i32);
+
+        let err = err.with_context(|| format!("in {}", "set_title"));
+        assert_eq!(err.to_string(), "in set_title: argument 'title': null pointer");
+        assert_eq!(err.code(), FfiErrorCode::NullPointer as i32);
+
+        let err = FfiError::custom_with_code(1234, "quota exceeded").context("upload");
+        assert_eq!(err.code(), 1234);
+    }
+
+    #[test]
+    fn test_result_ext() {
+        let result = check_not_null(ptr::null::<u8>()).ctx("argument 'buf'");
+        assert_eq!(result.unwrap_err().to_string(), "argument 'buf': null pointer");
+
+        let val = 1u8;
+        assert!(check_not_null(&val as *const u8).with_ctx(|| unreachable!()).is_ok());
+    }
+
     #[test]
     fn test_ffi_error_display() {
         assert_eq!(FfiError::NullPointer.to_string(), "null pointer");
@@ -128,4 +2269,199 @@ mod tests {
             "my error"
         );
     }
+
+    #[test]
+    fn test_set_error_struct() {
+        let before = live_allocations();
+        let mut err: *mut VimoError = ptr::null_mut();
+        unsafe { set_error_struct(&mut err, &FfiError::InvalidUtf8, Some("while reading title")) };
+        assert!(!err.is_null());
+
+        let (code, message, context) = unsafe {
+            let e = &*err;
+            (
+                e.code,
+                std::ffi::CStr::from_ptr(e.message).to_str().unwrap().to_owned(),
+                std::ffi::CStr::from_ptr(e.context).to_str().unwrap().to_owned(),
+            )
+        };
+        assert_eq!(code, 2);
+        assert_eq!(unsafe { (*err).severity }, Severity::Error as i32);
+        assert_eq!(message, "invalid UTF-8 string");
+        assert_eq!(context, "while reading title");
+        drop((message, context));
+
+        unsafe { vimo_ffi_free_error(err) };
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn test_set_error_struct_without_context() {
+        let before = live_allocations();
+        let mut err: *mut VimoError = ptr::null_mut();
+        unsafe { set_error_struct(&mut err, &FfiError::NullPointer, None) };
+        assert!(unsafe { (*err).context.is_null() });
+        unsafe { vimo_ffi_free_error(err) };
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn test_set_error_struct_null_out() {
+        let before = live_allocations();
+        unsafe { set_error_struct(ptr::null_mut(), &FfiError::NullPointer, Some("ctx")) };
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn test_free_null_error() {
+        unsafe { vimo_ffi_free_error(ptr::null_mut()) };
+    }
+
+    #[derive(Debug)]
+    struct Layer {
+        msg: &'static str,
+        source: Option<Box<Layer>>,
+    }
+
+    impl std::fmt::Display for Layer {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(self.msg)
+        }
+    }
+
+    impl std::error::Error for Layer {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            self.source.as_deref().map(|e| e as _)
+        }
+    }
+
+    /// source() 指向自身的错误，模拟成环的错误链
+    #[derive(Debug)]
+    struct Cycle;
+
+    impl std::fmt::Display for Cycle {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("cycle")
+        }
+    }
+
+    impl std::error::Error for Cycle {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&Cycle)
+        }
+    }
+
+    #[test]
+    fn test_set_error_chain_nested() {
+        let err = Layer {
+            msg: "failed to load config",
+            source: Some(Box::new(Layer {
+                msg: "failed to open file",
+                source: Some(Box::new(Layer {
+                    msg: "permission denied (os error 13)",
+                    source: None,
+                })),
+            })),
+        };
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        unsafe { set_error_chain(&mut error_ptr, &err) };
+        let msg = unsafe { CString::from_raw(error_ptr) };
+        assert_eq!(
+            msg.to_str().unwrap(),
+            "failed to load config: caused by: failed to open file: caused by: permission denied (os error 13)"
+        );
+    }
+
+    #[test]
+    fn test_set_error_chain_custom_separator() {
+        let err = Layer {
+            msg: "outer",
+            source: Some(Box::new(Layer { msg: "inner", source: None })),
+        };
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        unsafe { set_error_chain_with(&mut error_ptr, &err, " <- ") };
+        let msg = unsafe { CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "outer <- inner");
+    }
+
+    #[test]
+    fn test_map_err_to_ffi() {
+        let err = Layer {
+            msg: "outer",
+            source: Some(Box::new(Layer { msg: "inner", source: None })),
+        };
+        let mapped = map_err_to_ffi::<(), _>(Err(err)).unwrap_err();
+        assert_eq!(mapped, FfiError::custom("outer: caused by: inner"));
+
+        let mapped = map_err_to_ffi::<(), _>(Err(FfiError::NullPointer)).unwrap_err();
+        assert_eq!(mapped, FfiError::NullPointer);
+        assert_eq!(map_err_to_ffi(Ok::<_, Layer>(7)), Ok(7));
+    }
+
+    #[test]
+    fn test_format_error_chain_depth_cap() {
+        let msg = format_error_chain(&Cycle, " / ", ERROR_CHAIN_MAX_DEPTH);
+        let expected = format!("{} / ...", ["cycle"; ERROR_CHAIN_MAX_DEPTH].join(" / "));
+        assert_eq!(msg, expected);
+    }
+
+    /// `source()` 会 panic 的错误类型
+    #[derive(Debug)]
+    struct BadSource;
+
+    impl std::fmt::Display for BadSource {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("bad source")
+        }
+    }
+
+    impl std::error::Error for BadSource {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            panic!("source for BadSource")
+        }
+    }
+
+    #[test]
+    fn test_set_error_chain_source_panic() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        unsafe { set_error_chain(&mut error_ptr, &BadSource) };
+        let msg = unsafe { CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), FORMAT_PANIC_MESSAGE);
+    }
+
+    #[test]
+    fn test_set_error_buf() {
+        let mut buf = [0x7f as c_char; 16];
+        let required = unsafe { set_error_buf(buf.as_mut_ptr(), buf.len(), "short") };
+        assert_eq!(required, 6);
+        let written = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+        assert_eq!(written.to_str().unwrap(), "short");
+    }
+
+    #[test]
+    fn test_set_error_buf_truncates_at_char_boundary() {
+        // "错误" 每个字符 3 字节：容量 6 只能放下 5 字节内容，第二个字符不能被拆开
+        let mut buf = [0x7f as c_char; 6];
+        let required = unsafe { set_error_buf(buf.as_mut_ptr(), buf.len(), "错误") };
+        assert_eq!(required, 7);
+        let written = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+        assert_eq!(written.to_str().unwrap(), "错");
+    }
+
+    #[test]
+    fn test_set_error_buf_escapes_nul() {
+        let mut buf = [0x7f as c_char; 16];
+        let required = unsafe { set_error_buf(buf.as_mut_ptr(), buf.len(), "a\0b") };
+        assert_eq!(required, 5);
+        let written = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+        assert_eq!(written.to_bytes(), b"a\\0b");
+    }
+
+    #[test]
+    fn test_set_error_buf_no_buffer() {
+        assert_eq!(unsafe { set_error_buf(ptr::null_mut(), 16, "abc") }, 4);
+        let mut buf = [0x7f as c_char; 4];
+        assert_eq!(unsafe { set_error_buf(buf.as_mut_ptr(), 0, "abc") }, 4);
+        assert_eq!(buf[0], 0x7f);
+    }
 }
@@ -0,0 +1,221 @@
+//! 多错误累积
+//!
+//! 校验类接口常常需要一次报告所有不合法的字段，而不是遇到第一个就返回，
+//! 否则 C 侧只能反复“修一个、重新提交”。
+
+use std::ffi::c_char;
+use std::fmt;
+use std::ptr;
+
+use crate::message_limit::cap_message;
+use crate::string::sanitized_cstring;
+use crate::translate::translate;
+use crate::{FfiError, FfiErrorCode, Severity};
+
+/// 累积的多个错误
+///
+/// `Display` 以 `"; "` 连接所有消息，可直接用于单字符串的 `out_error` 通道。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// let mut errors = ErrorList::new();
+/// if name.is_empty() {
+///     errors.push(FfiError::custom("name is empty"));
+/// }
+/// if age > 150 {
+///     errors.push(FfiError::OutOfRange("age too large".into()));
+/// }
+/// errors.into_result()?;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorList(Vec<FfiError>);
+
+impl ErrorList {
+    /// 创建空列表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加一个错误
+    pub fn push(&mut self, err: FfiError) {
+        self.0.push(err);
+    }
+
+    /// 是否没有任何错误
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 错误个数
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// 遍历所有错误
+    pub fn iter(&self) -> std::slice::Iter<'_, FfiError> {
+        self.0.iter()
+    }
+
+    /// 没有错误时返回 `Ok(())`，否则返回列表本身
+    pub fn into_result(self) -> Result<(), ErrorList> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// 列表整体的错误码：第一个错误的错误码，空列表为 `FfiErrorCode::Ok`
+    pub fn code(&self) -> i32 {
+        self.0.first().map_or(FfiErrorCode::Ok as i32, FfiError::code)
+    }
+
+    /// 列表整体的严重级别：所有错误中最高的一个，空列表为 `Severity::Warning`
+    pub fn severity(&self) -> Severity {
+        self.0.iter().map(FfiError::severity).max().unwrap_or(Severity::Warning)
+    }
+}
+
+impl fmt::Display for ErrorList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, err) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ErrorList {}
+
+impl From<FfiError> for ErrorList {
+    fn from(err: FfiError) -> Self {
+        Self(vec![err])
+    }
+}
+
+impl From<Vec<FfiError>> for ErrorList {
+    fn from(errors: Vec<FfiError>) -> Self {
+        Self(errors)
+    }
+}
+
+impl IntoIterator for ErrorList {
+    type Item = FfiError;
+    type IntoIter = std::vec::IntoIter<FfiError>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// 将消息写出为 `char**` 数组
+///
+/// # Safety
+/// 同 `set_errors`
+pub(crate) unsafe fn write_string_array<I>(
+    out_errors: *mut *mut *mut c_char,
+    out_count: *mut usize,
+    messages: I,
+) where
+    I: ExactSizeIterator<Item = String>,
+{
+    if out_errors.is_null() || out_count.is_null() {
+        return;
+    }
+    if messages.len() == 0 {
+        *out_errors = ptr::null_mut();
+        *out_count = 0;
+        return;
+    }
+    let array: Box<[*mut c_char]> =
+        messages.map(|msg| sanitized_cstring(&msg).into_raw()).collect();
+    *out_count = array.len();
+    *out_errors = Box::into_raw(array) as *mut *mut c_char;
+}
+
+/// 将错误列表写出为 `char**` 数组
+///
+/// 每个错误一项，数组和其中的字符串必须由调用者使用 `vimo_ffi_free_string_array` 释放。
+/// 空列表写出 null 和 0，不做分配。
+///
+/// # Safety
+/// `out_errors` 和 `out_count` 必须是有效的可写指针；任一为 null 时不写入任何内容
+///
+/// # 示例
+///
+/// ```c
+/// char **errors = NULL;
+/// size_t count = 0;
+/// if (!validate_form(form, &errors, &count)) {
+///     for (size_t i = 0; i < count; i++) puts(errors[i]);
+///     vimo_ffi_free_string_array(errors, count);
+/// }
+/// ```
+pub unsafe fn set_errors(
+    out_errors: *mut *mut *mut c_char,
+    out_count: *mut usize,
+    list: &ErrorList,
+) {
+    let messages = list.iter().map(|e| cap_message(translate(e.code(), &e.to_string())).into_owned());
+    write_string_array(out_errors, out_count, messages);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_alloc::live_allocations;
+    use crate::vimo_ffi_free_string_array;
+    use std::ffi::CStr;
+
+    fn three_errors() -> ErrorList {
+        let mut errors = ErrorList::new();
+        errors.push(FfiError::NullPointer);
+        errors.push(FfiError::OutOfRange("age too large".into()));
+        errors.push(FfiError::custom_with_severity("name is empty", Severity::Warning));
+        errors
+    }
+
+    #[test]
+    fn test_error_list_display() {
+        let errors = three_errors();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors.to_string(), "null pointer; age too large; name is empty");
+        assert_eq!(errors.code(), FfiErrorCode::NullPointer as i32);
+        assert_eq!(errors.severity(), Severity::Error);
+    }
+
+    #[test]
+    fn test_set_errors() {
+        let before = live_allocations();
+        let mut array: *mut *mut c_char = ptr::null_mut();
+        let mut count = 0usize;
+        unsafe { set_errors(&mut array, &mut count, &three_errors()) };
+        assert_eq!(count, 3);
+
+        let messages: Vec<String> = (0..count)
+            .map(|i| unsafe { CStr::from_ptr(*array.add(i)) }.to_str().unwrap().to_owned())
+            .collect();
+        assert_eq!(messages, ["null pointer", "age too large", "name is empty"]);
+        drop(messages);
+
+        unsafe { vimo_ffi_free_string_array(array, count) };
+        assert_eq!(live_allocations(), before);
+    }
+
+    #[test]
+    fn test_empty_list_fast_path() {
+        assert_eq!(ErrorList::new().into_result(), Ok(()));
+
+        let before = live_allocations();
+        let mut array: *mut *mut c_char = ptr::dangling_mut();
+        let mut count = 9usize;
+        unsafe { set_errors(&mut array, &mut count, &ErrorList::new()) };
+        assert!(array.is_null());
+        assert_eq!(count, 0);
+        assert_eq!(live_allocations(), before);
+    }
+}
@@ -0,0 +1,128 @@
+//! 边界函数的失败值
+//!
+//! `ffi_boundary_default` 等函数从返回类型推断失败值，调用点不再需要显式传入 `default`。
+//! 需要特殊失败值（如 `ffi_boundary_i32` 的 `i32::MIN`）时仍使用带 `default` 参数的版本。
+
+/// 类型在 FFI 边界失败时返回的值
+///
+/// | 类型 | 失败值 |
+/// |------|--------|
+/// | `bool` | `false` |
+/// | 有符号整数 | `-1` |
+/// | 无符号整数 | `0` |
+/// | `*mut T` / `*const T` | null |
+/// | `()` | `()` |
+///
+/// 应用类型可以自行实现，例如 `#[repr(C)]` 的状态枚举返回其错误状态。
+pub trait FfiDefault {
+    /// 失败值
+    fn ffi_default() -> Self;
+}
+
+impl FfiDefault for bool {
+    fn ffi_default() -> Self {
+        false
+    }
+}
+
+impl FfiDefault for () {
+    fn ffi_default() -> Self {}
+}
+
+impl<T> FfiDefault for *mut T {
+    fn ffi_default() -> Self {
+        std::ptr::null_mut()
+    }
+}
+
+impl<T> FfiDefault for *const T {
+    fn ffi_default() -> Self {
+        std::ptr::null()
+    }
+}
+
+macro_rules! impl_ffi_default {
+    ($value:expr => $($ty:ty),+) => {
+        $(
+            impl FfiDefault for $ty {
+                fn ffi_default() -> Self {
+                    $value
+                }
+            }
+        )+
+    };
+}
+
+impl_ffi_default!(-1 => i8, i16, i32, i64, isize);
+impl_ffi_default!(0 => u8, u16, u32, u64, usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffi_boundary_default, FfiError};
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    use crate::{ffi_boundary_simple_default, ffi_boundary_with_log_default};
+    use std::ffi::{c_char, CString};
+    use std::ptr;
+
+    #[test]
+    fn test_builtin_sentinels() {
+        assert!(!bool::ffi_default());
+        assert_eq!(i8::ffi_default(), -1);
+        assert_eq!(i32::ffi_default(), -1);
+        assert_eq!(isize::ffi_default(), -1);
+        assert_eq!(u8::ffi_default(), 0);
+        assert_eq!(usize::ffi_default(), 0);
+        assert!(<*mut u32>::ffi_default().is_null());
+        assert!(<*const c_char>::ffi_default().is_null());
+        <()>::ffi_default();
+    }
+
+    #[test]
+    fn test_ffi_boundary_default() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let ok: bool = ffi_boundary_default(&mut error_ptr, || Err(FfiError::NullPointer));
+        assert!(!ok);
+        let msg = unsafe { CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "null pointer");
+
+        let count: i64 = ffi_boundary_default(ptr::null_mut(), || Err(FfiError::NullPointer));
+        assert_eq!(count, -1);
+        assert_eq!(ffi_boundary_default(ptr::null_mut(), || Ok::<_, FfiError>(7u32)), 7);
+    }
+
+    #[derive(Debug, PartialEq)]
+    #[repr(C)]
+    enum Status {
+        Ready,
+        Failed,
+    }
+
+    impl FfiDefault for Status {
+        fn ffi_default() -> Self {
+            Status::Failed
+        }
+    }
+
+    #[test]
+    fn test_user_type_opt_in() {
+        let status: Status = ffi_boundary_default(ptr::null_mut(), || Err(FfiError::Timeout));
+        assert_eq!(status, Status::Failed);
+        let status = ffi_boundary_default(ptr::null_mut(), || Ok::<_, FfiError>(Status::Ready));
+        assert_eq!(status, Status::Ready);
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_simple_and_log_variants() {
+        let p: *mut u8 = ffi_boundary_simple_default(|| panic!("boom"));
+        assert!(p.is_null());
+
+        let logged = std::cell::Cell::new(false);
+        let n: i32 = ffi_boundary_with_log_default(|msg| logged.set(msg == "boom"), || {
+            panic!("boom")
+        });
+        assert_eq!(n, -1);
+        assert!(logged.get());
+    }
+}
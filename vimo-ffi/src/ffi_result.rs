@@ -0,0 +1,177 @@
+//! `#[repr(C)]` 结果结构体
+//!
+//! 部分导出函数希望一次返回值和错误码，而不必使用 out 参数。
+//! `ffi_result_type!` 生成 `{ code, value }` 结构体，`ffi_boundary_result` 负责填充；
+//! 错误消息仍记录在线程局部的最近一次错误中（见 `vimo_ffi_last_error_message`）。
+
+/// 由 `ffi_result_type!` 生成的结果结构体
+pub trait FfiResultType: Sized {
+    /// 成功时携带的值类型
+    type Value;
+
+    /// 成功结果，`code` 为 `FfiErrorCode::Ok`
+    fn ok(value: Self::Value) -> Self;
+
+    /// 失败结果，`value` 为该类型的默认值
+    fn from_code(code: i32) -> Self;
+}
+
+/// 生成 `#[repr(C)]` 结果结构体
+///
+/// 生成的结构体为 `{ code: i32, value: T }`，并提供 `ok(v)`、`err(&FfiError)` 和 `is_ok()`。
+/// 失败时 `value` 取 `Default::default()`；值类型没有 `Default`（如裸指针）时，
+/// 在末尾传入失败值表达式。结构体前可以附加属性（如 `#[derive(...)]`）。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// ffi_result_type!(#[derive(Debug, Clone, Copy)] u32 => VimoResultU32);
+/// ffi_result_type!(*mut Document => VimoResultDocument, std::ptr::null_mut());
+///
+/// #[no_mangle]
+/// pub extern "C" fn doc_open(path: *const c_char) -> VimoResultDocument {
+///     ffi_boundary_result(|| {
+///         let path = unsafe { cstr_to_str(path)? };
+///         Ok::<_, FfiError>(Box::into_raw(Box::new(Document::open(path)?)))
+///     })
+/// }
+/// ```
+#[macro_export]
+macro_rules! ffi_result_type {
+    ($(#[$meta:meta])* $value:ty => $name:ident) => {
+        $crate::ffi_result_type!($(#[$meta])* $value => $name, ::core::default::Default::default());
+    };
+    ($(#[$meta:meta])* $value:ty => $name:ident, $failure:expr) => {
+        $(#[$meta])*
+        #[repr(C)]
+        pub struct $name {
+            /// 错误码，`0` 表示成功（见 `FfiErrorCode`）
+            pub code: i32,
+            /// 成功时的返回值，失败时为默认值
+            pub value: $value,
+        }
+
+        impl $name {
+            /// 成功结果
+            pub fn ok(value: $value) -> Self {
+                <Self as $crate::FfiResultType>::ok(value)
+            }
+
+            /// 以错误的错误码构造失败结果
+            pub fn err(err: &$crate::FfiError) -> Self {
+                <Self as $crate::FfiResultType>::from_code(err.code())
+            }
+
+            /// 是否成功
+            pub fn is_ok(&self) -> bool {
+                self.code == $crate::FfiErrorCode::Ok as i32
+            }
+        }
+
+        impl $crate::FfiResultType for $name {
+            type Value = $value;
+
+            fn ok(value: $value) -> Self {
+                Self {
+                    code: $crate::FfiErrorCode::Ok as i32,
+                    value,
+                }
+            }
+
+            fn from_code(code: i32) -> Self {
+                Self {
+                    code,
+                    value: $failure,
+                }
+            }
+        }
+    };
+}
+
+ffi_result_type!(#[derive(Debug, Clone, Copy, PartialEq, Eq)] i32 => VimoResultI32);
+ffi_result_type!(#[derive(Debug, Clone, Copy, PartialEq, Eq)] i64 => VimoResultI64);
+ffi_result_type!(#[derive(Debug, Clone, Copy, PartialEq, Eq)] u64 => VimoResultU64);
+ffi_result_type!(#[derive(Debug, Clone, Copy, PartialEq)] f64 => VimoResultF64);
+ffi_result_type!(#[derive(Debug, Clone, Copy, PartialEq, Eq)] bool => VimoResultBool);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::{c_void, CStr};
+    use std::mem::{align_of, offset_of, size_of};
+
+    use crate::{
+        ffi_boundary_result, vimo_ffi_free_string, vimo_ffi_last_error_code,
+        vimo_ffi_last_error_message, FfiError, FfiErrorCode,
+    };
+
+    fn last_message() -> String {
+        let ptr = vimo_ffi_last_error_message();
+        let msg = unsafe { CStr::from_ptr(ptr) }.to_str().unwrap().to_string();
+        unsafe { vimo_ffi_free_string(ptr) };
+        msg
+    }
+
+    #[test]
+    fn test_layout() {
+        // cbindgen 生成的 C 结构体依赖这些布局
+        assert_eq!(size_of::<VimoResultI32>(), 8);
+        assert_eq!(size_of::<VimoResultI64>(), 16);
+        assert_eq!(align_of::<VimoResultI64>(), align_of::<i64>());
+        assert_eq!(offset_of!(VimoResultI64, code), 0);
+        assert_eq!(offset_of!(VimoResultI64, value), 8);
+        assert_eq!(size_of::<VimoResultF64>(), 16);
+        assert_eq!(size_of::<VimoResultBool>(), 8);
+        assert_eq!(offset_of!(VimoResultBool, value), 4);
+    }
+
+    #[test]
+    fn test_constructors() {
+        assert_eq!(VimoResultI64::ok(42), VimoResultI64 { code: 0, value: 42 });
+        assert!(VimoResultI64::ok(42).is_ok());
+        let err = VimoResultI64::err(&FfiError::InvalidUtf8);
+        assert_eq!(err, VimoResultI64 { code: 2, value: 0 });
+        assert!(!err.is_ok());
+    }
+
+    ffi_result_type!(*mut c_void => VimoResultHandle, std::ptr::null_mut());
+
+    #[test]
+    fn test_custom_failure_value() {
+        let err = VimoResultHandle::err(&FfiError::NullPointer);
+        assert_eq!(err.code, 1);
+        assert!(err.value.is_null());
+        assert!(!err.is_ok());
+
+        let mut target = 0u8;
+        let ok = VimoResultHandle::ok(&mut target as *mut u8 as *mut c_void);
+        assert!(ok.is_ok());
+        assert!(!ok.value.is_null());
+    }
+
+    #[test]
+    fn test_boundary_result_success() {
+        let result: VimoResultI64 = ffi_boundary_result(|| Ok::<_, FfiError>(7));
+        assert_eq!(result, VimoResultI64::ok(7));
+        assert_eq!(vimo_ffi_last_error_code(), 0);
+    }
+
+    #[test]
+    fn test_boundary_result_error() {
+        let result: VimoResultI64 =
+            ffi_boundary_result(|| Err(FfiError::OutOfRange("too big".into())));
+        assert_eq!(result, VimoResultI64 { code: FfiErrorCode::OutOfRange as i32, value: 0 });
+        assert_eq!(vimo_ffi_last_error_code(), FfiErrorCode::OutOfRange as i32);
+        assert_eq!(last_message(), "too big");
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_boundary_result_panic() {
+        let result: VimoResultBool = ffi_boundary_result(|| -> Result<bool, FfiError> {
+            panic!("boom")
+        });
+        assert_eq!(result, VimoResultBool { code: FfiErrorCode::Panic as i32, value: false });
+        assert_eq!(last_message(), "internal panic: boom");
+    }
+}
@@ -0,0 +1,322 @@
+//! RAII 资源清理工具
+//!
+//! FFI 函数常常持有需要手动释放的资源（C 字符串、句柄等），
+//! 用守卫对象把释放逻辑绑定到作用域，避免在每个返回路径上重复清理。
+
+use std::ffi::{c_char, CStr};
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+
+/// 作用域守卫 - 离开作用域时对持有的值执行清理
+///
+/// 通过 `scope_guard` 创建。守卫可以像 `&T` 一样使用，
+/// 调用 `defuse` 可以取回值并放弃清理。
+///
+/// # C 侧的对应做法
+///
+/// C 没有析构函数，对应的模式是在函数内维护一个“待释放”列表，
+/// 统一在出口处调用 `vimo_ffi_free_string` 等释放函数：
+///
+/// ```c
+/// char *tmp[4] = {0};
+/// size_t n = 0;
+/// tmp[n++] = some_vimo_call(&err);
+/// if (err) goto cleanup;
+/// /* ... */
+/// cleanup:
+///     for (size_t i = 0; i < n; i++) vimo_ffi_free_string(tmp[i]);
+/// ```
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// let ptr = str_to_cstring("hello")?;
+/// let guard = scope_guard(ptr, |p| unsafe { vimo_ffi_free_string(p) });
+/// call_c_api(*guard)?; // 无论此处是否提前返回，ptr 都会被释放
+/// ```
+pub struct FfiScopeGuard<T, F: FnOnce(T)> {
+    value: ManuallyDrop<T>,
+    cleanup: Option<F>,
+}
+
+/// 创建作用域守卫
+pub fn scope_guard<T, F: FnOnce(T)>(value: T, cleanup: F) -> FfiScopeGuard<T, F> {
+    FfiScopeGuard {
+        value: ManuallyDrop::new(value),
+        cleanup: Some(cleanup),
+    }
+}
+
+impl<T, F: FnOnce(T)> FfiScopeGuard<T, F> {
+    /// 解除守卫，取回值且不执行清理
+    ///
+    /// 用于所有权需要转交给调用者的成功路径。
+    pub fn defuse(mut self) -> T {
+        self.cleanup = None;
+        // SAFETY: cleanup 已置空，Drop 不会再访问 value
+        let value = unsafe { ManuallyDrop::take(&mut self.value) };
+        std::mem::forget(self);
+        value
+    }
+}
+
+impl<T, F: FnOnce(T)> Deref for FfiScopeGuard<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T, F: FnOnce(T)> DerefMut for FfiScopeGuard<T, F> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T, F: FnOnce(T)> Drop for FfiScopeGuard<T, F> {
+    fn drop(&mut self) {
+        // SAFETY: value 只在这里或 defuse 中取出一次
+        let value = unsafe { ManuallyDrop::take(&mut self.value) };
+        if let Some(cleanup) = self.cleanup.take() {
+            cleanup(value);
+        }
+    }
+}
+
+/// 延迟执行的守卫 - 离开作用域时执行闭包
+///
+/// 与 `scope_guard` 不同，它不持有值，适合清理分散在闭包各处的临时资源。
+/// 多个守卫按创建的逆序执行（后进先出）。边界函数捕获 panic 时同样会执行，
+/// 因为展开发生在边界的 `catch_unwind` 之内。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// ffi_boundary(out_error, ptr::null_mut(), || {
+///     let handle = vimo_open(path)?;
+///     let close = FfiGuard::new(|| vimo_close(handle));
+///     // 提前返回或 panic 时句柄都会释放；只在 panic 时才需要的清理用 on_panic_only
+///     let doc = parse(handle)?;
+///     close.dismiss(); // 句柄交给返回值
+///     Ok(Box::into_raw(Box::new(doc)))
+/// })
+/// ```
+#[must_use = "守卫没有绑定到变量时会立即执行清理"]
+pub struct FfiGuard<F: FnOnce()> {
+    cleanup: Option<F>,
+    on_panic_only: bool,
+}
+
+impl<F: FnOnce()> FfiGuard<F> {
+    /// 创建守卫，离开作用域时执行 `f`
+    pub fn new(f: F) -> Self {
+        Self {
+            cleanup: Some(f),
+            on_panic_only: false,
+        }
+    }
+
+    /// 创建只在 panic 展开时执行 `f` 的守卫，正常返回（包括返回错误）时不执行
+    pub fn on_panic_only(f: F) -> Self {
+        Self {
+            cleanup: Some(f),
+            on_panic_only: true,
+        }
+    }
+
+    /// 解除守卫，不再执行清理
+    pub fn dismiss(mut self) {
+        self.cleanup = None;
+    }
+}
+
+impl<F: FnOnce()> Drop for FfiGuard<F> {
+    fn drop(&mut self) {
+        if let Some(cleanup) = self.cleanup.take() {
+            if !self.on_panic_only || std::thread::panicking() {
+                cleanup();
+            }
+        }
+    }
+}
+
+/// 持有边界函数写出的错误消息，重复使用时释放旧消息
+///
+/// 同一个错误变量在循环中反复传给边界函数时，边界函数进入时直接把 `*out_error` 置为 null，
+/// 不会释放旧消息（它无法确认旧值是否归本库所有，也可能根本没有初始化）。
+/// `ErrorSlot` 确定自己持有的是本库写出的消息：每次 `as_out` 先释放上一条，
+/// 离开作用域时释放最后一条。
+///
+/// C 侧的对应做法是每次调用后检查并 `vimo_ffi_free_string(err); err = NULL;`，
+/// 或者在写入前用 `set_error_safe` 显式释放旧值。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// let mut err = ErrorSlot::new();
+/// for path in paths {
+///     if !vimo_doc_open(path, err.as_out()) {
+///         eprintln!("{}: {}", path, err.message().unwrap_or_default());
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ErrorSlot(*mut c_char);
+
+impl ErrorSlot {
+    /// 创建空的错误槽
+    pub fn new() -> Self {
+        Self(ptr::null_mut())
+    }
+
+    /// 释放当前消息，返回可以传给边界函数的 `out_error`
+    pub fn as_out(&mut self) -> *mut *mut c_char {
+        self.clear();
+        &mut self.0
+    }
+
+    /// 当前是否持有错误消息
+    pub fn is_set(&self) -> bool {
+        !self.0.is_null()
+    }
+
+    /// 当前的错误消息，不是有效 UTF-8 时返回 None
+    pub fn message(&self) -> Option<&str> {
+        if self.0.is_null() {
+            return None;
+        }
+        // SAFETY: 非 null 时是边界函数写出的 NUL 结尾字符串，由本槽持有
+        unsafe { CStr::from_ptr(self.0) }.to_str().ok()
+    }
+
+    /// 取出错误消息并清空
+    pub fn take(&mut self) -> Option<String> {
+        let message = self.message().map(str::to_owned);
+        self.clear();
+        message
+    }
+
+    /// 释放当前消息
+    pub fn clear(&mut self) {
+        let old = std::mem::replace(&mut self.0, ptr::null_mut());
+        // SAFETY: 非 null 的值只可能由边界函数通过 as_out 写入
+        unsafe { crate::vimo_ffi_free_string(old) };
+    }
+}
+
+impl Default for ErrorSlot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ErrorSlot {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_scope_guard_runs_cleanup() {
+        let cleaned = Cell::new(0);
+        {
+            let guard = scope_guard(42, |v| cleaned.set(v));
+            assert_eq!(*guard, 42);
+        }
+        assert_eq!(cleaned.get(), 42);
+    }
+
+    #[test]
+    fn test_scope_guard_defuse() {
+        let cleaned = Cell::new(false);
+        let guard = scope_guard(String::from("owned"), |_| cleaned.set(true));
+        let value = guard.defuse();
+        assert_eq!(value, "owned");
+        assert!(!cleaned.get());
+    }
+
+    #[test]
+    fn test_scope_guard_frees_cstring() {
+        let ptr = crate::str_to_cstring("hello").unwrap();
+        let guard = scope_guard(ptr, |p| unsafe { crate::vimo_ffi_free_string(p) });
+        assert!(!guard.is_null());
+    }
+
+    #[test]
+    fn test_ffi_guard_lifo_order() {
+        let order = std::cell::RefCell::new(Vec::new());
+        {
+            let _first = FfiGuard::new(|| order.borrow_mut().push(1));
+            let _second = FfiGuard::new(|| order.borrow_mut().push(2));
+            let _third = FfiGuard::new(|| order.borrow_mut().push(3));
+        }
+        assert_eq!(*order.borrow(), [3, 2, 1]);
+    }
+
+    #[test]
+    fn test_ffi_guard_dismiss() {
+        let cleaned = Cell::new(false);
+        let guard = FfiGuard::new(|| cleaned.set(true));
+        guard.dismiss();
+        assert!(!cleaned.get());
+
+        // 正常离开作用域时 on_panic_only 不执行
+        drop(FfiGuard::on_panic_only(|| cleaned.set(true)));
+        assert!(!cleaned.get());
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_ffi_guard_runs_when_boundary_catches_panic() {
+        use crate::{ffi_boundary, FfiError};
+
+        let order = std::cell::RefCell::new(Vec::new());
+        let ok = ffi_boundary(ptr::null_mut(), false, || -> Result<bool, FfiError> {
+            let _always = FfiGuard::new(|| order.borrow_mut().push("always"));
+            let _rollback = FfiGuard::on_panic_only(|| order.borrow_mut().push("rollback"));
+            panic!("boom");
+        });
+        assert!(!ok);
+        assert_eq!(*order.borrow(), ["rollback", "always"]);
+
+        let rolled_back = Cell::new(false);
+        let ok = ffi_boundary(ptr::null_mut(), false, || -> Result<bool, FfiError> {
+            let _rollback = FfiGuard::on_panic_only(|| rolled_back.set(true));
+            Err(FfiError::Timeout)
+        });
+        assert!(!ok);
+        assert!(!rolled_back.get());
+    }
+
+    #[test]
+    fn test_error_slot_frees_previous_message() {
+        use crate::test_alloc::live_allocations;
+        use crate::{ffi_boundary, FfiError};
+
+        let mut slot = ErrorSlot::new();
+        assert!(!slot.is_set());
+        ffi_boundary(slot.as_out(), false, || Err(FfiError::Timeout));
+        let after_first = live_allocations();
+        ffi_boundary(slot.as_out(), false, || Err(FfiError::Timeout));
+        assert_eq!(slot.message(), Some("operation timed out"));
+        // 第二次失败前释放了第一条消息，存活分配数不变
+        assert_eq!(live_allocations(), after_first);
+        drop(slot);
+        assert_eq!(live_allocations(), after_first - 1);
+
+        let mut slot = ErrorSlot::default();
+        ffi_boundary(slot.as_out(), false, || Err(FfiError::NullPointer));
+        assert!(ffi_boundary(slot.as_out(), false, || Ok::<_, FfiError>(true)));
+        assert!(!slot.is_set());
+        ffi_boundary(slot.as_out(), false, || Err(FfiError::NullPointer));
+        assert_eq!(slot.take().as_deref(), Some("null pointer"));
+        assert_eq!(slot.message(), None);
+    }
+}
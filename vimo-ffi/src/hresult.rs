@@ -0,0 +1,153 @@
+//! Windows `HRESULT` 映射
+//!
+//! 为包装成 COM 风格 `HRESULT` 的宿主提供固定的错误码映射。只是整数运算，
+//! 不依赖 Windows API，所有平台都能编译和测试。
+//!
+//! 失败码的布局：严重级别位（bit 31）置 1 表示失败，客户位（bit 29）置 1 表示非微软定义，
+//! 设施号为 `FACILITY_VIMO`，低 16 位为 `FfiError::code()`。
+//! 无法放入 16 位的错误码（如 `Unknown`）映射为 `E_FAIL`，panic 映射为 `E_UNEXPECTED`。
+
+use std::ffi::c_char;
+
+use crate::{FfiError, FfiErrorCode};
+
+/// 成功
+pub const S_OK: i32 = 0;
+/// 未指定的失败
+pub const E_FAIL: i32 = 0x8000_4005_u32 as i32;
+/// 意外的失败（panic）
+pub const E_UNEXPECTED: i32 = 0x8000_FFFF_u32 as i32;
+
+/// 本库错误使用的设施号
+pub const FACILITY_VIMO: u32 = 0x0766;
+
+const SEVERITY_BIT: u32 = 0x8000_0000;
+const CUSTOMER_BIT: u32 = 0x2000_0000;
+const FACILITY_MASK: u32 = 0x07FF_0000;
+
+impl FfiError {
+    /// 对应的 `HRESULT`（总是失败码）
+    pub fn to_hresult(&self) -> i32 {
+        code_to_hresult(self.code())
+    }
+}
+
+fn code_to_hresult(code: i32) -> i32 {
+    match u16::try_from(code) {
+        Ok(code) if code != 0 => {
+            (SEVERITY_BIT | CUSTOMER_BIT | (FACILITY_VIMO << 16) | code as u32) as i32
+        }
+        _ => E_FAIL,
+    }
+}
+
+/// 从 `HRESULT` 还原错误，与 `FfiError::to_hresult` 互逆
+///
+/// 只识别由本库生成的失败码（客户位和 `FACILITY_VIMO`），成功码和其它来源的 `HRESULT`
+/// （包括 `E_FAIL`、`E_UNEXPECTED`）返回 None。`HRESULT` 中不含消息，
+/// 需要消息的错误以 `"HRESULT 0x..."` 作为消息，错误码保持不变。
+pub fn hresult_to_ffi_error(hr: i32) -> Option<FfiError> {
+    let bits = hr as u32;
+    let ours = CUSTOMER_BIT | (FACILITY_VIMO << 16);
+    if bits & SEVERITY_BIT == 0 || bits & (CUSTOMER_BIT | FACILITY_MASK) != ours {
+        return None;
+    }
+    let code = (bits & 0xFFFF) as i32;
+    Some(FfiError::from_code(code, format!("HRESULT 0x{:08X}", bits)))
+}
+
+/// FFI 边界防护 - 返回 `HRESULT`
+///
+/// 成功返回 `S_OK`，闭包返回错误时返回映射后的 `HRESULT`（非 `FfiError` 的错误为 `E_FAIL`），
+/// panic 返回 `E_UNEXPECTED`。错误信息仍写入 `out_error`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "system" fn VimoOpen(path: *const c_char, out_error: *mut *mut c_char) -> i32 {
+///     ffi_boundary_hresult(out_error, || {
+///         let path = unsafe { cstr_to_str(path)? };
+///         db::open(path)?;
+///         Ok::<_, FfiError>(())
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_hresult<E, F>(out_error: *mut *mut c_char, f: F) -> i32
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<(), E>,
+{
+    match crate::ffi_boundary_code(out_error, f) {
+        0 => S_OK,
+        code if code == FfiErrorCode::Panic as i32 => E_UNEXPECTED,
+        code => code_to_hresult(code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::ptr;
+
+    #[test]
+    fn test_hresult_bits() {
+        let hr = FfiError::NullPointer.to_hresult();
+        assert!(hr < 0, "severity bit must mark failure");
+        assert_ne!(hr as u32 & CUSTOMER_BIT, 0);
+        assert_eq!((hr as u32 & FACILITY_MASK) >> 16, FACILITY_VIMO);
+        assert_eq!(hr as u32 & 0xFFFF, 1);
+        assert_eq!(hr as u32, 0xA766_0001);
+    }
+
+    #[test]
+    fn test_hresult_roundtrip() {
+        let errors = [
+            FfiError::NullPointer,
+            FfiError::InvalidUtf8,
+            FfiError::OutOfRange("x".into()),
+            FfiError::NotFound("x".into()),
+            FfiError::custom("x"),
+            FfiError::custom_with_code(1234, "x"),
+        ];
+        for err in errors {
+            let back = hresult_to_ffi_error(err.to_hresult()).unwrap();
+            assert_eq!(back.code(), err.code());
+        }
+        assert_eq!(hresult_to_ffi_error(S_OK), None);
+        assert_eq!(hresult_to_ffi_error(E_FAIL), None);
+        assert_eq!(hresult_to_ffi_error(E_UNEXPECTED), None);
+    }
+
+    #[test]
+    fn test_unrepresentable_codes() {
+        assert_eq!(FfiError::custom_with_code(70_000, "x").to_hresult(), E_FAIL);
+        let negative = FfiError::CustomCode {
+            code: -5,
+            message: "x".into(),
+        };
+        assert_eq!(negative.to_hresult(), E_FAIL);
+    }
+
+    #[test]
+    fn test_ffi_boundary_hresult() {
+        assert_eq!(ffi_boundary_hresult(ptr::null_mut(), || Ok::<_, FfiError>(())), S_OK);
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let hr = ffi_boundary_hresult(&mut error_ptr, || Err(FfiError::InvalidUtf8));
+        assert_eq!(hr, FfiError::InvalidUtf8.to_hresult());
+        drop(unsafe { CString::from_raw(error_ptr) });
+
+        let hr = ffi_boundary_hresult(ptr::null_mut(), || Err("plain"));
+        assert_eq!(hr, E_FAIL);
+
+        #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+        {
+            let hr = ffi_boundary_hresult(ptr::null_mut(), || -> Result<(), FfiError> {
+                panic!("boom")
+            });
+            assert_eq!(hr, E_UNEXPECTED);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! C 字符串驻留
+//!
+//! 对反复传入的相同字符串（如配置键名）只分配一次，之后直接返回缓存的 `&'static str`。
+
+use std::ffi::{c_char, CStr};
+use std::sync::OnceLock;
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+
+use crate::FfiError;
+
+static INTERN_CACHE: OnceLock<DashMap<u64, &'static str>> = OnceLock::new();
+
+fn cache() -> &'static DashMap<u64, &'static str> {
+    INTERN_CACHE.get_or_init(DashMap::new)
+}
+
+/// 64 位 FNV-1a 哈希
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &b| (hash ^ b as u64).wrapping_mul(PRIME))
+}
+
+/// 将 C 字符串驻留为 `&'static str`
+///
+/// 首次遇到某个字符串时分配并**泄漏**一个 `Box<str>`，之后相同内容直接返回缓存，
+/// 不再分配。适合取值集合有限的参数（键名、枚举名），不要用于任意用户输入，
+/// 否则内存会无限增长。
+///
+/// 哈希冲突（不同内容哈希相同）时返回一份不进入缓存的新分配，同样会泄漏。
+///
+/// # Safety
+/// 同 `cstr_to_str`
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// let key: &'static str = unsafe { cstr_intern(key_ptr)? };
+/// ```
+pub unsafe fn cstr_intern(ptr: *const c_char) -> Result<&'static str, FfiError> {
+    if ptr.is_null() {
+        return Err(FfiError::NullPointer);
+    }
+    let bytes = CStr::from_ptr(ptr).to_bytes();
+    let hash = fnv1a(bytes);
+
+    if let Some(cached) = cache().get(&hash) {
+        if cached.as_bytes() == bytes {
+            return Ok(*cached);
+        }
+    }
+
+    let s = std::str::from_utf8(bytes).map_err(|_| FfiError::InvalidUtf8)?;
+    match cache().entry(hash) {
+        Entry::Occupied(entry) if entry.get().as_bytes() == bytes => Ok(*entry.get()),
+        Entry::Occupied(_) => Ok(Box::leak(s.into())),
+        Entry::Vacant(entry) => {
+            let leaked: &'static str = Box::leak(s.into());
+            entry.insert(leaked);
+            Ok(leaked)
+        }
+    }
+}
+
+/// 清空驻留缓存并释放其中的字符串
+///
+/// 哈希冲突时未进入缓存的字符串不会被释放。
+///
+/// # Safety
+/// 调用后，此前由 `cstr_intern` 返回的所有 `&'static str` 都变为悬垂引用，
+/// 调用者必须确保它们不再被使用（例如只在库卸载前调用）。
+pub unsafe fn clear_intern_cache() {
+    let Some(cache) = INTERN_CACHE.get() else {
+        return;
+    };
+    let keys: Vec<u64> = cache.iter().map(|entry| *entry.key()).collect();
+    for key in keys {
+        if let Some((_, s)) = cache.remove(&key) {
+            drop(Box::from_raw(s as *const str as *mut str));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn test_cstr_intern_dedup() {
+        let a = CString::new("intern-key").unwrap();
+        let b = CString::new("intern-key").unwrap();
+        let first = unsafe { cstr_intern(a.as_ptr()) }.unwrap();
+        let second = unsafe { cstr_intern(b.as_ptr()) }.unwrap();
+        assert_eq!(first, "intern-key");
+        assert!(std::ptr::eq(first, second));
+    }
+
+    #[test]
+    fn test_cstr_intern_errors() {
+        assert_eq!(unsafe { cstr_intern(std::ptr::null()) }, Err(FfiError::NullPointer));
+        let bad = CString::new(vec![0xff, 0xfe]).unwrap();
+        assert_eq!(unsafe { cstr_intern(bad.as_ptr()) }, Err(FfiError::InvalidUtf8));
+    }
+
+    #[test]
+    fn test_fnv1a_known_values() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
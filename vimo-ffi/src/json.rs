@@ -0,0 +1,226 @@
+//! JSON 格式的错误输出
+//!
+//! 便于宿主（如 Electron）直接解析错误码，而不是匹配错误文本。
+//! 输出始终是单行 JSON，控制字符（包括 NUL）都会被转义，仍可通过 `char*` 通道传递。
+//!
+//! `FfiError` 的序列化形式固定为 `{"code":i32,"message":String}`，只依赖稳定错误码，
+//! 增加变体不会破坏已经持久化的数据。`FfiErrorCode` 序列化为稳定名称（如 `"NULL_POINTER"`）。
+
+use std::ffi::c_char;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::write_error;
+use crate::message_limit::cap_message;
+use crate::string::sanitized_cstring;
+use crate::translate::translate;
+use crate::{FfiError, FfiErrorCode};
+
+/// 边界函数写出错误时使用的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// 纯文本（默认）
+    Plain,
+    /// `{"code":2,"message":"...","context":"..."}`
+    Json,
+}
+
+static ERROR_FORMAT: AtomicU8 = AtomicU8::new(ErrorFormat::Plain as u8);
+
+/// 设置全局错误输出格式，对所有线程生效
+pub fn set_error_format(format: ErrorFormat) {
+    ERROR_FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+/// 当前的全局错误输出格式
+pub fn error_format() -> ErrorFormat {
+    match ERROR_FORMAT.load(Ordering::Relaxed) {
+        x if x == ErrorFormat::Json as u8 => ErrorFormat::Json,
+        _ => ErrorFormat::Plain,
+    }
+}
+
+#[derive(Serialize)]
+struct JsonError<'a> {
+    code: i32,
+    message: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct StoredError {
+    code: i32,
+    message: String,
+}
+
+/// 序列化为 `{"code":i32,"message":String}`，消息为未经翻译的 `Display` 文本
+impl Serialize for FfiError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let message = self.to_string();
+        JsonError {
+            code: self.code(),
+            message: &message,
+            context: None,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// 按 `FfiError::from_code` 还原，保留错误码和消息；未知的保留错误码还原为 `Custom`
+impl<'de> Deserialize<'de> for FfiError {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stored = StoredError::deserialize(deserializer)?;
+        Ok(FfiError::from_code(stored.code, stored.message))
+    }
+}
+
+/// 序列化为稳定名称（见 `FfiErrorCode::name`）
+impl Serialize for FfiErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+/// 从稳定名称还原，未知名称返回错误
+impl<'de> Deserialize<'de> for FfiErrorCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = std::borrow::Cow::<str>::deserialize(deserializer)?;
+        FfiErrorCode::from_name(&name)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown error code name {name:?}")))
+    }
+}
+
+impl FfiError {
+    /// 序列化为 JSON 字符串（格式见 `Serialize` 实现）
+    ///
+    /// 返回的字符串必须由调用者使用 `vimo_ffi_free_string` 释放。
+    pub fn to_json_cstring(&self) -> *mut c_char {
+        // 字段都是字符串和整数，序列化不会失败
+        let json = serde_json::to_string(self).unwrap_or_default();
+        sanitized_cstring(&json).into_raw()
+    }
+}
+
+/// 将错误码、消息和上下文渲染为单行 JSON
+pub(crate) fn render_json(code: i32, message: &str, context: Option<&str>) -> String {
+    let error = JsonError {
+        code,
+        message,
+        context,
+    };
+    // 字段都是字符串和整数，序列化不会失败
+    serde_json::to_string(&error).unwrap_or_default()
+}
+
+/// 以 JSON 格式设置 FFI 错误输出指针
+///
+/// 不受 `set_error_format` 影响，总是输出 JSON。`message` 字段经过翻译函数（见 `set_error_translator`）。
+///
+/// # Safety
+/// 同 `set_error`
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// // {"code":2,"message":"invalid UTF-8 string","context":"while reading title"}
+/// unsafe { set_error_json(out_error, &FfiError::InvalidUtf8, Some("while reading title")) };
+/// ```
+pub unsafe fn set_error_json(out_error: *mut *mut c_char, err: &FfiError, context: Option<&str>) {
+    let message = err.to_string();
+    let message = cap_message(translate(err.code(), &message));
+    write_error(out_error, &render_json(err.code(), &message, context));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::ptr;
+
+    fn take(ptr: *mut c_char) -> serde_json::Value {
+        let s = unsafe { CString::from_raw(ptr) };
+        let s = s.to_str().unwrap();
+        assert!(!s.contains('\n'));
+        serde_json::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_set_error_json() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        unsafe { set_error_json(&mut error_ptr, &FfiError::InvalidUtf8, Some("while reading title")) };
+        let value = take(error_ptr);
+        assert_eq!(value["code"], 2);
+        assert_eq!(value["message"], "invalid UTF-8 string");
+        assert_eq!(value["context"], "while reading title");
+    }
+
+    #[test]
+    fn test_set_error_json_escaping() {
+        let err = FfiError::custom("say \"hi\"\nsecond line\0after nul");
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        unsafe { set_error_json(&mut error_ptr, &err, None) };
+        let value = take(error_ptr);
+        assert_eq!(value["message"], "say \"hi\"\nsecond line\0after nul");
+        assert!(value.get("context").is_none());
+    }
+
+    #[test]
+    fn test_ffi_error_serde_roundtrip() {
+        let errors = [
+            FfiError::NullPointer,
+            FfiError::Timeout,
+            FfiError::NotFound("no such key: theme".into()),
+            FfiError::custom("my error"),
+            FfiError::custom_with_code(1234, "app error"),
+        ];
+        for err in errors {
+            let json = serde_json::to_string(&err).unwrap();
+            assert_eq!(serde_json::from_str::<FfiError>(&json).unwrap(), err);
+        }
+        assert_eq!(
+            serde_json::to_string(&FfiError::NotFound("theme".into())).unwrap(),
+            r#"{"code":7,"message":"theme"}"#
+        );
+
+        // 字段无法还原的变体保留错误码和消息
+        let io = FfiError::from(std::io::Error::other("disk on fire"));
+        let back: FfiError = serde_json::from_str(&serde_json::to_string(&io).unwrap()).unwrap();
+        assert_eq!((back.code(), back.to_string()), (io.code(), io.to_string()));
+    }
+
+    #[test]
+    fn test_ffi_error_deserialize_unknown_code() {
+        // 未来版本新增的内置错误码，旧版本读取时退化为 Custom
+        let json = r#"{"code":42,"message":"from the future"}"#;
+        let err: FfiError = serde_json::from_str(json).unwrap();
+        assert_eq!(err, FfiError::custom("from the future"));
+        assert!(serde_json::from_str::<FfiError>(r#"{"message":"no code"}"#).is_err());
+    }
+
+    #[test]
+    fn test_error_code_serde() {
+        assert_eq!(serde_json::to_string(&FfiErrorCode::NullPointer).unwrap(), r#""NULL_POINTER""#);
+        for code in [FfiErrorCode::Ok, FfiErrorCode::Panic, FfiErrorCode::BufferTooSmall] {
+            let json = serde_json::to_string(&code).unwrap();
+            assert_eq!(serde_json::from_str::<FfiErrorCode>(&json).unwrap(), code);
+        }
+        let err = serde_json::from_str::<FfiErrorCode>(r#""NullPointer""#).unwrap_err();
+        assert!(err.to_string().contains("unknown error code name \"NullPointer\""), "{err}");
+    }
+
+    #[test]
+    fn test_to_json_cstring() {
+        // NUL 和换行被 JSON 转义，结果仍是单行 C 字符串
+        let value = take(FfiError::custom("nul\0inside\nline").to_json_cstring());
+        assert_eq!(value["code"], 100);
+        assert_eq!(value["message"], "nul\0inside\nline");
+    }
+
+    #[test]
+    fn test_render_json_single_line() {
+        let json = render_json(100, "a\r\nb", Some("c\td"));
+        assert_eq!(json, r#"{"code":100,"message":"a\r\nb","context":"c\td"}"#);
+    }
+}
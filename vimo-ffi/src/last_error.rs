@@ -0,0 +1,244 @@
+//! 线程局部的最近一次错误
+//!
+//! 边界函数失败时记录错误码、消息、严重级别、是否 panic 以及导出函数名，
+//! 进入下一次边界调用时整体清空。C 侧可以在调用返回后直接查询，无需传入 `out_error`。
+
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::ffi::{c_char, CStr};
+use std::ptr;
+use std::sync::Mutex;
+
+use crate::message_limit::cap_message;
+use crate::Severity;
+
+/// 当前线程最近一次边界调用的错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastError {
+    /// 错误码（见 `FfiErrorCode`）
+    pub code: i32,
+    /// 原始英文消息（未经翻译）
+    pub message: String,
+    /// 严重级别
+    pub severity: Severity,
+    /// 是否由 panic 引起（边界捕获的 panic，或在 panic 展开期间被调用）
+    pub was_panic: bool,
+    /// 导出函数名，只有 `ffi_boundary_named` 会填写
+    pub function: Option<&'static str>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<LastError>> = const { RefCell::new(None) };
+    static CURRENT_FUNCTION: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+/// 记录当前线程最近一次的错误，函数名取自正在执行的 `ffi_boundary_named`
+pub(crate) fn record_last_error(code: i32, message: &str, severity: Severity, was_panic: bool) {
+    let function = current_function();
+    LAST_ERROR.with(|last| {
+        *last.borrow_mut() = Some(LastError {
+            code,
+            message: cap_message(Cow::Borrowed(message)).into_owned(),
+            severity,
+            was_panic,
+            function,
+        });
+    });
+}
+
+/// 在 `f` 执行期间把当前线程的导出函数名设为 `name`，返回时恢复（允许嵌套）
+pub(crate) fn with_function_name<R>(name: &'static str, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<&'static str>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CURRENT_FUNCTION.with(|current| current.set(self.0));
+        }
+    }
+
+    let _restore = Restore(CURRENT_FUNCTION.with(|current| current.replace(Some(name))));
+    f()
+}
+
+/// 当前线程正在执行的导出函数名
+pub(crate) fn current_function() -> Option<&'static str> {
+    CURRENT_FUNCTION.with(Cell::get)
+}
+
+/// 清空当前线程最近一次的错误
+pub(crate) fn clear_last_error() {
+    LAST_ERROR.with(|last| last.borrow_mut().take());
+}
+
+fn with_last_error<R>(f: impl FnOnce(Option<&LastError>) -> R) -> R {
+    LAST_ERROR.with(|last| f(last.borrow().as_ref()))
+}
+
+/// 当前线程最近一次边界调用的错误，没有错误时返回 None
+pub fn last_error() -> Option<LastError> {
+    with_last_error(|e| e.cloned())
+}
+
+/// 当前线程最近一次边界调用的错误码，没有错误时返回 0
+#[no_mangle]
+pub extern "C" fn vimo_ffi_last_error_code() -> i32 {
+    with_last_error(|e| e.map_or(0, |e| e.code))
+}
+
+/// 当前线程最近一次边界调用的严重级别（取值见 `Severity`），没有错误时返回 0
+#[no_mangle]
+pub extern "C" fn vimo_ffi_last_error_severity() -> i32 {
+    with_last_error(|e| e.map_or(0, |e| e.severity as i32))
+}
+
+/// 当前线程最近一次边界调用的错误消息，没有错误时返回 null
+///
+/// 消息中的 NUL 转义为 `\0`，与写入 `out_error` 的消息一致。
+/// 返回的字符串必须由调用者使用 `vimo_ffi_free_string` 释放。
+#[no_mangle]
+pub extern "C" fn vimo_ffi_last_error_message() -> *mut c_char {
+    with_last_error(|e| {
+        e.map_or(ptr::null_mut(), |e| {
+            crate::string::sanitized_cstring(&e.message).into_raw()
+        })
+    })
+}
+
+/// 当前线程最近一次边界调用的错误是否由 panic 引起，没有错误时返回 false
+#[no_mangle]
+pub extern "C" fn vimo_ffi_last_error_was_panic() -> bool {
+    with_last_error(|e| e.is_some_and(|e| e.was_panic))
+}
+
+static FUNCTION_NAMES: Mutex<BTreeMap<&'static str, &'static CStr>> = Mutex::new(BTreeMap::new());
+
+/// 函数名的 C 字符串，每个名称只分配一次
+fn intern_function_name(name: &'static str) -> &'static CStr {
+    let mut names = FUNCTION_NAMES.lock().unwrap_or_else(|e| e.into_inner());
+    // 函数名来自代码中的字面量，集合有限，泄漏的字符串在进程生命周期内有效
+    names
+        .entry(name)
+        .or_insert_with(|| Box::leak(crate::string::sanitized_cstring(name).into_boxed_c_str()))
+}
+
+/// 当前线程最近一次边界调用的导出函数名，没有错误或没有名称时返回 null
+///
+/// 返回的字符串由本库持有，在进程生命周期内有效，**不要释放**。
+#[no_mangle]
+pub extern "C" fn vimo_ffi_last_error_function() -> *const c_char {
+    let function = with_last_error(|e| e.and_then(|e| e.function));
+    function.map_or(ptr::null(), |name| intern_function_name(name).as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffi_boundary, ffi_boundary_named, FfiError};
+    use std::ffi::CString;
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    use crate::{ffi_boundary_code, ffi_boundary_structured, vimo_ffi_free_error, VimoError};
+
+    fn last_message() -> Option<String> {
+        let ptr = vimo_ffi_last_error_message();
+        if ptr.is_null() {
+            return None;
+        }
+        Some(unsafe { CString::from_raw(ptr) }.into_string().unwrap())
+    }
+
+    #[test]
+    fn test_boundary_records_last_error() {
+        let failure = FfiError::custom_with_severity("try again", Severity::Warning);
+        let ok = ffi_boundary(ptr::null_mut(), true, || Err(failure));
+        assert!(ok);
+        assert_eq!(vimo_ffi_last_error_code(), 100);
+        assert_eq!(vimo_ffi_last_error_severity(), Severity::Warning as i32);
+        assert_eq!(last_message().as_deref(), Some("try again"));
+
+        // 成功的调用清空上一次的错误
+        ffi_boundary(ptr::null_mut(), (), || Ok::<_, FfiError>(()));
+        assert_eq!(vimo_ffi_last_error_code(), 0);
+        assert_eq!(vimo_ffi_last_error_severity(), 0);
+        assert_eq!(last_message(), None);
+
+        // 消息中的 NUL 与 out_error 一样转义，而不是返回 null
+        ffi_boundary(ptr::null_mut(), (), || Err(FfiError::custom("x\0y")));
+        assert_eq!(last_message().as_deref(), Some("x\\0y"));
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_panic_is_fatal() {
+        ffi_boundary_code(ptr::null_mut(), || -> Result<(), FfiError> { panic!("boom") });
+        assert_eq!(vimo_ffi_last_error_code(), -1000);
+        assert_eq!(vimo_ffi_last_error_severity(), Severity::Fatal as i32);
+        assert_eq!(last_message().as_deref(), Some("internal panic: boom"));
+
+        let mut err: *mut VimoError = ptr::null_mut();
+        ffi_boundary_structured(&mut err, (), || -> Result<(), FfiError> { panic!("boom") });
+        assert_eq!(unsafe { (*err).severity }, Severity::Fatal as i32);
+        unsafe { vimo_ffi_free_error(err) };
+        assert!(vimo_ffi_last_error_was_panic());
+    }
+
+    #[test]
+    fn test_named_boundary_records_function() {
+        ffi_boundary_named("vimo_doc_open", ptr::null_mut(), false, || {
+            Err::<bool, _>(FfiError::NotFound("doc.txt".into()))
+        });
+        assert_eq!(
+            last_error(),
+            Some(LastError {
+                code: 7,
+                message: "doc.txt".into(),
+                severity: Severity::Error,
+                was_panic: false,
+                function: Some("vimo_doc_open"),
+            })
+        );
+        assert!(!vimo_ffi_last_error_was_panic());
+        let name = vimo_ffi_last_error_function();
+        assert_eq!(unsafe { CStr::from_ptr(name) }.to_str().unwrap(), "vimo_doc_open");
+        // 同一名称只驻留一次，指针稳定
+        assert_eq!(vimo_ffi_last_error_function(), name);
+
+        // 成功的调用一次性清空所有字段
+        ffi_boundary_named("vimo_doc_open", ptr::null_mut(), (), || Ok::<_, FfiError>(()));
+        assert_eq!(last_error(), None);
+        assert_eq!(vimo_ffi_last_error_code(), 0);
+        assert!(!vimo_ffi_last_error_was_panic());
+        assert!(vimo_ffi_last_error_function().is_null());
+    }
+
+    #[test]
+    fn test_function_name_scoped_to_named_boundary() {
+        ffi_boundary_named("outer", ptr::null_mut(), (), || {
+            ffi_boundary_named("inner", ptr::null_mut(), (), || Ok::<_, FfiError>(()));
+            assert_eq!(current_function(), Some("outer"));
+            Ok::<_, FfiError>(())
+        });
+        assert_eq!(current_function(), None);
+
+        ffi_boundary(ptr::null_mut(), (), || Err::<(), _>(FfiError::NullPointer));
+        assert_eq!(last_error().unwrap().function, None);
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_last_error_is_thread_local() {
+        ffi_boundary_named("main_thread", ptr::null_mut(), (), || {
+            Err::<(), _>(FfiError::NullPointer)
+        });
+        std::thread::spawn(|| {
+            assert_eq!(last_error(), None);
+            ffi_boundary_code(ptr::null_mut(), || -> Result<(), FfiError> { panic!("worker") });
+            assert!(vimo_ffi_last_error_was_panic());
+            assert!(vimo_ffi_last_error_function().is_null());
+        })
+        .join()
+        .unwrap();
+        let last = last_error().unwrap();
+        assert_eq!((last.code, last.was_panic, last.function), (1, false, Some("main_thread")));
+    }
+}
@@ -4,6 +4,7 @@
 //! - Panic 捕获，防止跨 FFI 边界传播
 //! - C 字符串转换工具
 //! - 统一的错误处理模式
+//! - RAII 资源清理守卫
 //!
 //! # 使用示例
 //!
@@ -24,11 +25,122 @@
 //!     })
 //! }
 //! ```
+//!
+//! # Features
+//!
+//! - `catch-unwind`（默认启用）: 边界函数用 `catch_unwind` 捕获 panic。`panic = "abort"` 的构建
+//!   可以关闭（用 `panic = "abort"` 编译时也会自动跳过），边界只负责 `Result` 到错误的转换
+//! - `tracing`: `ffi_boundary` 在 tracing span 中执行并记录成功状态；`ffi_boundary_traced`
+//!   记录结果和耗时，失败时输出 `error!` 事件；`ffi_boundary_simple` 的 panic 改为 `error!` 输出
+//! - `intern`: 提供 `cstr_intern` 字符串驻留（依赖 `dashmap`）
+//! - `serde`: JSON 格式的错误输出（`set_error_json`、`set_error_format`），`FfiError` 的序列化
+//! - `backtrace`: 错误消息可附带调用栈（运行时由 `set_backtrace_capture` 开启）
+//! - `metrics`: 边界调用、错误、panic 的原子计数（`vimo_ffi_get_metrics`），
+//!   `ffi_boundary_named` 按函数名分别计数（`vimo_ffi_stats_json`）
+//! - `anyhow`: `ffi_boundary_anyhow` / `set_error_anyhow`，以 `{:#}` 保留完整 context 链
+//! - `miette`: `ffi_boundary_miette`，错误渲染为带源码标注的诊断（`serde` 下可输出 JSON）
+//! - `tokio`: `ffi_boundary_async`，在当前线程的 tokio 运行时上运行 future
+//! - `url`: `cstr_to_url` / `url_to_cstring`，C 字符串与 `url::Url` 互转
+//! - `phf`: `ERROR_CODE_NAMES` / `ERROR_CODE_VALUES`，错误码与名称的编译期完美哈希表
+//! - `pprof`: `set_pprof_enabled` 后边界调用期间用 pprof 采样，
+//!   `vimo_ffi_pprof_report` 输出火焰图 SVG（仅 Unix）
+//! - `log`: 边界函数的错误和 panic 通过 `log` crate 输出（级别由 `FfiBoundaryOptions::log_level` 设置）
+//! - `test-mode`: 所有边界函数在记录后重新抛出闭包中的 panic，让测试断言能正常失败；不要在发布构建中启用
+
+// 边界函数按 C 惯例接收 `out_error` 裸指针，且对 null 容忍，
+// 因此刻意保持为安全函数。
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
 
+#[cfg(feature = "anyhow")]
+mod anyhow_support;
+#[cfg(feature = "backtrace")]
+mod backtrace;
 mod panic;
+mod panic_handler;
+mod panic_hook;
+mod panic_info;
+mod panic_payload;
+mod panic_policy;
 mod string;
+mod thread;
+mod translate;
+mod verbosity;
+mod context;
+mod errno;
 mod error;
+mod error_list;
+mod ffi_default;
+mod ffi_result;
+mod guard;
+mod hresult;
+mod last_error;
+#[cfg(feature = "log")]
+mod log_support;
+mod message_limit;
+#[cfg(feature = "miette")]
+mod miette_support;
+mod observer;
+mod poison;
+#[cfg(all(feature = "pprof", unix))]
+mod pprof_support;
+mod redact;
+mod reentrancy;
+mod registry;
+#[cfg(debug_assertions)]
+mod stack_check;
+#[cfg(feature = "intern")]
+mod intern;
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "tokio")]
+mod tokio_support;
+#[cfg(feature = "url")]
+mod url_support;
+#[cfg(test)]
+mod test_alloc;
 
+#[cfg(feature = "anyhow")]
+pub use anyhow_support::*;
+#[cfg(feature = "backtrace")]
+pub use backtrace::*;
 pub use panic::*;
+pub use panic_handler::*;
+pub use panic_hook::*;
+pub use panic_info::*;
+pub use panic_payload::*;
+pub use panic_policy::*;
 pub use string::*;
+pub use thread::*;
+pub use translate::*;
+pub use verbosity::*;
+pub use context::*;
+pub use errno::*;
 pub use error::*;
+pub use error_list::*;
+pub use ffi_default::*;
+pub use ffi_result::*;
+pub use guard::*;
+pub use hresult::*;
+pub use last_error::*;
+#[cfg(feature = "log")]
+pub use log_support::*;
+pub use message_limit::*;
+pub use observer::*;
+pub use poison::*;
+#[cfg(all(feature = "pprof", unix))]
+pub use pprof_support::*;
+pub use redact::*;
+pub use reentrancy::*;
+pub use registry::*;
+#[cfg(feature = "intern")]
+pub use intern::*;
+#[cfg(feature = "serde")]
+pub use json::*;
+#[cfg(feature = "metrics")]
+pub use metrics::*;
+#[cfg(feature = "tokio")]
+pub use tokio_support::NESTED_RUNTIME_MESSAGE;
+#[cfg(feature = "url")]
+pub use url_support::*;
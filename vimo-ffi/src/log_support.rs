@@ -0,0 +1,42 @@
+//! `log` crate 集成
+//!
+//! 通过 `out_error` 返回的错误对应用自己的日志系统不可见；启用 `log` feature 后，
+//! 边界函数在错误和 panic 时各输出一条日志。调用方传入 null `out_error` 时，
+//! 这条日志就是失败的唯一记录。
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static LOG_LEVEL: AtomicUsize = AtomicUsize::new(log::Level::Error as usize);
+
+/// 边界函数的全局选项
+pub struct FfiBoundaryOptions;
+
+impl FfiBoundaryOptions {
+    /// 设置边界错误日志的级别（默认 `Error`），对所有线程生效
+    ///
+    /// # 示例
+    ///
+    /// ```rust,ignore
+    /// // 校验失败很常见，降为 warn 避免刷屏
+    /// FfiBoundaryOptions::log_level(log::Level::Warn);
+    /// ```
+    pub fn log_level(level: log::Level) {
+        LOG_LEVEL.store(level as usize, Ordering::Relaxed);
+    }
+
+    /// 当前的边界错误日志级别
+    pub fn current_log_level() -> log::Level {
+        match LOG_LEVEL.load(Ordering::Relaxed) {
+            x if x == log::Level::Warn as usize => log::Level::Warn,
+            x if x == log::Level::Info as usize => log::Level::Info,
+            x if x == log::Level::Debug as usize => log::Level::Debug,
+            x if x == log::Level::Trace as usize => log::Level::Trace,
+            _ => log::Level::Error,
+        }
+    }
+}
+
+/// 以配置的级别记录一条边界错误
+pub(crate) fn log_failure(message: &str) {
+    log::log!(FfiBoundaryOptions::current_log_level(), "[vimo-ffi] error: {}", message);
+}
@@ -0,0 +1,72 @@
+//! 错误消息长度上限
+//!
+//! 防止下游把整个请求体之类的大块数据拼进错误消息，再被宿主反复复制和记录。
+//! 超过上限的消息在字符边界处截断，并附加 `"... (truncated, N bytes total)"`。
+
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// 默认的错误消息长度上限（字节）
+pub const DEFAULT_MAX_ERROR_LEN: usize = 8 * 1024;
+
+static MAX_ERROR_LEN: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_ERROR_LEN);
+
+/// 设置全局错误消息长度上限（字节），`0` 表示不限制，对所有线程生效
+///
+/// 作用于所有写出的错误消息（`set_error`、`set_error_from`、边界函数的错误和 panic 等）
+/// 以及线程局部的最近一次错误。上限针对截断后保留的消息部分，不含截断说明，
+/// 也不含 `backtrace` feature 附加的调用栈；JSON 格式下截断的是 `message` 字段。
+pub fn set_max_error_len(max_len: usize) {
+    MAX_ERROR_LEN.store(max_len, Ordering::Relaxed);
+}
+
+/// 当前的全局错误消息长度上限，`0` 表示不限制
+pub fn max_error_len() -> usize {
+    MAX_ERROR_LEN.load(Ordering::Relaxed)
+}
+
+/// 按全局脱敏函数处理后再按全局上限截断，未超出时原样返回
+///
+/// 所有写出的消息都经过这里，脱敏放在截断之前，截断不会留下半个令牌。
+pub(crate) fn cap_message(message: Cow<'_, str>) -> Cow<'_, str> {
+    cap_message_to(crate::redact::redact(message), max_error_len())
+}
+
+fn cap_message_to(message: Cow<'_, str>, max_len: usize) -> Cow<'_, str> {
+    if max_len == 0 || message.len() <= max_len {
+        return message;
+    }
+    let kept = crate::truncate_at_char_boundary(&message, max_len);
+    Cow::Owned(format!("{kept}... (truncated, {} bytes total)", message.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cap(s: &str, max_len: usize) -> String {
+        cap_message_to(Cow::Borrowed(s), max_len).into_owned()
+    }
+
+    #[test]
+    fn test_cap_message() {
+        assert_eq!(cap("short", 8), "short");
+        assert_eq!(cap("exactly8", 8), "exactly8");
+        assert_eq!(cap("nine long", 8), "nine lon... (truncated, 9 bytes total)");
+        assert_eq!(cap(&"x".repeat(100), 0), "x".repeat(100));
+    }
+
+    #[test]
+    fn test_cap_message_char_boundary() {
+        // "é" 占两个字节，上限落在它中间时整个字符都不保留
+        let s = "abcé";
+        assert_eq!(cap(s, 4), "abc... (truncated, 5 bytes total)");
+        assert_eq!(cap("日本語", 4), "日... (truncated, 9 bytes total)");
+    }
+
+    #[test]
+    fn test_cap_message_borrows_when_short() {
+        let capped = cap_message_to(Cow::Borrowed("fits"), DEFAULT_MAX_ERROR_LEN);
+        assert!(matches!(capped, Cow::Borrowed("fits")));
+    }
+}
@@ -0,0 +1,198 @@
+//! FFI 边界调用计数
+//!
+//! 全部基于原子操作，开销只有几次 `fetch_add`，适合常开用于线上监控。
+//! `ffi_boundary_named` 另外按导出函数名分别计数，可通过 `vimo_ffi_stats_json` 查询。
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::c_char;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// 边界调用计数器
+#[derive(Debug)]
+pub struct FfiBoundaryMetrics {
+    /// 边界函数调用总次数
+    pub total_calls: AtomicU64,
+    /// 捕获到的 panic 次数
+    pub panics: AtomicU64,
+    /// 闭包返回错误的次数（不含 panic）
+    pub errors: AtomicU64,
+}
+
+/// 计数器快照，可按值返回给 C 侧
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FfiMetricsSnapshot {
+    pub total_calls: u64,
+    pub panics: u64,
+    pub errors: u64,
+}
+
+/// 进程级计数器，所有边界函数都会更新
+pub static GLOBAL_METRICS: FfiBoundaryMetrics = FfiBoundaryMetrics::new();
+
+impl FfiBoundaryMetrics {
+    /// 创建归零的计数器
+    pub const fn new() -> Self {
+        Self {
+            total_calls: AtomicU64::new(0),
+            panics: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    /// 读取当前计数
+    pub fn snapshot(&self) -> FfiMetricsSnapshot {
+        FfiMetricsSnapshot {
+            total_calls: self.total_calls.load(Ordering::Relaxed),
+            panics: self.panics.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 所有计数归零
+    pub fn reset(&self) {
+        self.total_calls.store(0, Ordering::Relaxed);
+        self.panics.store(0, Ordering::Relaxed);
+        self.errors.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Default for FfiBoundaryMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 获取全局计数器快照
+#[no_mangle]
+pub extern "C" fn vimo_ffi_get_metrics() -> FfiMetricsSnapshot {
+    GLOBAL_METRICS.snapshot()
+}
+
+/// 全局计数器归零
+#[no_mangle]
+pub extern "C" fn vimo_ffi_reset_metrics() {
+    GLOBAL_METRICS.reset();
+}
+
+/// 按导出函数名注册的计数器，注册后永不移除，因此可以交出 `&'static` 引用
+static FUNCTION_METRICS: Mutex<BTreeMap<&'static str, &'static FfiBoundaryMetrics>> =
+    Mutex::new(BTreeMap::new());
+
+thread_local! {
+    /// 已查找过的计数器，命中后更新计数不再加锁
+    static FUNCTION_CACHE: RefCell<HashMap<&'static str, &'static FfiBoundaryMetrics>> =
+        RefCell::new(HashMap::new());
+}
+
+/// 导出函数 `name` 的计数器，首次使用时注册
+///
+/// 每个线程首次查找某个函数名时加一次全局锁，之后从线程局部缓存中取得，
+/// 更新计数只有原子操作。在初始化时调用可以预先注册，
+/// 让尚未被调用的函数也以零计数出现在 `vimo_ffi_stats_json` 中。
+pub fn function_metrics(name: &'static str) -> &'static FfiBoundaryMetrics {
+    FUNCTION_CACHE
+        .try_with(|cache| {
+            if let Some(metrics) = cache.borrow().get(name) {
+                return *metrics;
+            }
+            let metrics = register_function(name);
+            cache.borrow_mut().insert(name, metrics);
+            metrics
+        })
+        .unwrap_or_else(|_| register_function(name))
+}
+
+fn register_function(name: &'static str) -> &'static FfiBoundaryMetrics {
+    let mut functions = FUNCTION_METRICS.lock().unwrap_or_else(|e| e.into_inner());
+    functions
+        .entry(name)
+        .or_insert_with(|| Box::leak(Box::new(FfiBoundaryMetrics::new())))
+}
+
+/// 所有已注册函数的计数快照，按函数名排序
+pub fn function_metrics_snapshot() -> Vec<(&'static str, FfiMetricsSnapshot)> {
+    let functions = FUNCTION_METRICS.lock().unwrap_or_else(|e| e.into_inner());
+    functions.iter().map(|(name, metrics)| (*name, metrics.snapshot())).collect()
+}
+
+/// 为 `ffi_boundary_named` 包装闭包：计入调用，闭包返回错误或 panic 时分别计数
+///
+/// panic 在展开经过闭包时计数，因此 `Rethrow` 策略和 `test-mode` 下同样会被统计。
+pub(crate) fn counted<T, E>(
+    name: &'static str,
+    f: impl FnOnce() -> Result<T, E>,
+) -> impl FnOnce() -> Result<T, E> {
+    struct CountPanic(&'static FfiBoundaryMetrics);
+
+    impl Drop for CountPanic {
+        fn drop(&mut self) {
+            self.0.panics.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let metrics = function_metrics(name);
+    metrics.total_calls.fetch_add(1, Ordering::Relaxed);
+    move || {
+        let guard = CountPanic(metrics);
+        let result = f();
+        std::mem::forget(guard);
+        if result.is_err() {
+            metrics.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+/// 按函数统计的 JSON：`{"fn_name": {"calls": n, "errors": n, "panics": n}, ...}`
+pub fn function_stats_json() -> String {
+    let mut json = String::from("{");
+    for (i, (name, stats)) in function_metrics_snapshot().into_iter().enumerate() {
+        if i > 0 {
+            json.push_str(", ");
+        }
+        write_json_string(&mut json, name);
+        let _ = write!(
+            json,
+            ": {{\"calls\": {}, \"errors\": {}, \"panics\": {}}}",
+            stats.total_calls, stats.errors, stats.panics
+        );
+    }
+    json.push('}');
+    json
+}
+
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// 按函数统计的 JSON，见 `function_stats_json`
+///
+/// 返回的字符串必须由调用者使用 `vimo_ffi_free_string` 释放。
+#[no_mangle]
+pub extern "C" fn vimo_ffi_stats_json() -> *mut c_char {
+    crate::string::sanitized_cstring(&function_stats_json()).into_raw()
+}
+
+/// 所有按函数统计的计数归零，已注册的函数保留
+#[no_mangle]
+pub extern "C" fn vimo_ffi_stats_reset() {
+    let functions = FUNCTION_METRICS.lock().unwrap_or_else(|e| e.into_inner());
+    for metrics in functions.values() {
+        metrics.reset();
+    }
+}
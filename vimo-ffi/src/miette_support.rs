@@ -0,0 +1,105 @@
+//! `miette` 集成
+//!
+//! `miette::Report` 渲染为带源码标注的诊断文本，通过 FFI 边界原样交给宿主。
+//! 输出不含终端颜色，也不查询终端宽度，可以直接写入日志或 UI。
+
+use miette::{GraphicalReportHandler, GraphicalTheme};
+
+use crate::{FfiError, FfiErrorCode, Severity};
+
+/// 让 `FfiError` 可以直接用 `?` 转换为 `miette::Report`，错误码在边界处仍能取回
+impl miette::Diagnostic for FfiError {}
+
+/// 渲染为图形化诊断文本（Unicode 边框，无颜色）
+pub(crate) fn render_graphical(report: &miette::Report) -> String {
+    let mut out = String::new();
+    let handler = GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor());
+    match handler.render_report(&mut out, report.as_ref()) {
+        Ok(()) => out.trim_end().to_owned(),
+        Err(_) => report.to_string(),
+    }
+}
+
+/// 渲染为 miette 的 JSON 诊断格式
+#[cfg(feature = "serde")]
+pub(crate) fn render_json(report: &miette::Report) -> String {
+    let mut out = String::new();
+    match miette::JSONReportHandler::new().render_report(&mut out, report.as_ref()) {
+        Ok(()) => out,
+        Err(_) => crate::json::render_json(miette_code(report), &report.to_string(), None),
+    }
+}
+
+/// `miette::Report` 对应的错误码：诊断本身是 `FfiError` 时取其错误码
+pub(crate) fn miette_code(report: &miette::Report) -> i32 {
+    match report.downcast_ref::<FfiError>() {
+        Some(e) => e.code(),
+        None => FfiErrorCode::Unknown as i32,
+    }
+}
+
+/// `miette::Report` 对应的严重级别：诊断本身是 `FfiError` 时取其严重级别
+pub(crate) fn miette_severity(report: &miette::Report) -> Severity {
+    match report.downcast_ref::<FfiError>() {
+        Some(e) => e.severity(),
+        None => Severity::Error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi_boundary_miette;
+    use miette::{Diagnostic, NamedSource, SourceSpan};
+    use std::ffi::{c_char, CString};
+    use std::ptr;
+
+    #[derive(Debug, thiserror::Error, Diagnostic)]
+    #[error("unexpected token")]
+    #[diagnostic(code(config::syntax), help("remove the trailing comma"))]
+    struct SyntaxError {
+        #[source_code]
+        src: NamedSource<String>,
+        #[label("here")]
+        span: SourceSpan,
+    }
+
+    fn syntax_error() -> miette::Report {
+        SyntaxError {
+            src: NamedSource::new("config.toml", "name = \"vimo\",\n".to_string()),
+            span: (13, 1).into(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_render_graphical() {
+        let rendered = render_graphical(&syntax_error());
+        assert!(rendered.contains("config::syntax"), "{rendered}");
+        assert!(rendered.contains("unexpected token"), "{rendered}");
+        assert!(rendered.contains("[config.toml:1:14]"), "{rendered}");
+        assert!(rendered.contains("name = \"vimo\","), "{rendered}");
+        assert!(rendered.contains("help: remove the trailing comma"), "{rendered}");
+        assert!(!rendered.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_miette_code() {
+        let err = miette::Report::new(FfiError::Timeout);
+        assert_eq!(miette_code(&err), FfiErrorCode::Timeout as i32);
+        assert_eq!(miette_code(&syntax_error()), FfiErrorCode::Unknown as i32);
+        assert_eq!(miette_severity(&miette::Report::new(FfiError::OutOfMemory)), Severity::Fatal);
+    }
+
+    #[test]
+    fn test_ffi_boundary_miette() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let ok = ffi_boundary_miette(&mut error_ptr, false, || Err(syntax_error()));
+        assert!(!ok);
+        let msg = unsafe { CString::from_raw(error_ptr) }.into_string().unwrap();
+        assert_eq!(msg, render_graphical(&syntax_error()));
+        assert_eq!(crate::vimo_ffi_last_error_code(), FfiErrorCode::Unknown as i32);
+
+        assert!(ffi_boundary_miette(ptr::null_mut(), false, || Ok(true)));
+    }
+}
@@ -0,0 +1,140 @@
+//! 全局错误观察者
+//!
+//! 供遥测在不改动各个导出函数的情况下统计和采样错误：所有边界函数的错误和 panic、
+//! 以及 `set_error` / `set_error_from` 写出的错误，都会以 `ErrorEvent` 通知观察者。
+//!
+//! 观察者在出错的线程上同步调用，其中的 panic 会被吞掉；
+//! 观察者内部再次触发的错误不会再通知观察者，避免无限递归。
+
+use std::cell::Cell;
+use std::ffi::{c_char, c_void};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::sync::{Arc, RwLock};
+
+/// 一次错误的描述，只在观察者调用期间有效
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorEvent<'a> {
+    /// 错误码（见 `FfiErrorCode`）
+    pub code: i32,
+    /// 原始英文消息（未经翻译）
+    pub message: &'a str,
+    /// 是否为边界处捕获的 panic
+    pub is_panic: bool,
+    /// 报告错误的导出函数名，只有 `ffi_boundary_named` 会填写
+    pub function: Option<&'a str>,
+}
+
+/// Rust 侧的错误观察者
+pub type ErrorObserver = Box<dyn Fn(&ErrorEvent) + Send + Sync>;
+
+/// C 侧看到的错误事件，字符串只在回调期间有效
+#[repr(C)]
+#[derive(Debug)]
+pub struct VimoErrorEvent {
+    pub code: i32,
+    pub is_panic: bool,
+    /// 以 NUL 结尾的 UTF-8 消息
+    pub message: *const c_char,
+    /// 导出函数名，没有时为 null
+    pub function: *const c_char,
+}
+
+/// C 侧的错误观察者，见 `vimo_ffi_set_error_observer`
+pub type CErrorObserver = extern "C" fn(event: *const VimoErrorEvent, user_data: *mut c_void);
+
+type SharedObserver = Arc<dyn Fn(&ErrorEvent) + Send + Sync>;
+
+static OBSERVER: RwLock<Option<SharedObserver>> = RwLock::new(None);
+
+thread_local! {
+    static IN_OBSERVER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// 安装全局错误观察者，替换之前的观察者，对所有线程生效
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// set_error_observer(Box::new(|event| {
+///     telemetry::count("ffi_error", event.code, event.is_panic);
+/// }));
+/// ```
+pub fn set_error_observer(observer: ErrorObserver) {
+    *OBSERVER.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::from(observer));
+}
+
+/// 移除全局错误观察者
+pub fn clear_error_observer() {
+    *OBSERVER.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+struct UserData(*mut c_void);
+
+// SAFETY: 按 `vimo_ffi_set_error_observer` 的约定，user_data 可以在任意线程上使用
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+impl UserData {
+    // 通过方法访问，让闭包捕获整个 `UserData` 而不是其中的裸指针
+    fn get(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// 从 C 侧安装全局错误观察者，传入 null 则移除
+///
+/// 回调在出错的线程上同步调用，`user_data` 原样传回，必须可以在任意线程上使用，
+/// 并且在观察者被替换或移除之前保持有效。回调不得跨越 FFI 边界 unwind。
+///
+/// # 示例
+///
+/// ```c
+/// void on_error(const VimoErrorEvent *event, void *user_data) {
+///     atomic_fetch_add((atomic_int *)user_data, 1);
+/// }
+/// vimo_ffi_set_error_observer(on_error, &error_count);
+/// ```
+#[no_mangle]
+pub extern "C" fn vimo_ffi_set_error_observer(
+    observer: Option<CErrorObserver>,
+    user_data: *mut c_void,
+) {
+    let Some(observer) = observer else {
+        clear_error_observer();
+        return;
+    };
+    let user_data = UserData(user_data);
+    set_error_observer(Box::new(move |event| {
+        let message = crate::string::sanitized_cstring(event.message);
+        let function = event.function.map(crate::string::sanitized_cstring);
+        let c_event = VimoErrorEvent {
+            code: event.code,
+            is_panic: event.is_panic,
+            message: message.as_ptr(),
+            function: function.as_deref().map_or(ptr::null(), |f| f.as_ptr()),
+        };
+        observer(&c_event, user_data.get());
+    }));
+}
+
+/// 通知观察者；观察者内部触发的错误不再通知
+pub(crate) fn notify_error(code: i32, message: &str, is_panic: bool) {
+    // 先复制出观察者再调用，观察者内部替换观察者时不会死锁
+    let observer = OBSERVER.read().unwrap_or_else(|e| e.into_inner()).clone();
+    let Some(observer) = observer else {
+        return;
+    };
+    if IN_OBSERVER.with(|flag| flag.replace(true)) {
+        return;
+    }
+    let message = crate::redact::redact(message.into());
+    let event = ErrorEvent {
+        code,
+        message: &message,
+        is_panic,
+        function: crate::last_error::current_function(),
+    };
+    let _ = catch_unwind(AssertUnwindSafe(|| observer(&event)));
+    IN_OBSERVER.with(|flag| flag.set(false));
+}
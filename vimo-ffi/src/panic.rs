@@ -1,12 +1,38 @@
 //! Panic 捕获工具
 //!
 //! Rust 的 panic 跨 FFI 边界是未定义行为，必须在边界处捕获。
+//!
+//! 捕获 panic 后进程继续运行，闭包 panic 时持有的锁会中毒。边界内访问的全局状态应使用
+//! `lock_recover`、`read_recover`、`write_recover` 或 `try_lock_recover` 加锁，
+//! 而不是 `lock().unwrap()`，否则一次 panic 之后的调用都会因 `PoisonError` 失败。
 
 use std::any::Any;
+use std::borrow::Cow;
 use std::ffi::c_char;
+#[cfg(all(feature = "catch-unwind", not(panic = "abort")))]
 use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::panic::UnwindSafe;
+#[cfg(feature = "metrics")]
+use std::sync::atomic::Ordering;
 
-use crate::set_error;
+use crate::error::{message_of, write_error};
+use crate::error_list::write_string_array;
+use crate::string::try_sanitized_cstring;
+use crate::last_error::{clear_last_error, record_last_error, with_function_name};
+use crate::message_limit::cap_message;
+use crate::observer::notify_error;
+use crate::panic_hook::{clear_panic_location, panic_location, PanicLocation};
+use crate::panic_handler::notify_panic_handler;
+use crate::panic_info::{record_panic_info, PanicEvent};
+use crate::panic_payload::payload_message;
+use crate::panic_policy::{panic_policy, PanicPolicy};
+use crate::translate::translate;
+use crate::verbosity::with_detail;
+use crate::{
+    code_of, format_error_chain, set_error_buf, set_errors, severity_of,
+    write_error_struct, ErrorList, FfiContext, FfiDefault, FfiError, FfiErrorCode, FfiResultType, Severity, VimoError,
+    ReentrancyGuard, ERROR_CHAIN_MAX_DEPTH, ERROR_CHAIN_SEPARATOR,
+};
 
 /// FFI 边界防护 - 捕获 panic 并转换为错误
 ///
@@ -17,6 +43,58 @@ use crate::set_error;
 /// - `default`: panic 或错误时返回的默认值
 /// - `f`: 要执行的闭包，返回 `Result<T, E>`
 ///
+/// 错误按全局 `ErrorFormat` 渲染（需要 `serde` feature，默认纯文本）；
+/// 启用 `backtrace` feature 并调用 `set_backtrace_capture(true)` 后，错误消息附带调用栈。
+/// 启用 `tracing` feature 时，每次调用都在 `ffi_boundary` span 内执行，
+/// 并在返回前记录 `success` 字段，调用耗时由 tracing 后端从 span 进出中得出。
+///
+/// 进入边界时先把 `*out_error` 置为 null（`out_error` 本身为 null 时忽略），
+/// 因此无论成功与否，返回后 `*out_error` 都是确定的值，调用方不必预先初始化。
+/// 带 `out_error` 的其它边界函数（`ffi_boundary_code`、`ffi_boundary_structured` 等）同样如此。
+///
+/// 所有边界函数都会把失败的错误码、消息和严重级别记录到线程局部的最近一次错误中
+/// （panic 总是 `Severity::Fatal`），可通过 `vimo_ffi_last_error_*` 查询；
+/// 启用 `log` feature 时同时输出 `[vimo-ffi] error: ...` 日志；安装了错误观察者时
+/// 同时通知观察者（见 `set_error_observer`）。
+///
+/// # 内存耗尽
+///
+/// 为错误消息分配内存失败时，`*out_error` 指向静态字符串 `OOM_MESSAGE`
+/// （`"[allocation failure during error reporting]"`），而不是留为 null，调用方不会把失败误认为成功。
+/// 静态消息交给 `vimo_ffi_free_string` 是安全的。指针上没有标记位，
+/// 需要区分时调用 `vimo_ffi_is_static_error`（按地址与 `OOM_MESSAGE` 比较）代替检查标记位。
+/// 需要在内存耗尽时保留原始消息的导出函数
+/// 使用 `ffi_boundary_oom_safe`，它在进入时预留错误缓冲区。
+///
+/// # 栈溢出
+///
+/// 栈溢出不是 panic：Rust 运行时在保护页上收到 `SIGSEGV`（Windows 上为
+/// `STATUS_STACK_OVERFLOW`）后直接终止进程，任何边界函数都无法捕获或报告。
+/// 深度递归的代码应自行限制深度并返回 `FfiError::StackOverflow`；
+/// 在 Rust 创建的线程上可通过 `RUST_MIN_STACK` 环境变量（或 `thread::Builder::stack_size`）
+/// 调大栈空间，宿主创建的线程（如 C 线程池）的栈大小由宿主决定。
+///
+/// 启用 `test-mode` feature 时，闭包中的 panic 在记录后通过 `resume_unwind` 重新抛出，
+/// 不会转换为错误（所有边界函数都是如此）。
+/// 运行时可以用 `set_panic_policy` 让所有边界函数终止进程或重新抛出 panic。
+/// 关闭默认的 `catch-unwind` feature，或以 `panic = "abort"` 编译时，所有边界函数都不再调用
+/// `catch_unwind`，闭包直接执行，panic 按运行时的策略展开或终止进程。
+///
+/// # Unwind 安全
+///
+/// 闭包被刻意包在 `AssertUnwindSafe` 中执行，因此不要求 `F: UnwindSafe`：
+/// 导出函数的闭包几乎总会捕获 `&mut` 参数或带内部可变性的句柄，要求该约束只会逼调用方
+/// 到处手写 `AssertUnwindSafe`。代价是 panic 后调用方可能观察到改了一半的状态。
+/// 闭包只读取参数、或 panic 后相关状态会被丢弃时，用本函数即可；
+/// 闭包修改在 panic 后仍会被继续使用的共享状态（如 `&RefCell`、全局缓存）时，
+/// 改用 `ffi_boundary_safe`，让编译器检查这些捕获。
+///
+/// # Safety
+///
+/// 如果在另一个 panic 正在展开时调用（例如从某个值的 `Drop` 中调用导出函数），
+/// 不会执行 `f`，也不会嵌套 `catch_unwind`：直接写出错误 `"called during unwinding"`
+/// （错误码 `FfiErrorCode::Panic`）并返回 `default`。
+///
 /// # 示例
 ///
 /// ```rust,ignore
@@ -30,117 +108,1455 @@ use crate::set_error;
 /// ```
 pub fn ffi_boundary<T, E, F>(out_error: *mut *mut c_char, default: T, f: F) -> T
 where
-    E: std::fmt::Display,
+    E: std::fmt::Display + 'static,
     F: FnOnce() -> Result<T, E>,
 {
-    match catch_unwind(AssertUnwindSafe(f)) {
+    reset_out_error(out_error);
+    if std::thread::panicking() {
+        let code = FfiErrorCode::Panic as i32;
+        record_last_error(code, UNWINDING_MESSAGE, Severity::Fatal, true);
+        report_error(out_error, code, UNWINDING_MESSAGE);
+        return default;
+    }
+
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!("ffi_boundary", success = tracing::field::Empty);
+    #[cfg(feature = "tracing")]
+    let _entered = span.enter();
+
+    let outcome = run_guarded(f);
+
+    #[cfg(feature = "tracing")]
+    span.record("success", matches!(outcome, Ok(Ok(_))));
+
+    match outcome {
         Ok(Ok(result)) => result,
         Ok(Err(e)) => {
-            unsafe { set_error(out_error, &e.to_string()) };
+            report_closure_error(out_error, &e);
             default
         }
         Err(panic) => {
-            let msg = extract_panic_message(&panic);
-            unsafe { set_error(out_error, &format!("internal panic: {}", msg)) };
+            set_panic_error(out_error, &panic);
             default
         }
     }
 }
 
-/// FFI 边界防护 - 简化版，不处理 Result
+/// FFI 边界防护 - 带导出函数名
 ///
-/// 适用于不会返回错误的场景，只捕获 panic。
+/// 与 `ffi_boundary` 相同，失败时 `name` 记录到最近一次错误（`vimo_ffi_last_error_function`）
+/// 并出现在错误观察者的 `ErrorEvent::function` 中。
+/// 启用 `metrics` feature 时，同时按 `name` 统计调用、错误和 panic 次数（见 `vimo_ffi_stats_json`）。
 ///
-/// # 参数
-/// - `default`: panic 时返回的默认值
-/// - `f`: 要执行的闭包
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn vimo_doc_open(path: *const c_char, out_error: *mut *mut c_char) -> bool {
+///     ffi_boundary_named("vimo_doc_open", out_error, false, || {
+///         let path = unsafe { cstr_to_str(path)? };
+///         Document::open(path)?;
+///         Ok::<_, FfiError>(true)
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_named<T, E, F>(
+    name: &'static str,
+    out_error: *mut *mut c_char,
+    default: T,
+    f: F,
+) -> T
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<T, E>,
+{
+    #[cfg(feature = "metrics")]
+    let f = crate::metrics::counted(name, f);
+    with_function_name(name, || ffi_boundary(out_error, default, f))
+}
+
+/// FFI 边界防护 - 带 tracing 记录的导出函数
+///
+/// 与 `ffi_boundary_named` 相同，另外在 `ffi_call` span 内执行，使一次宿主调用期间
+/// Rust 内部输出的日志都带有同一个 span。span 的字段：
+///
+/// - `function`: 导出函数名 `name`
+/// - `outcome`: `"ok"`、`"error"` 或 `"panic"`
+/// - `elapsed_us`: 调用耗时（微秒）
+///
+/// 失败时再输出一条 `error!` 事件，消息与最近一次错误相同，并带有 `function` 和 `code` 字段。
+/// tracing 的 span 名必须是编译期常量，因此函数名记录在 `function` 字段而不是 span 名中。
 ///
 /// # 示例
 ///
 /// ```rust,ignore
 /// #[no_mangle]
-/// pub extern "C" fn get_count() -> i32 {
-///     ffi_boundary_simple(-1, || {
-///         compute_count()
+/// pub extern "C" fn vimo_doc_save(doc: *mut Doc, out_error: *mut *mut c_char) -> bool {
+///     ffi_boundary_traced("vimo_doc_save", out_error, false, || {
+///         unsafe { doc.as_mut() }.ok_or(FfiError::NullPointer)?.save()?;
+///         Ok::<_, FfiError>(true)
 ///     })
 /// }
 /// ```
-pub fn ffi_boundary_simple<T, F>(default: T, f: F) -> T
+#[cfg(feature = "tracing")]
+pub fn ffi_boundary_traced<T, E, F>(
+    name: &'static str,
+    out_error: *mut *mut c_char,
+    default: T,
+    f: F,
+) -> T
 where
-    F: FnOnce() -> T,
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<T, E>,
 {
-    match catch_unwind(AssertUnwindSafe(f)) {
-        Ok(result) => result,
+    use tracing::field::Empty;
+
+    let span = tracing::info_span!(
+        "ffi_call",
+        function = name,
+        outcome = Empty,
+        elapsed_us = Empty
+    );
+    let _entered = span.enter();
+    let start = std::time::Instant::now();
+    // 闭包没有返回（panic）时保持 "panic"
+    let mut outcome = "panic";
+    let result = ffi_boundary_named(name, out_error, default, || {
+        let result = f();
+        outcome = if result.is_ok() { "ok" } else { "error" };
+        result
+    });
+    span.record("outcome", outcome);
+    span.record("elapsed_us", start.elapsed().as_micros() as u64);
+    if outcome != "ok" {
+        if let Some(err) = crate::last_error() {
+            tracing::error!(function = name, code = err.code, "{}", err.message);
+        }
+    }
+    result
+}
+
+/// FFI 边界防护 - 不可重入的导出函数
+///
+/// 与 `ffi_boundary` 相同，但执行期间通过 `ReentrancyGuard` 占用入口 `name`：
+/// 同一线程在闭包返回前再次调用（通常来自闭包触发的宿主回调）时不执行 `f`，
+/// 写出 `FfiError::ReentrantCall` 并返回 `default`。闭包 panic 时入口同样会释放。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn vimo_sync(out_error: *mut *mut c_char) -> bool {
+///     ffi_boundary_nonreentrant("vimo_sync", out_error, false, || {
+///         engine().sync_and_notify()?;
+///         Ok::<_, FfiError>(true)
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_nonreentrant<T, E, F>(
+    name: &'static str,
+    out_error: *mut *mut c_char,
+    default: T,
+    f: F,
+) -> T
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<T, E>,
+{
+    let _token = match ReentrancyGuard::enter(name) {
+        Ok(token) => token,
+        Err(e) => return ffi_boundary(out_error, default, || Err(e)),
+    };
+    ffi_boundary(out_error, default, f)
+}
+
+/// FFI 边界防护 - 限时执行
+///
+/// 在名为 `vimo-ffi-deadline` 的工作线程上运行闭包，最多等待 `timeout`。
+/// 按时完成时与 `ffi_boundary` 相同；超时时写出 `FfiError::Timeout` 并立即返回 `default`，
+/// 防止第三方代码卡住宿主的 UI 线程。
+///
+/// # 超时后的工作线程
+///
+/// Rust 无法强行终止线程：超时后工作线程与本次调用分离，在后台继续运行到闭包返回，
+/// 它的结果（包括错误）被直接丢弃，之后发生的 panic 只由 panic hook 输出，不会被记录。
+/// 闭包因此必须拥有它用到的全部数据（`'static`），不能借用导出函数的参数；
+/// 超时后仍然持有的锁、文件等资源要到闭包返回才释放。反复超时的调用会累积后台线程，
+/// 调用方应在超时后停止重试，或者让闭包自身可以取消。
+///
+/// 工作线程在超时前 panic 时，panic 负载交回调用线程，按 `ffi_boundary` 的方式捕获和报告
+/// （panic 记录中的线程名为调用线程）。无法创建线程时报告 `FfiError::Io`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn vimo_parse(src: *const c_char, out_error: *mut *mut c_char) -> *mut Doc {
+///     ffi_boundary_deadline(out_error, ptr::null_mut(), Duration::from_secs(2), {
+///         let src = unsafe { cstr_to_string(src) };
+///         move || Ok::<_, FfiError>(Box::into_raw(Box::new(third_party::parse(&src?)?)))
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_deadline<T, E, F>(
+    out_error: *mut *mut c_char,
+    default: T,
+    timeout: std::time::Duration,
+    f: F,
+) -> T
+where
+    T: Send + 'static,
+    E: std::fmt::Display + Send + 'static,
+    F: FnOnce() -> Result<T, E> + Send + 'static,
+{
+    reset_out_error(out_error);
+    let outcome = run_guarded(|| -> Result<Result<T, E>, FfiError> {
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        let worker = std::thread::Builder::new()
+            .name("vimo-ffi-deadline".to_owned())
+            .spawn(move || {
+                // 超时后接收端已丢弃，发送失败即丢弃结果
+                let _ = tx.send(f());
+            })?;
+        match rx.recv_timeout(timeout) {
+            Ok(result) => Ok(result),
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => Err(FfiError::Timeout),
+            // 接收端仍在时断开只可能是闭包 panic，在调用线程上重新抛出，由 run_guarded 捕获
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => match worker.join() {
+                Err(panic) => std::panic::resume_unwind(panic),
+                Ok(()) => unreachable!("deadline worker exited without sending a result"),
+            },
+        }
+    });
+    match outcome {
+        Ok(Ok(Ok(result))) => result,
+        Ok(Ok(Err(e))) => {
+            report_closure_error(out_error, &e);
+            default
+        }
+        Ok(Err(e)) => {
+            report_closure_error(out_error, &e);
+            default
+        }
         Err(panic) => {
-            let msg = extract_panic_message(&panic);
-            eprintln!("[vimo-ffi] panic caught: {}", msg);
+            set_panic_error(out_error, &panic);
             default
         }
     }
 }
 
-/// FFI 边界防护 - 带日志回调
+/// FFI 边界防护 - 要求闭包 `UnwindSafe`
 ///
-/// 允许自定义 panic 日志处理。
+/// 与 `ffi_boundary` 行为完全相同，只是要求 `F: UnwindSafe`，
+/// 捕获 `&mut T`、`&RefCell<T>` 等 panic 后可能处于不一致状态的引用时无法编译。
+/// 确认某个捕获没有问题时，可以只对它包一层 `AssertUnwindSafe`，而不是放弃整个检查。
 ///
-/// # 参数
-/// - `default`: panic 时返回的默认值
-/// - `on_panic`: panic 时的回调，接收 panic 消息
-/// - `f`: 要执行的闭包
-pub fn ffi_boundary_with_log<T, F, L>(default: T, on_panic: L, f: F) -> T
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn cache_get(
+///     cache: *const Cache,
+///     key: *const c_char,
+///     out_error: *mut *mut c_char,
+/// ) -> i64 {
+///     ffi_boundary_safe(out_error, -1, || {
+///         let cache = unsafe { cache.as_ref().ok_or(FfiError::NullPointer)? };
+///         let key = unsafe { cstr_to_str(key)? };
+///         Ok::<_, FfiError>(cache.get(key))
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_safe<T, E, F>(out_error: *mut *mut c_char, default: T, f: F) -> T
 where
-    F: FnOnce() -> T,
-    L: FnOnce(&str),
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<T, E> + UnwindSafe,
+{
+    ffi_boundary(out_error, default, f)
+}
+
+/// FFI 边界防护 - 失败值由返回类型决定
+///
+/// 与 `ffi_boundary` 相同，失败值为 `T::ffi_default()`（见 `FfiDefault`），
+/// 避免在调用点手写失败值时写错（如把 `true` 当成失败值）。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn vimo_doc_save(doc: *mut Document, out_error: *mut *mut c_char) -> bool {
+///     ffi_boundary_default(out_error, || {
+///         let doc = unsafe { doc.as_mut().ok_or(FfiError::NullPointer)? };
+///         doc.save()?;
+///         Ok::<_, FfiError>(true)
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_default<T, E, F>(out_error: *mut *mut c_char, f: F) -> T
+where
+    T: FfiDefault,
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<T, E>,
+{
+    ffi_boundary(out_error, T::ffi_default(), f)
+}
+
+/// FFI 边界防护 - 不捕获 panic
+///
+/// 与 `ffi_boundary` 相同地处理错误，但闭包中的 panic 直接向上传播。
+/// 不受 feature 影响，供测试辅助函数显式使用；**不能**用在真正的 `extern "C"` 导出函数中，
+/// panic 跨越 FFI 边界是未定义行为。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[test]
+/// fn open_rejects_null() {
+///     // 闭包内的断言失败会让测试失败，而不是变成 false
+///     let ok = ffi_boundary_no_catch(ptr::null_mut(), false, || open_impl(ptr::null()));
+///     assert!(!ok);
+/// }
+/// ```
+pub fn ffi_boundary_no_catch<T, E, F>(out_error: *mut *mut c_char, default: T, f: F) -> T
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<T, E>,
 {
-    match catch_unwind(AssertUnwindSafe(f)) {
+    reset_out_error(out_error);
+    clear_last_error();
+    match f() {
         Ok(result) => result,
-        Err(panic) => {
-            let msg = extract_panic_message(&panic);
-            on_panic(&msg);
+        Err(e) => {
+            report_closure_error(out_error, &e);
             default
         }
     }
 }
 
-/// 从 panic 信息中提取可读消息
-fn extract_panic_message(panic: &Box<dyn Any + Send>) -> String {
-    if let Some(s) = panic.downcast_ref::<&str>() {
-        s.to_string()
-    } else if let Some(s) = panic.downcast_ref::<String>() {
-        s.clone()
-    } else {
-        "unknown panic".to_string()
-    }
+/// FFI 边界防护 - 用于 `extern "C-unwind"` 导出函数，panic 继续展开
+///
+/// 不调用 `catch_unwind`，错误照常写入 `out_error`，panic 原样穿过导出函数展开到调用方。
+/// 适用于调用方（C++ 或以 `-fexceptions` 编译的 C）与 Rust 使用兼容的 unwinder，
+/// 希望在自己的栈帧中处理 panic 的场景。
+///
+/// **只能**在 `extern "C-unwind"` 函数中使用：panic 展开穿过普通的 `extern "C"` 函数时，
+/// 运行时会直接终止进程。调用方不能处理展开时（纯 C、不带 unwind 表），使用 `ffi_boundary`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C-unwind" fn vimo_doc_render(doc: *mut Doc, out_error: *mut *mut c_char) -> bool {
+///     ffi_boundary_unwind(out_error, false, || {
+///         let doc = unsafe { doc.as_mut().ok_or(FfiError::NullPointer)? };
+///         doc.render()?;
+///         Ok::<_, FfiError>(true)
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_unwind<T, E, F>(out_error: *mut *mut c_char, default: T, f: F) -> T
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<T, E>,
+{
+    ffi_boundary_no_catch(out_error, default, f)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::ptr;
+/// FFI 边界防护 - 用于 `extern "system"` 导出函数
+///
+/// Windows 上许多 API 使用 `extern "system"`（32 位下为 stdcall）调用约定。
+/// 调用约定属于导出函数本身，而不是这个帮助函数：它接收泛型闭包，无法以 C ABI 暴露，
+/// 只需在 `pub extern "system" fn` 内部调用即可，行为与 `ffi_boundary` 完全相同。
+/// 非 Windows 平台上 `extern "system"` 等同于 `extern "C"`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "system" fn DoSomething(out_error: *mut *mut c_char) -> bool {
+///     ffi_boundary_system(out_error, false, || {
+///         might_fail()?;
+///         Ok(true)
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_system<T, E, F>(out_error: *mut *mut c_char, default: T, f: F) -> T
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<T, E>,
+{
+    ffi_boundary(out_error, default, f)
+}
 
-    #[test]
-    fn test_ffi_boundary_success() {
-        let result: bool = ffi_boundary(ptr::null_mut(), false, || Ok::<_, String>(true));
-        assert!(result);
-    }
+/// FFI 边界防护 - 返回 `i32`，失败时返回 `i32::MIN`
+///
+/// 与 `ffi_boundary` 相同，但不需要 `default` 参数：失败值固定为 `i32::MIN`，
+/// C 侧可以用 `INT32_MIN` 判断是否出错。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn vimo_count(path: *const c_char, out_error: *mut *mut c_char) -> i32 {
+///     ffi_boundary_i32(out_error, || {
+///         let path = unsafe { cstr_to_str(path)? };
+///         Ok::<_, FfiError>(db::count(path)?)
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_i32<E, F>(out_error: *mut *mut c_char, f: F) -> i32
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<i32, E>,
+{
+    ffi_boundary(out_error, i32::MIN, f)
+}
 
-    #[test]
-    fn test_ffi_boundary_error() {
-        let mut error_ptr: *mut c_char = ptr::null_mut();
-        let result: bool = ffi_boundary(&mut error_ptr, false, || {
-            Err::<bool, _>("something failed")
-        });
-        assert!(!result);
-        assert!(!error_ptr.is_null());
-        // 清理
+/// FFI 边界防护 - 返回 `i64`，失败时返回 `i64::MIN`
+pub fn ffi_boundary_i64<E, F>(out_error: *mut *mut c_char, f: F) -> i64
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<i64, E>,
+{
+    ffi_boundary(out_error, i64::MIN, f)
+}
+
+/// FFI 边界防护 - 返回 `usize`，失败时返回 `0`
+///
+/// 适用于长度、计数等 0 不是合法成功值、或者 0 与失败可以同等对待的场景；
+/// 需要区分时检查 `out_error`。
+pub fn ffi_boundary_usize<E, F>(out_error: *mut *mut c_char, f: F) -> usize
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<usize, E>,
+{
+    ffi_boundary(out_error, 0, f)
+}
+
+/// FFI 边界防护 - 返回指针，失败时返回 null
+///
+/// 闭包只是构造一个新对象时，用 `ffi_boundary_box` 省去 `Box::into_raw`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn vimo_db_open(path: *const c_char, out_error: *mut *mut c_char) -> *mut Db {
+///     ffi_boundary_ptr(out_error, || {
+///         let path = unsafe { cstr_to_str(path)? };
+///         Ok::<_, FfiError>(Box::into_raw(Box::new(Db::open(path)?)))
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_ptr<T, E, F>(out_error: *mut *mut c_char, f: F) -> *mut T
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<*mut T, E>,
+{
+    ffi_boundary(out_error, std::ptr::null_mut(), f)
+}
+
+/// FFI 边界防护 - 装箱成功值并返回裸指针，失败时返回 null
+///
+/// 闭包保持在安全代码中，装箱在边界内完成。返回的指针由调用方通过对应的释放函数
+/// （内部用 `Box::from_raw`）交还。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn vimo_db_open(path: *const c_char, out_error: *mut *mut c_char) -> *mut Db {
+///     ffi_boundary_box(out_error, || {
+///         let path = unsafe { cstr_to_str(path)? };
+///         Ok::<_, FfiError>(Db::open(path)?)
+///     })
+/// }
+///
+/// #[no_mangle]
+/// pub extern "C" fn vimo_db_close(db: *mut Db) {
+///     if !db.is_null() {
+///         drop(unsafe { Box::from_raw(db) });
+///     }
+/// }
+/// ```
+pub fn ffi_boundary_box<T, E, F>(out_error: *mut *mut c_char, f: F) -> *mut T
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<T, E>,
+{
+    ffi_boundary_ptr(out_error, || f().map(|value| Box::into_raw(Box::new(value))))
+}
+
+/// FFI 边界防护 - 成功时把两个值写入输出参数，返回是否成功
+///
+/// 闭包返回 `(A, B)`，成功时分别写入 `out_a`、`out_b`；失败或 panic 时输出参数保持不变。
+/// 某个输出参数为 null 表示调用方不需要该值，直接跳过。写入不读取也不 drop 旧值，
+/// 输出参数可以指向未初始化的内存。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn vimo_doc_size(
+///     doc: *const Doc,
+///     out_width: *mut u32,
+///     out_height: *mut u32,
+///     out_error: *mut *mut c_char,
+/// ) -> bool {
+///     ffi_boundary_outs2(out_width, out_height, out_error, || {
+///         let doc = unsafe { doc.as_ref().ok_or(FfiError::NullPointer)? };
+///         Ok::<_, FfiError>((doc.width(), doc.height()))
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_outs2<A, B, E, F>(
+    out_a: *mut A,
+    out_b: *mut B,
+    out_error: *mut *mut c_char,
+    f: F,
+) -> bool
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<(A, B), E>,
+{
+    ffi_boundary(out_error, false, || {
+        let (a, b) = f()?;
         unsafe {
-            if !error_ptr.is_null() {
-                let _ = std::ffi::CString::from_raw(error_ptr);
-            }
+            write_out(out_a, a);
+            write_out(out_b, b);
+        }
+        Ok::<_, E>(true)
+    })
+}
+
+/// FFI 边界防护 - 成功时把三个值写入输出参数，返回是否成功
+///
+/// 同 `ffi_boundary_outs2`。更多的值可以让闭包返回 `#[repr(C)]` 结构体，
+/// 用 `ffi_boundary_outs2` 或 `ffi_boundary` 写出。
+pub fn ffi_boundary_outs3<A, B, C, E, F>(
+    out_a: *mut A,
+    out_b: *mut B,
+    out_c: *mut C,
+    out_error: *mut *mut c_char,
+    f: F,
+) -> bool
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<(A, B, C), E>,
+{
+    ffi_boundary(out_error, false, || {
+        let (a, b, c) = f()?;
+        unsafe {
+            write_out(out_a, a);
+            write_out(out_b, b);
+            write_out(out_c, c);
         }
+        Ok::<_, E>(true)
+    })
+}
+
+/// 写入输出参数，null 时丢弃该值
+unsafe fn write_out<T>(out: *mut T, value: T) {
+    if !out.is_null() {
+        out.write(value);
     }
+}
 
-    #[test]
+/// FFI 边界防护 - 错误信息包含完整的错误链
+///
+/// 与 `ffi_boundary` 相同，但错误按 `set_error_chain` 的方式渲染，
+/// 底层原因（如 "permission denied (os error 13)"）不会被顶层消息吞掉。
+pub fn ffi_boundary_chained<T, E, F>(out_error: *mut *mut c_char, default: T, f: F) -> T
+where
+    E: std::error::Error + 'static,
+    F: FnOnce() -> Result<T, E>,
+{
+    reset_out_error(out_error);
+    match run_guarded(f) {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => {
+            let msg = format_error_chain(&e, ERROR_CHAIN_SEPARATOR, ERROR_CHAIN_MAX_DEPTH);
+            report_closure_error_with(out_error, &e, &msg);
+            default
+        }
+        Err(panic) => {
+            set_panic_error(out_error, &panic);
+            default
+        }
+    }
+}
+
+/// FFI 边界防护 - 返回整数状态码
+///
+/// 适用于“成功返回 0，失败返回错误码”约定的导出函数。错误信息仍写入 `out_error`。
+///
+/// # 返回值
+/// - `0`: 成功
+/// - `FfiError::code()`: 闭包返回 `FfiError`
+/// - `-1`（`FfiErrorCode::Unknown`）: 闭包返回其它错误类型
+/// - `-1000`（`FfiErrorCode::Panic`）: 捕获到 panic
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn open_db(path: *const c_char, out_error: *mut *mut c_char) -> i32 {
+///     ffi_boundary_code(out_error, || {
+///         let path = unsafe { cstr_to_str(path)? };
+///         db::open(path)?;
+///         Ok::<_, FfiError>(())
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_code<E, F>(out_error: *mut *mut c_char, f: F) -> i32
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<(), E>,
+{
+    reset_out_error(out_error);
+    match run_guarded(f) {
+        Ok(Ok(())) => FfiErrorCode::Ok as i32,
+        Ok(Err(e)) => report_closure_error(out_error, &e),
+        Err(panic) => {
+            set_panic_error(out_error, &panic);
+            FfiErrorCode::Panic as i32
+        }
+    }
+}
+
+/// FFI 边界防护 - 按系统调用的约定返回 `-errno`
+///
+/// 成功返回 `0`，失败返回负的 errno（映射见 `FfiError::to_errno`），错误信息仍写入 `out_error`。
+/// 非 `FfiError` 的错误返回 `-EIO`，panic 返回 `-ENOTRECOVERABLE`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn vimo_open(path: *const c_char) -> i32 {
+///     ffi_boundary_errno(std::ptr::null_mut(), || {
+///         let path = unsafe { cstr_to_str(path)? };
+///         db::open(path)?;
+///         Ok::<_, FfiError>(())
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_errno<E, F>(out_error: *mut *mut c_char, f: F) -> i32
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<(), E>,
+{
+    reset_out_error(out_error);
+    match run_guarded(f) {
+        Ok(Ok(())) => 0,
+        Ok(Err(e)) => {
+            report_closure_error(out_error, &e);
+            let errno = match (&e as &dyn Any).downcast_ref::<FfiError>() {
+                Some(e) => e.to_errno(),
+                None => crate::errno::EIO,
+            };
+            -errno
+        }
+        Err(panic) => {
+            set_panic_error(out_error, &panic);
+            -crate::errno::ENOTRECOVERABLE
+        }
+    }
+}
+
+/// FFI 边界防护 - 简化版，不处理 Result
+///
+/// 适用于不会返回错误的场景，只捕获 panic。panic 消息输出到 stderr，
+/// 启用 `tracing` feature 时改为输出 `error!` 事件。
+///
+/// # 参数
+/// - `default`: panic 时返回的默认值
+/// - `f`: 要执行的闭包
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn get_count() -> i32 {
+///     ffi_boundary_simple(-1, || {
+///         compute_count()
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_simple<T, F>(default: T, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    match run_guarded(f) {
+        Ok(result) => result,
+        Err(panic) => {
+            let msg = extract_panic_message(&panic);
+            #[cfg(feature = "tracing")]
+            tracing::error!("panic caught: {}", msg);
+            #[cfg(not(feature = "tracing"))]
+            eprintln!("[vimo-ffi] panic caught: {}", msg);
+            default
+        }
+    }
+}
+
+/// FFI 边界防护 - 带日志回调
+///
+/// 允许自定义 panic 日志处理。
+///
+/// # 参数
+/// - `default`: panic 时返回的默认值
+/// - `on_panic`: panic 时的回调，接收 panic 消息
+/// - `f`: 要执行的闭包
+///
+/// 需要位置和线程名时使用 `ffi_boundary_with_log_event`。
+pub fn ffi_boundary_with_log<T, F, L>(default: T, on_panic: L, f: F) -> T
+where
+    F: FnOnce() -> T,
+    L: FnOnce(&str),
+{
+    ffi_boundary_with_log_event(default, |event| on_panic(event.message), f)
+}
+
+/// FFI 边界防护 - 带日志回调，回调接收结构化的 `PanicEvent`
+///
+/// 与 `ffi_boundary_with_log` 相同，回调另外得到 panic 位置（需要先调用
+/// `install_ffi_panic_hook`，否则为 None）和所在线程名，可以直接交给应用的结构化日志。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn vimo_render() -> bool {
+///     ffi_boundary_with_log_event(false, |event| {
+///         log::error!(
+///             target: "ffi",
+///             "panic on {} at {}: {}",
+///             event.thread,
+///             event.location.unwrap_or("?"),
+///             event.message
+///         );
+///     }, || render())
+/// }
+/// ```
+pub fn ffi_boundary_with_log_event<T, F, L>(default: T, on_panic: L, f: F) -> T
+where
+    F: FnOnce() -> T,
+    L: FnOnce(&PanicEvent),
+{
+    match run_guarded(f) {
+        Ok(result) => result,
+        Err(panic) => {
+            let msg = extract_panic_message(&panic);
+            let location = matching_panic_location(&msg)
+                .map(|location| location.to_string());
+            let thread = std::thread::current();
+            on_panic(&PanicEvent {
+                message: &msg,
+                location: location.as_deref(),
+                thread: thread.name().unwrap_or("<unnamed>"),
+            });
+            default
+        }
+    }
+}
+
+/// 同 `ffi_boundary_simple`，panic 时返回 `T::ffi_default()`
+pub fn ffi_boundary_simple_default<T, F>(f: F) -> T
+where
+    T: FfiDefault,
+    F: FnOnce() -> T,
+{
+    ffi_boundary_simple(T::ffi_default(), f)
+}
+
+/// 同 `ffi_boundary_with_log`，panic 时返回 `T::ffi_default()`
+pub fn ffi_boundary_with_log_default<T, F, L>(on_panic: L, f: F) -> T
+where
+    T: FfiDefault,
+    F: FnOnce() -> T,
+    L: FnOnce(&str),
+{
+    ffi_boundary_with_log(T::ffi_default(), on_panic, f)
+}
+
+/// FFI 边界防护 - 错误写入调用者提供的定长缓冲区
+///
+/// 与 `ffi_boundary` 相同，但错误和 panic 信息通过 `set_error_buf` 写入 `err_buf`，
+/// 不做堆分配，适用于无法回调本库释放字符串的宿主。
+/// `err_buf` 为 null 或 `err_cap` 为 0 时不写入消息。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn do_something(err_buf: *mut c_char, err_cap: usize) -> bool {
+///     ffi_boundary_buf(err_buf, err_cap, false, || {
+///         might_fail()?;
+///         Ok(true)
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_buf<T, E, F>(err_buf: *mut c_char, err_cap: usize, default: T, f: F) -> T
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<T, E>,
+{
+    match run_guarded(f) {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => {
+            let msg = message_of(&e);
+            let msg = render_report(record_closure_error(&e, &msg), &msg);
+            unsafe { set_error_buf(err_buf, err_cap, &msg) };
+            default
+        }
+        Err(panic) => {
+            let msg = panic_message(&panic);
+            let msg = render_report(FfiErrorCode::Panic as i32, &msg);
+            unsafe { set_error_buf(err_buf, err_cap, &msg) };
+            default
+        }
+    }
+}
+
+/// FFI 边界防护 - `anyhow::Result` 闭包
+///
+/// 与 `ffi_boundary` 相同，但错误以 `{:#}` 渲染，保留所有 `.context()` 层级；
+/// 同时启用 `backtrace` feature 时附带 anyhow 在错误源头捕获的调用栈。
+/// 错误链根部是 `FfiError` 时沿用其错误码。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn load(out_error: *mut *mut c_char) -> bool {
+///     ffi_boundary_anyhow(out_error, false, || {
+///         config::load().context("failed to load config")?;
+///         Ok(true)
+///     })
+/// }
+/// ```
+#[cfg(feature = "anyhow")]
+pub fn ffi_boundary_anyhow<T, F>(out_error: *mut *mut c_char, default: T, f: F) -> T
+where
+    F: FnOnce() -> anyhow::Result<T>,
+{
+    reset_out_error(out_error);
+    match run_guarded(f) {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => {
+            let (msg, has_backtrace) = crate::anyhow_support::format_anyhow(&e);
+            let code = record_closure_error(&e, &msg);
+            let msg = render_report_with(code, &msg, !has_backtrace);
+            unsafe { write_error(out_error, &msg) };
+            default
+        }
+        Err(panic) => {
+            set_panic_error(out_error, &panic);
+            default
+        }
+    }
+}
+
+/// FFI 边界防护 - `miette::Result` 版本
+///
+/// 与 `ffi_boundary` 相同，但错误渲染为 miette 的图形化诊断（源码片段、标注、help），
+/// 不含终端颜色。启用 `serde` feature 且错误格式为 `ErrorFormat::Json` 时，
+/// 改为输出 miette 的 JSON 诊断（不经过翻译和长度截断，保证 JSON 完整）。
+/// 错误码和严重级别在诊断本身是 `FfiError` 时取自它，否则为 `FfiErrorCode::Unknown`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn vimo_config_check(src: *const c_char, out_error: *mut *mut c_char) -> bool {
+///     ffi_boundary_miette(out_error, false, || {
+///         let src = unsafe { cstr_to_str(src)? };
+///         config::parse(src)?;
+///         Ok(true)
+///     })
+/// }
+/// ```
+#[cfg(feature = "miette")]
+pub fn ffi_boundary_miette<T, F>(out_error: *mut *mut c_char, default: T, f: F) -> T
+where
+    F: FnOnce() -> miette::Result<T>,
+{
+    reset_out_error(out_error);
+    use crate::miette_support::{miette_code, miette_severity, render_graphical};
+
+    match run_guarded(f) {
+        Ok(Ok(result)) => result,
+        Ok(Err(report)) => {
+            let code = miette_code(&report);
+            let msg = render_graphical(&report);
+            on_error(code, &msg, miette_severity(&report));
+            #[cfg(feature = "serde")]
+            if crate::error_format() == crate::ErrorFormat::Json {
+                let json = crate::miette_support::render_json(&report);
+                unsafe { write_error(out_error, &json) };
+                return default;
+            }
+            report_error(out_error, code, &msg);
+            default
+        }
+        Err(panic) => {
+            set_panic_error(out_error, &panic);
+            default
+        }
+    }
+}
+
+/// FFI 边界防护 - 在当前线程上运行 future
+///
+/// 与 `ffi_boundary` 相同，但执行的是 future：在当前线程的 tokio 运行时上 `block_on`，
+/// 运行时在线程第一次调用时创建，之后复用。创建运行时和轮询 future 时的 panic 都会被捕获，
+/// 创建失败时报告 `FfiError::Io`。
+///
+/// 已经处于 tokio 运行时内（例如在 async 代码中调用导出函数）时不会阻塞，
+/// 而是报告消息为 `NESTED_RUNTIME_MESSAGE` 的 `FfiError::Custom` 错误。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn vimo_sync_now(out_error: *mut *mut c_char) -> bool {
+///     ffi_boundary_async(out_error, false, async {
+///         client().sync().await?;
+///         Ok::<_, FfiError>(true)
+///     })
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+pub fn ffi_boundary_async<T, E, Fut>(out_error: *mut *mut c_char, default: T, fut: Fut) -> T
+where
+    E: std::fmt::Display + 'static,
+    Fut: std::future::Future<Output = Result<T, E>>,
+{
+    reset_out_error(out_error);
+    match run_guarded(|| crate::tokio_support::block_on(fut)) {
+        Ok(Ok(Ok(result))) => result,
+        Ok(Ok(Err(e))) => {
+            report_closure_error(out_error, &e);
+            default
+        }
+        Ok(Err(e)) => {
+            report_closure_error(out_error, &e);
+            default
+        }
+        Err(panic) => {
+            set_panic_error(out_error, &panic);
+            default
+        }
+    }
+}
+
+/// `ffi_boundary_oom_safe` 预留的错误缓冲区大小（含结尾 NUL）
+pub const OOM_RESERVE_SIZE: usize = 256;
+
+/// FFI 边界防护 - 内存耗尽时仍能写出错误
+///
+/// 与 `ffi_boundary` 相同，但进入时预先分配 `OOM_RESERVE_SIZE` 字节的错误缓冲区：
+/// 报告错误时如果无法为消息分配内存，就把消息（截断到缓冲区大小，不足部分以空格补齐）
+/// 写入预留缓冲区交给调用者，仍然用 `vimo_ffi_free_string` 释放。
+/// 成功时预留缓冲区直接释放，因此每次调用多一次小分配。
+///
+/// 注意 Rust 默认在分配失败时终止进程，这里只能保证报告错误这一步是可失败的；
+/// 适用于安装了可恢复分配器的嵌入式 / wasm 目标。
+pub fn ffi_boundary_oom_safe<T, E, F>(out_error: *mut *mut c_char, default: T, f: F) -> T
+where
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<T, E>,
+{
+    reset_out_error(out_error);
+    let reserve = vec![b' '; OOM_RESERVE_SIZE].into_boxed_slice();
+    match run_guarded(f) {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => {
+            let msg = message_of(&e);
+            let msg = render_report(record_closure_error(&e, &msg), &msg);
+            unsafe { write_error_reserved(out_error, &msg, reserve, try_sanitized_cstring) };
+            default
+        }
+        Err(panic) => {
+            let msg = panic_message(&panic);
+            let msg = render_report(FfiErrorCode::Panic as i32, &msg);
+            unsafe { write_error_reserved(out_error, &msg, reserve, try_sanitized_cstring) };
+            default
+        }
+    }
+}
+
+/// 写出错误消息，`alloc` 失败时改用预留缓冲区
+///
+/// 预留缓冲区按原长度整体交出：消息之后用空格补齐、最后一字节为 NUL，
+/// 这样 `CString::from_raw` 按 strlen 推算出的长度与分配时一致，可以正常释放。
+unsafe fn write_error_reserved(
+    out_error: *mut *mut c_char,
+    msg: &str,
+    mut reserve: Box<[u8]>,
+    alloc: impl FnOnce(&str) -> Option<std::ffi::CString>,
+) {
+    if out_error.is_null() {
+        return;
+    }
+    if let Some(c_string) = alloc(msg) {
+        *out_error = c_string.into_raw();
+        return;
+    }
+    let last = reserve.len() - 1;
+    let truncated = crate::truncate_at_char_boundary(msg, last);
+    for (dst, &src) in reserve.iter_mut().zip(truncated.as_bytes()) {
+        *dst = if src == 0 { b' ' } else { src };
+    }
+    reserve[last] = 0;
+    *out_error = Box::into_raw(reserve) as *mut c_char;
+}
+
+/// FFI 边界防护 - 输出结构化错误
+///
+/// 与 `ffi_boundary` 相同，但错误以 `VimoError` 的形式写出，C 侧可以直接读取错误码。
+/// panic 时错误码为 `FfiErrorCode::Panic`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn load(path: *const c_char, out_error: *mut *mut VimoError) -> bool {
+///     ffi_boundary_structured(out_error, false, || {
+///         let path = unsafe { cstr_to_str(path)? };
+///         config::load(path)?;
+///         Ok::<_, FfiError>(true)
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_structured<T, E, F>(out_error: *mut *mut VimoError, default: T, f: F) -> T
+where
+    E: Into<FfiError>,
+    F: FnOnce() -> Result<T, E>,
+{
+    reset_out_error(out_error);
+    match run_guarded(f) {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => {
+            let err: FfiError = e.into();
+            record_closure_error(&err, &message_of(&err));
+            unsafe { crate::set_error_struct(out_error, &err, None) };
+            default
+        }
+        Err(panic) => {
+            let msg = panic_message(&panic);
+            let code = FfiErrorCode::Panic as i32;
+            unsafe { write_error_struct(out_error, code, Severity::Fatal, &msg, None) };
+            default
+        }
+    }
+}
+
+/// FFI 边界防护 - 一次报告多个错误
+///
+/// 闭包返回 `ErrorList` 时，每个错误作为一项通过 `set_errors` 写出；
+/// panic 时写出只含 panic 消息的单项数组。线程局部的最近一次错误记录以 `"; "`
+/// 连接的完整消息，错误码取第一个错误的错误码。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn validate_form(
+///     form: *const Form,
+///     out_errors: *mut *mut *mut c_char,
+///     out_count: *mut usize,
+/// ) -> bool {
+///     ffi_boundary_multi(out_errors, out_count, false, || {
+///         let mut errors = ErrorList::new();
+///         // ... 对每个字段 errors.push(...)
+///         errors.into_result()?;
+///         Ok(true)
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_multi<T, F>(
+    out_errors: *mut *mut *mut c_char,
+    out_count: *mut usize,
+    default: T,
+    f: F,
+) -> T
+where
+    F: FnOnce() -> Result<T, ErrorList>,
+{
+    reset_out_error(out_errors);
+    if !out_count.is_null() {
+        unsafe { *out_count = 0 };
+    }
+    match run_guarded(f) {
+        Ok(Ok(result)) => result,
+        Ok(Err(errors)) => {
+            on_error(errors.code(), &errors.to_string(), errors.severity());
+            unsafe { set_errors(out_errors, out_count, &errors) };
+            default
+        }
+        Err(panic) => {
+            let msg = panic_message(&panic);
+            let msg = cap_message(translate(FfiErrorCode::Panic as i32, &msg)).into_owned();
+            unsafe { write_string_array(out_errors, out_count, std::iter::once(msg)) };
+            default
+        }
+    }
+}
+
+/// 在 panic 展开期间调用 `ffi_boundary` 时写出的错误消息
+const UNWINDING_MESSAGE: &str = "called during unwinding";
+
+/// FFI 边界防护 - 返回 `{ code, value }` 结果结构体
+///
+/// 不需要 out 参数：成功时 `code` 为 `FfiErrorCode::Ok`，错误时为错误的错误码，
+/// panic 时为 `FfiErrorCode::Panic`，失败时 `value` 为默认值。
+/// 错误消息通过 `vimo_ffi_last_error_message` 获取。结果类型见 `ffi_result_type!`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn file_size(path: *const c_char) -> VimoResultI64 {
+///     ffi_boundary_result(|| {
+///         let path = unsafe { cstr_to_str(path)? };
+///         Ok::<_, FfiError>(std::fs::metadata(path)?.len() as i64)
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_result<R, E, F>(f: F) -> R
+where
+    R: FfiResultType,
+    E: std::fmt::Display + 'static,
+    F: FnOnce() -> Result<R::Value, E>,
+{
+    match run_guarded(f) {
+        Ok(Ok(value)) => R::ok(value),
+        Ok(Err(e)) => {
+            R::from_code(record_closure_error(&e, &message_of(&e)))
+        }
+        Err(_) => R::from_code(FfiErrorCode::Panic as i32),
+    }
+}
+
+/// FFI 边界防护 - 通过 `&mut FfiContext` 报告错误
+///
+/// 不需要 out 参数：闭包用 `FfiContext::set_error` 报告错误，返回值原样返回；
+/// 边界返回前把报告的错误写入最近一次错误（`vimo_ffi_last_error_message`），
+/// 并交给 `FfiContext::log_fn`。panic 时返回 `T::default()`，错误码为 `FfiErrorCode::Panic`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// pub extern "C" fn vimo_doc_count(path: *const c_char) -> i64 {
+///     ffi_boundary_ctx_obj(|ctx| match unsafe { cstr_to_str(path) } {
+///         Ok(path) => Document::count(path),
+///         Err(e) => {
+///             ctx.set_error(e);
+///             -1
+///         }
+///     })
+/// }
+/// ```
+pub fn ffi_boundary_ctx_obj<T, F>(f: F) -> T
+where
+    T: Default,
+    F: FnOnce(&mut FfiContext) -> T,
+{
+    let mut ctx = FfiContext::new();
+    match run_guarded(|| f(&mut ctx)) {
+        Ok(result) => {
+            if let Some((code, msg, severity)) = ctx.take_error() {
+                on_error(code, &msg, severity);
+                ctx.log(&msg);
+            }
+            result
+        }
+        Err(_) => T::default(),
+    }
+}
+
+/// 所有边界函数共用的执行入口：捕获 panic，更新调用计数和最近一次错误
+pub(crate) fn run_guarded<R>(f: impl FnOnce() -> R) -> std::thread::Result<R> {
+    #[cfg(feature = "metrics")]
+    crate::GLOBAL_METRICS.total_calls.fetch_add(1, Ordering::Relaxed);
+    clear_last_error();
+    clear_panic_location();
+    #[cfg(all(feature = "pprof", unix))]
+    let sample = crate::pprof_support::start_sample();
+
+    #[cfg(all(feature = "catch-unwind", not(panic = "abort")))]
+    let result = catch_unwind(AssertUnwindSafe(f));
+    // 不捕获时 panic 直接展开（panic = "abort" 下直接终止进程），边界只负责 Result 的转换
+    #[cfg(any(not(feature = "catch-unwind"), panic = "abort"))]
+    let result: std::thread::Result<R> = Ok(f());
+    // 只采样闭包本身，不包括下面的错误记录
+    #[cfg(all(feature = "pprof", unix))]
+    drop(sample);
+
+    if let Err(panic) = &result {
+        #[cfg(feature = "metrics")]
+        crate::GLOBAL_METRICS.panics.fetch_add(1, Ordering::Relaxed);
+        let code = FfiErrorCode::Panic as i32;
+        let msg = describe_panic(panic);
+        #[cfg(feature = "log")]
+        crate::log_support::log_failure(&msg);
+        let message = extract_panic_message(panic);
+        let location = matching_panic_location(&message)
+            .map(|location| location.to_string());
+        notify_panic_handler(&message, location.as_deref());
+        notify_error(code, &msg, true);
+        record_last_error(code, &msg, Severity::Fatal, true);
+        record_panic_info(&message, location.unwrap_or_default());
+
+        match panic_policy() {
+            PanicPolicy::Catch => {}
+            PanicPolicy::Abort => {
+                eprintln!("[vimo-ffi] {}, aborting", msg);
+                std::process::abort();
+            }
+            PanicPolicy::Rethrow => {
+                if let Err(panic) = result {
+                    std::panic::resume_unwind(panic);
+                }
+            }
+        }
+        // 测试构建中所有边界函数都在记录后重新抛出，让测试断言能正常失败
+        #[cfg(feature = "test-mode")]
+        if let Err(panic) = result {
+            std::panic::resume_unwind(panic);
+        }
+    }
+    result
+}
+
+/// 闭包返回错误时调用
+fn on_error(code: i32, message: &str, severity: Severity) {
+    #[cfg(feature = "metrics")]
+    crate::GLOBAL_METRICS.errors.fetch_add(1, Ordering::Relaxed);
+    #[cfg(feature = "log")]
+    crate::log_support::log_failure(message);
+    notify_error(code, message, false);
+    record_last_error(code, message, severity, false);
+}
+
+/// 进入边界时把 `*out_error` 置为 null，成功返回后调用方读到的也是确定的值
+///
+/// 这里不释放旧值，也不提示覆盖：调用方未初始化的变量正是要解决的情况。
+fn reset_out_error<P>(out_error: *mut *mut P) {
+    if !out_error.is_null() {
+        unsafe { *out_error = std::ptr::null_mut() };
+    }
+}
+
+/// 边界函数写出错误的统一入口
+fn report_error(out_error: *mut *mut c_char, code: i32, message: &str) {
+    unsafe { write_error(out_error, &render_report(code, message)) };
+}
+
+/// 闭包返回错误时通知观察者并记录最近一次错误，返回错误码
+///
+/// `message` 是记录和写出的消息，通常为 `message_of(e)`。
+fn record_closure_error<E: 'static>(e: &E, message: &str) -> i32 {
+    let code = code_of(e);
+    on_error(code, message, severity_of(e));
+    code
+}
+
+/// 闭包返回错误时的统一处理：通知、记录并写出错误，返回错误码
+fn report_closure_error<E: std::fmt::Display + 'static>(out_error: *mut *mut c_char, e: &E) -> i32 {
+    report_closure_error_with(out_error, e, &message_of(e))
+}
+
+/// 同 `report_closure_error`，但以 `message` 代替错误本身的消息
+fn report_closure_error_with<E: 'static>(out_error: *mut *mut c_char, e: &E, message: &str) -> i32 {
+    let code = record_closure_error(e, message);
+    report_error(out_error, code, message);
+    code
+}
+
+/// 按全局配置渲染边界错误：翻译消息，可选附带调用栈，可选 JSON 格式
+fn render_report(code: i32, message: &str) -> Cow<'_, str> {
+    render_report_with(code, message, true)
+}
+
+/// 同 `render_report`，`capture_backtrace` 为 false 时不再附带当前调用栈
+/// （消息已经自带调用栈的情况）
+fn render_report_with(code: i32, message: &str, capture_backtrace: bool) -> Cow<'_, str> {
+    let message = cap_message(translate(code, message));
+    #[cfg(feature = "backtrace")]
+    let message = match capture_backtrace
+        .then(|| crate::backtrace::append_backtrace(&message))
+        .flatten()
+    {
+        Some(with_backtrace) => Cow::Owned(with_backtrace),
+        None => message,
+    };
+    #[cfg(not(feature = "backtrace"))]
+    let _ = capture_backtrace;
+
+    #[cfg(feature = "serde")]
+    if crate::error_format() == crate::ErrorFormat::Json {
+        return Cow::Owned(crate::json::render_json(code, &message, None));
+    }
+    #[cfg(not(feature = "serde"))]
+    let _ = code;
+    message
+}
+
+/// 将 panic 转换为错误信息写入 `out_error`
+fn set_panic_error(out_error: *mut *mut c_char, panic: &Box<dyn Any + Send>) {
+    report_error(out_error, FfiErrorCode::Panic as i32, &panic_message(panic));
+}
+
+/// 边界写出的 panic 消息，`Verbose` 时附带 `panic_detail`
+fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+    let message = describe_panic(panic);
+    with_detail(&message, || Some(panic_detail(panic))).into_owned()
+}
+
+/// `Verbose` 时附加在 panic 消息后的细节：线程名和负载类型
+fn panic_detail(panic: &Box<dyn Any + Send>) -> String {
+    let thread = std::thread::current();
+    let payload = if panic.is::<&str>() {
+        "&str"
+    } else if panic.is::<String>() {
+        "String"
+    } else {
+        "non-string payload"
+    };
+    format!("thread '{}', payload {}", thread.name().unwrap_or("<unnamed>"), payload)
+}
+
+/// 边界处写出的 panic 错误消息
+pub(crate) fn describe_panic(panic: &Box<dyn Any + Send>) -> String {
+    let message = extract_panic_message(panic);
+    match matching_panic_location(&message) {
+        Some(location) => format!("internal panic at {location}: {message}"),
+        None => format!("internal panic: {}", message),
+    }
+}
+
+/// panic hook 记录的位置，只使用与载荷一致的记录，避免沿用同一闭包内更早被捕获的 panic
+fn matching_panic_location(message: &str) -> Option<PanicLocation> {
+    panic_location().filter(|location| location.message.as_deref().is_none_or(|m| m == message))
+}
+
+/// 从 panic 信息中提取可读消息
+///
+/// 内置类型和 `register_panic_extractor` 注册的提取函数都不匹配时为 `"unknown panic"`。
+fn extract_panic_message(panic: &Box<dyn Any + Send>) -> String {
+    payload_message(panic.as_ref()).unwrap_or_else(|| "unknown panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vimo_ffi_free_error;
+    use std::panic::AssertUnwindSafe;
+    use std::ptr;
+
+    #[test]
+    fn test_ffi_boundary_success() {
+        let result: bool = ffi_boundary(ptr::null_mut(), false, || Ok::<_, String>(true));
+        assert!(result);
+    }
+
+    #[test]
+    fn test_ffi_boundary_static_message_allocations() {
+        fn count<E: std::fmt::Display + 'static>(err: E) -> usize {
+            let mut error_ptr: *mut c_char = ptr::null_mut();
+            let before = crate::test_alloc::allocation_count();
+            ffi_boundary(&mut error_ptr, (), || Err::<(), _>(err));
+            let allocations = crate::test_alloc::allocation_count() - before;
+            let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+            assert_eq!(msg.to_str().unwrap(), "null pointer");
+            allocations
+        }
+        // 同样的消息，FfiError 借用静态消息，省去一次格式化出的 String
+        let formatted = count(String::from("null pointer"));
+        let static_message = count(FfiError::NullPointer);
+        assert_eq!(static_message + 1, formatted);
+    }
+
+    #[test]
+    fn test_ffi_boundary_safe() {
+        let values = [1, 2, 3];
+        let sum = ffi_boundary_safe(ptr::null_mut(), -1, || Ok::<_, FfiError>(values.iter().sum()));
+        assert_eq!(sum, 6);
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let result = ffi_boundary_safe(&mut error_ptr, -1, || Err::<i32, _>(FfiError::NullPointer));
+        assert_eq!(result, -1);
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "null pointer");
+
+        // 确认没有问题的 `&mut` 捕获可以单独断言
+        let mut count = 0;
+        let mut counter = AssertUnwindSafe(&mut count);
+        ffi_boundary_safe(ptr::null_mut(), (), move || {
+            **counter += 1;
+            Ok::<_, FfiError>(())
+        });
+        assert_eq!(count, 1);
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_ffi_boundary_safe_panic() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let result = ffi_boundary_safe(&mut error_ptr, false, || -> Result<bool, FfiError> {
+            panic!("boom")
+        });
+        assert!(!result);
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "internal panic: boom");
+    }
+
+    #[test]
+    fn test_ffi_boundary_error() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let result: bool = ffi_boundary(&mut error_ptr, false, || {
+            Err::<bool, _>("something failed")
+        });
+        assert!(!result);
+        assert!(!error_ptr.is_null());
+        // 清理
+        unsafe {
+            if !error_ptr.is_null() {
+                let _ = std::ffi::CString::from_raw(error_ptr);
+            }
+        }
+    }
+
+    /// `Display` 实现会 panic 的错误类型
+    #[derive(Debug)]
+    struct BadDisplay;
+
+    impl std::fmt::Display for BadDisplay {
+        fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            panic!("Display for BadDisplay")
+        }
+    }
+
+    #[test]
+    fn test_ffi_boundary_display_panic() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let result = ffi_boundary(&mut error_ptr, -1, || Err::<i32, _>(BadDisplay));
+        assert_eq!(result, -1);
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) }.into_string().unwrap();
+        assert_eq!(msg, crate::FORMAT_PANIC_MESSAGE);
+        assert_eq!(crate::last_error().unwrap().message, crate::FORMAT_PANIC_MESSAGE);
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        unsafe { crate::set_error_from(&mut error_ptr, &BadDisplay) };
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) }.into_string().unwrap();
+        assert_eq!(msg, crate::FORMAT_PANIC_MESSAGE);
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
     fn test_ffi_boundary_panic() {
         let mut error_ptr: *mut c_char = ptr::null_mut();
         let result: bool = ffi_boundary(&mut error_ptr, false, || {
@@ -158,12 +1574,175 @@ mod tests {
         }
     }
 
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_ffi_boundary_panic_with_nul() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let result = ffi_boundary(&mut error_ptr, false, || -> Result<bool, FfiError> {
+            panic!("payload \0 bytes")
+        });
+        assert!(!result);
+        assert!(!error_ptr.is_null());
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "internal panic: payload \\0 bytes");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_ffi_boundary_async() {
+        let value = ffi_boundary_async(ptr::null_mut(), 0, async {
+            tokio::task::yield_now().await;
+            Ok::<_, FfiError>(42)
+        });
+        assert_eq!(value, 42);
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let value = ffi_boundary_async(&mut error_ptr, -1, async { Err(FfiError::Timeout) });
+        assert_eq!(value, -1);
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) }.into_string().unwrap();
+        assert_eq!(msg, "operation timed out");
+        assert_eq!(crate::vimo_ffi_last_error_code(), FfiErrorCode::Timeout as i32);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_ffi_boundary_async_nested_runtime() {
+        let mut inner_error: *mut c_char = ptr::null_mut();
+        let out = &mut inner_error as *mut *mut c_char as usize;
+        let ok = ffi_boundary_async(ptr::null_mut(), false, async move {
+            // 在运行时内再次调用：立即报错，不阻塞也不 panic
+            let nested = ffi_boundary_async(out as *mut *mut c_char, false, async {
+                Ok::<_, FfiError>(true)
+            });
+            Ok::<_, FfiError>(!nested)
+        });
+        assert!(ok);
+        let msg = unsafe { std::ffi::CString::from_raw(inner_error) }.into_string().unwrap();
+        assert_eq!(msg, crate::NESTED_RUNTIME_MESSAGE);
+    }
+
+    #[cfg(all(feature = "tokio", feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_ffi_boundary_async_panic() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let ok = ffi_boundary_async(&mut error_ptr, false, async {
+            tokio::task::yield_now().await;
+            panic!("poll failed");
+            #[allow(unreachable_code)]
+            Ok::<_, FfiError>(true)
+        });
+        assert!(!ok);
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) }.into_string().unwrap();
+        assert_eq!(msg, "internal panic: poll failed");
+
+        // panic 后运行时重新创建，继续可用
+        assert!(ffi_boundary_async(ptr::null_mut(), false, async { Ok::<_, FfiError>(true) }));
+    }
+
+    #[test]
+    fn test_ffi_boundary_deadline() {
+        use std::time::Duration;
+
+        let value = ffi_boundary_deadline(ptr::null_mut(), 0, Duration::from_secs(10), || {
+            assert_eq!(std::thread::current().name(), Some("vimo-ffi-deadline"));
+            Ok::<_, FfiError>(42)
+        });
+        assert_eq!(value, 42);
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let value = ffi_boundary_deadline(&mut error_ptr, -1, Duration::from_secs(10), || {
+            Err::<i32, _>(FfiError::NotFound("doc".into()))
+        });
+        assert_eq!(value, -1);
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) }.into_string().unwrap();
+        assert_eq!(msg, "doc");
+        assert_eq!(crate::vimo_ffi_last_error_code(), FfiErrorCode::NotFound as i32);
+    }
+
+    #[test]
+    fn test_ffi_boundary_deadline_timeout() {
+        use std::sync::mpsc;
+        use std::time::{Duration, Instant};
+
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let (done_tx, done_rx) = mpsc::channel();
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let start = Instant::now();
+        let ok = ffi_boundary_deadline(&mut error_ptr, false, Duration::from_millis(50), move || {
+            // 卡住直到测试放行，模拟挂起的第三方解析器
+            let _ = release_rx.recv();
+            done_tx.send(()).unwrap();
+            Ok::<_, FfiError>(true)
+        });
+        assert!(!ok);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) }.into_string().unwrap();
+        assert_eq!(msg, "operation timed out");
+        assert_eq!(crate::vimo_ffi_last_error_code(), FfiErrorCode::Timeout as i32);
+
+        // 超时后工作线程在后台继续运行到结束
+        release_tx.send(()).unwrap();
+        done_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_ffi_boundary_deadline_panic() {
+        use std::time::Duration;
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let ok = ffi_boundary_deadline(&mut error_ptr, false, Duration::from_secs(10), || {
+            panic!("parser crashed");
+            #[allow(unreachable_code)]
+            Ok::<_, FfiError>(true)
+        });
+        assert!(!ok);
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) }.into_string().unwrap();
+        assert_eq!(msg, "internal panic: parser crashed");
+        assert_eq!(crate::vimo_ffi_last_error_code(), FfiErrorCode::Panic as i32);
+        assert_eq!(crate::last_panic_info().unwrap().message, "parser crashed");
+    }
+
+    #[test]
+    fn test_ffi_boundary_oom_safe() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let result = ffi_boundary_oom_safe(&mut error_ptr, -1, || Err(FfiError::NullPointer));
+        assert_eq!(result, -1);
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "null pointer");
+
+        let result = ffi_boundary_oom_safe(ptr::null_mut(), -1, || Ok::<_, FfiError>(1));
+        assert_eq!(result, 1);
+    }
+
+    #[test]
+    fn test_write_error_reserved_fallback() {
+        let before = crate::test_alloc::live_allocations();
+        let reserve = vec![b' '; 16].into_boxed_slice();
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        // 模拟分配失败，消息写入预留缓冲区
+        unsafe { write_error_reserved(&mut error_ptr, "out of memory!!!!", reserve, |_| None) };
+        let msg = unsafe { std::ffi::CStr::from_ptr(error_ptr) };
+        assert_eq!(msg.to_bytes(), b"out of memory!!");
+
+        let reserve = vec![b' '; 16].into_boxed_slice();
+        let mut short_ptr: *mut c_char = ptr::null_mut();
+        unsafe { write_error_reserved(&mut short_ptr, "oom", reserve, |_| None) };
+        let msg = unsafe { std::ffi::CStr::from_ptr(short_ptr) };
+        assert_eq!(msg.to_bytes(), b"oom            ");
+
+        unsafe { crate::vimo_ffi_free_string(error_ptr) };
+        unsafe { crate::vimo_ffi_free_string(short_ptr) };
+        assert_eq!(crate::test_alloc::live_allocations(), before);
+    }
+
     #[test]
     fn test_ffi_boundary_simple_success() {
         let result = ffi_boundary_simple(-1, || 42);
         assert_eq!(result, 42);
     }
 
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
     #[test]
     fn test_ffi_boundary_simple_panic() {
         let result = ffi_boundary_simple(-1, || {
@@ -173,4 +1752,587 @@ mod tests {
         });
         assert_eq!(result, -1);
     }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_ffi_boundary_with_log_event() {
+        let logged = std::thread::Builder::new()
+            .name("render-worker".to_string())
+            .spawn(|| {
+                let mut logged = None;
+                let log = |event: &PanicEvent| {
+                    let (message, thread) = (event.message.to_string(), event.thread.to_string());
+                    logged = Some((message, event.location.map(str::to_owned), thread));
+                };
+                let result = ffi_boundary_with_log_event(-1, log, || -> i32 {
+                    panic!("frame {} dropped", 7)
+                });
+                assert_eq!(result, -1);
+                logged
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        // 本测试二进制没有安装 panic hook，没有位置
+        assert_eq!(
+            logged,
+            Some(("frame 7 dropped".to_string(), None, "render-worker".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_ffi_boundary_code_success() {
+        let code = ffi_boundary_code(ptr::null_mut(), || Ok::<_, FfiError>(()));
+        assert_eq!(code, 0);
+    }
+
+    #[test]
+    fn test_ffi_boundary_code_ffi_error() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let code = ffi_boundary_code(&mut error_ptr, || Err(FfiError::InvalidUtf8));
+        assert_eq!(code, FfiErrorCode::InvalidUtf8 as i32);
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "invalid UTF-8 string");
+    }
+
+    #[test]
+    fn test_ffi_boundary_code_other_error() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let code = ffi_boundary_code(&mut error_ptr, || Err("plain failure"));
+        assert_eq!(code, -1);
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "plain failure");
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_ffi_boundary_code_panic() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let code = ffi_boundary_code(&mut error_ptr, || {
+            panic!("boom");
+            #[allow(unreachable_code)]
+            Ok::<(), String>(())
+        });
+        assert_eq!(code, -1000);
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "internal panic: boom");
+    }
+
+    #[test]
+    fn test_ffi_boundary_structured_error() {
+        let mut err: *mut VimoError = ptr::null_mut();
+        let result = ffi_boundary_structured(&mut err, -1, || Err(FfiError::NullPointer));
+        assert_eq!(result, -1);
+        assert_eq!(unsafe { (*err).code }, FfiErrorCode::NullPointer as i32);
+        unsafe { vimo_ffi_free_error(err) };
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_ffi_boundary_structured_panic() {
+        let mut err: *mut VimoError = ptr::null_mut();
+        let result = ffi_boundary_structured(&mut err, -1, || {
+            panic!("boom");
+            #[allow(unreachable_code)]
+            Ok::<i32, FfiError>(1)
+        });
+        assert_eq!(result, -1);
+        let (code, msg) = unsafe {
+            (
+                (*err).code,
+                std::ffi::CStr::from_ptr((*err).message).to_str().unwrap().to_owned(),
+            )
+        };
+        assert_eq!(code, FfiErrorCode::Panic as i32);
+        assert_eq!(msg, "internal panic: boom");
+        unsafe { vimo_ffi_free_error(err) };
+    }
+
+    #[test]
+    fn test_ffi_boundary_structured_null_out() {
+        let result = ffi_boundary_structured(ptr::null_mut(), 0, || Err(FfiError::InvalidUtf8));
+        assert_eq!(result, 0);
+    }
+
+    #[test]
+    fn test_typed_boundaries() {
+        assert_eq!(ffi_boundary_i32(ptr::null_mut(), || Ok::<_, FfiError>(7)), 7);
+        assert_eq!(ffi_boundary_i32(ptr::null_mut(), || Err(FfiError::NullPointer)), i32::MIN);
+        assert_eq!(ffi_boundary_i64(ptr::null_mut(), || Err(FfiError::NullPointer)), i64::MIN);
+        assert_eq!(ffi_boundary_usize(ptr::null_mut(), || Ok::<_, FfiError>(3)), 3);
+        assert_eq!(ffi_boundary_usize(ptr::null_mut(), || Err(FfiError::NullPointer)), 0);
+
+        let p = ffi_boundary_ptr(ptr::null_mut(), || {
+            Ok::<_, FfiError>(Box::into_raw(Box::new(5u32)))
+        });
+        assert_eq!(unsafe { *Box::from_raw(p) }, 5);
+    }
+
+    #[test]
+    fn test_ffi_boundary_box() {
+        let p = ffi_boundary_box(ptr::null_mut(), || Ok::<_, FfiError>(vec![1u8, 2, 3]));
+        assert!(!p.is_null());
+        assert_eq!(*unsafe { Box::from_raw(p) }, [1, 2, 3]);
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let p: *mut String = ffi_boundary_box(&mut error_ptr, || Err(FfiError::NullPointer));
+        assert!(p.is_null());
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "null pointer");
+    }
+
+    #[test]
+    fn test_ffi_boundary_unwind() {
+        extern "C-unwind" fn render(fail: bool, out_error: *mut *mut c_char) -> bool {
+            ffi_boundary_unwind(out_error, false, || {
+                if fail {
+                    return Err(FfiError::Timeout);
+                }
+                panic!("render bug")
+            })
+        }
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        assert!(!render(true, &mut error_ptr));
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "operation timed out");
+
+        // panic 穿过 extern "C-unwind" 函数到达调用方
+        let payload = std::panic::catch_unwind(|| render(false, ptr::null_mut())).unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"render bug"));
+    }
+
+    #[test]
+    fn test_out_error_reset_on_success() {
+        // 模拟 C 侧未初始化的变量：哨兵值不能被读取或释放
+        let sentinel = ptr::dangling_mut::<c_char>();
+        let mut error_ptr = sentinel;
+        assert!(ffi_boundary(&mut error_ptr, false, || Ok::<_, FfiError>(true)));
+        assert!(error_ptr.is_null());
+
+        let mut error_ptr = sentinel;
+        assert_eq!(ffi_boundary_code(&mut error_ptr, || Ok::<_, FfiError>(())), 0);
+        assert!(error_ptr.is_null());
+
+        let mut error_ptr = sentinel;
+        assert_eq!(ffi_boundary_errno(&mut error_ptr, || Ok::<_, FfiError>(())), 0);
+        assert!(error_ptr.is_null());
+
+        let mut error_ptr = sentinel;
+        assert!(ffi_boundary_chained(&mut error_ptr, false, || Ok::<_, FfiError>(true)));
+        assert!(error_ptr.is_null());
+
+        let mut err = ptr::dangling_mut::<VimoError>();
+        assert!(ffi_boundary_structured(&mut err, false, || Ok::<_, FfiError>(true)));
+        assert!(err.is_null());
+
+        let (mut errors, mut count) = (ptr::dangling_mut::<*mut c_char>(), 7usize);
+        assert!(ffi_boundary_multi(&mut errors, &mut count, false, || Ok(true)));
+        assert!(errors.is_null());
+        assert_eq!(count, 0);
+
+        // 失败时写出错误，不会把哨兵当作旧消息处理
+        let mut error_ptr = sentinel;
+        assert!(!ffi_boundary(&mut error_ptr, false, || Err(FfiError::Timeout)));
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "operation timed out");
+    }
+
+    #[test]
+    fn test_ffi_boundary_outs() {
+        let (mut w, mut h) = (0u32, 0u32);
+        assert!(ffi_boundary_outs2(&mut w, &mut h, ptr::null_mut(), || {
+            Ok::<_, FfiError>((640, 480))
+        }));
+        assert_eq!((w, h), (640, 480));
+
+        // 失败时输出参数保持不变
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        assert!(!ffi_boundary_outs2(&mut w, &mut h, &mut error_ptr, || {
+            Err::<(u32, u32), _>(FfiError::Timeout)
+        }));
+        assert_eq!((w, h), (640, 480));
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "operation timed out");
+
+        // null 输出参数被跳过
+        let (mut x, mut name) = (0i64, "");
+        let ok = ffi_boundary_outs3(&mut x, ptr::null_mut::<f64>(), &mut name, ptr::null_mut(), || {
+            Ok::<_, FfiError>((-3, 1.5, "box"))
+        });
+        assert!(ok);
+        assert_eq!((x, name), (-3, "box"));
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_ffi_boundary_ptr_panic() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let p: *mut u32 = ffi_boundary_ptr(&mut error_ptr, || -> Result<_, FfiError> {
+            panic!("boom")
+        });
+        assert!(p.is_null());
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "internal panic: boom");
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let p: *mut u32 = ffi_boundary_box(&mut error_ptr, || -> Result<_, FfiError> {
+            panic!("boxed boom")
+        });
+        assert!(p.is_null());
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "internal panic: boxed boom");
+    }
+
+    #[cfg(feature = "test-mode")]
+    #[test]
+    fn test_ffi_boundary_test_mode_reraises() {
+        let outer = std::panic::catch_unwind(|| {
+            ffi_boundary(ptr::null_mut(), false, || -> Result<bool, FfiError> {
+                panic!("visible to the harness")
+            })
+        });
+        let payload = outer.unwrap_err();
+        assert_eq!(extract_panic_message(&payload), "visible to the harness");
+        // 即使重新抛出，最近一次错误仍然记录了 panic
+        assert_eq!(crate::vimo_ffi_last_error_code(), FfiErrorCode::Panic as i32);
+
+        // 其它边界函数同样重新抛出
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let outer = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            ffi_boundary_code(&mut error_ptr, || -> Result<(), FfiError> { panic!("also visible") })
+        }));
+        assert_eq!(extract_panic_message(&outer.unwrap_err()), "also visible");
+        assert!(error_ptr.is_null());
+    }
+
+    #[test]
+    fn test_ffi_boundary_no_catch() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let result = ffi_boundary_no_catch(&mut error_ptr, -1, || Err(FfiError::NullPointer));
+        assert_eq!(result, -1);
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "null pointer");
+
+        let outer = std::panic::catch_unwind(|| {
+            ffi_boundary_no_catch(ptr::null_mut(), 0, || -> Result<i32, FfiError> {
+                panic!("not caught")
+            })
+        });
+        assert!(outer.is_err());
+    }
+
+    #[test]
+    fn test_ffi_boundary_during_unwinding() {
+        use std::cell::Cell;
+
+        struct PanicOnDrop<'a> {
+            called: &'a Cell<bool>,
+            error: &'a Cell<*mut c_char>,
+        }
+
+        impl Drop for PanicOnDrop<'_> {
+            fn drop(&mut self) {
+                let mut error_ptr: *mut c_char = ptr::null_mut();
+                let result = ffi_boundary(&mut error_ptr, -1, || {
+                    self.called.set(true);
+                    Ok::<i32, FfiError>(1)
+                });
+                assert_eq!(result, -1);
+                self.error.set(error_ptr);
+            }
+        }
+
+        let called = Cell::new(false);
+        let error = Cell::new(ptr::null_mut());
+        let outer = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = PanicOnDrop {
+                called: &called,
+                error: &error,
+            };
+            panic!("outer panic");
+        }));
+        assert!(outer.is_err());
+        assert!(!called.get());
+
+        let msg = unsafe { std::ffi::CString::from_raw(error.get()) };
+        assert_eq!(msg.to_str().unwrap(), "called during unwinding");
+    }
+
+    #[test]
+    fn test_ffi_boundary_multi() {
+        let mut array: *mut *mut c_char = ptr::null_mut();
+        let mut count = 0usize;
+        let result = ffi_boundary_multi(&mut array, &mut count, false, || {
+            let mut errors = ErrorList::new();
+            errors.push(FfiError::NullPointer);
+            errors.push(FfiError::custom("name is empty"));
+            errors.into_result()?;
+            Ok(true)
+        });
+        assert!(!result);
+        assert_eq!(count, 2);
+        assert_eq!(
+            unsafe { std::ffi::CStr::from_ptr(*array.add(1)) }.to_str(),
+            Ok("name is empty")
+        );
+        unsafe { crate::vimo_ffi_free_string_array(array, count) };
+
+        let result = ffi_boundary_multi(&mut array, &mut count, false, || Ok(true));
+        assert!(result);
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_ffi_boundary_multi_panic() {
+        let mut array: *mut *mut c_char = ptr::null_mut();
+        let mut count = 0usize;
+        let result = ffi_boundary_multi(&mut array, &mut count, 0, || -> Result<i32, ErrorList> {
+            panic!("boom")
+        });
+        assert_eq!(result, 0);
+        assert_eq!(count, 1);
+        assert_eq!(
+            unsafe { std::ffi::CStr::from_ptr(*array) }.to_str(),
+            Ok("internal panic: boom")
+        );
+        unsafe { crate::vimo_ffi_free_string_array(array, count) };
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_ffi_boundary_records_span() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing_subscriber::layer::{Context, SubscriberExt};
+        use tracing_subscriber::Layer;
+
+        struct SuccessVisitor<'a>(&'a Mutex<Vec<bool>>);
+
+        impl Visit for SuccessVisitor<'_> {
+            fn record_bool(&mut self, field: &Field, value: bool) {
+                if field.name() == "success" {
+                    self.0.lock().unwrap().push(value);
+                }
+            }
+
+            fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+        }
+
+        #[derive(Clone, Default)]
+        struct Recorder {
+            spans: Arc<Mutex<Vec<String>>>,
+            success: Arc<Mutex<Vec<bool>>>,
+        }
+
+        impl<S: tracing::Subscriber> Layer<S> for Recorder {
+            fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+                self.spans.lock().unwrap().push(attrs.metadata().name().to_string());
+            }
+
+            fn on_record(&self, _: &Id, values: &Record<'_>, _: Context<'_, S>) {
+                values.record(&mut SuccessVisitor(&self.success));
+            }
+        }
+
+        let recorder = Recorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            ffi_boundary(ptr::null_mut(), false, || Ok::<_, String>(true));
+            ffi_boundary(ptr::null_mut(), false, || Err::<bool, _>("failed"));
+        });
+
+        assert_eq!(*recorder.spans.lock().unwrap(), ["ffi_boundary", "ffi_boundary"]);
+        assert_eq!(*recorder.success.lock().unwrap(), [true, false]);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_ffi_boundary_traced() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing_subscriber::layer::{Context, SubscriberExt};
+        use tracing_subscriber::Layer;
+
+        struct FieldVisitor<'a>(&'a mut Vec<String>);
+
+        impl Visit for FieldVisitor<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0.push(format!("{}={:?}", field.name(), value));
+            }
+        }
+
+        /// 按顺序记录 span 的创建和字段，以及事件的级别和字段
+        #[derive(Clone, Default)]
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+
+        impl Recorder {
+            fn push(&self, kind: &str, fields: Vec<String>) {
+                self.0.lock().unwrap().push(format!("{kind} {}", fields.join(" ")));
+            }
+        }
+
+        impl<S: tracing::Subscriber> Layer<S> for Recorder {
+            fn on_new_span(&self, attrs: &Attributes<'_>, _: &Id, _: Context<'_, S>) {
+                let mut fields = Vec::new();
+                attrs.record(&mut FieldVisitor(&mut fields));
+                self.push(attrs.metadata().name(), fields);
+            }
+
+            fn on_record(&self, _: &Id, values: &Record<'_>, _: Context<'_, S>) {
+                let mut fields = Vec::new();
+                values.record(&mut FieldVisitor(&mut fields));
+                // elapsed_us 的值不确定，只记录字段名
+                fields.iter_mut().filter(|f| f.starts_with("elapsed_us=")).for_each(|f| {
+                    f.truncate("elapsed_us".len());
+                });
+                self.push("record", fields);
+            }
+
+            fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+                let mut fields = Vec::new();
+                event.record(&mut FieldVisitor(&mut fields));
+                self.push(event.metadata().level().as_str(), fields);
+            }
+        }
+
+        let recorder = Recorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(ffi_boundary_traced("vimo_ok", ptr::null_mut(), false, || {
+                Ok::<_, FfiError>(true)
+            }));
+            let mut error_ptr: *mut c_char = ptr::null_mut();
+            assert!(!ffi_boundary_traced("vimo_fail", &mut error_ptr, false, || {
+                Err::<bool, _>(FfiError::NotFound("doc 7".into()))
+            }));
+            drop(unsafe { std::ffi::CString::from_raw(error_ptr) });
+        });
+
+        let events = recorder.0.lock().unwrap().clone();
+        // 内层 ffi_boundary span 的创建和 success 字段见 test_ffi_boundary_records_span
+        let calls: Vec<_> = events
+            .iter()
+            .filter(|e| !e.starts_with("ffi_boundary") && !e.starts_with("record success"))
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                "ffi_call function=\"vimo_ok\"",
+                "record outcome=\"ok\"",
+                "record elapsed_us",
+                "ffi_call function=\"vimo_fail\"",
+                "record outcome=\"error\"",
+                "record elapsed_us",
+                "ERROR message=doc 7 function=\"vimo_fail\" code=7",
+            ]
+        );
+
+        #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+        {
+            let recorder = Recorder::default();
+            let subscriber = tracing_subscriber::registry().with(recorder.clone());
+            tracing::subscriber::with_default(subscriber, || {
+                ffi_boundary_traced("vimo_crash", ptr::null_mut(), 0, || -> Result<i32, FfiError> {
+                    panic!("boom")
+                });
+                assert_eq!(ffi_boundary_simple(-1, || -> i32 { panic!("simple boom") }), -1);
+            });
+            let events = recorder.0.lock().unwrap().clone();
+            assert!(events.contains(&"record outcome=\"panic\"".to_string()));
+            let crashed = "ERROR message=internal panic: boom function=\"vimo_crash\" code=-1000";
+            assert!(events.contains(&crashed.to_string()));
+            assert!(events.contains(&"ERROR message=panic caught: simple boom".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_ffi_boundary_chained() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let result = ffi_boundary_chained(&mut error_ptr, false, || {
+            let source = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+            Err::<bool, _>(std::io::Error::other(Wrapper(source)))
+        });
+        assert!(!result);
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(
+            msg.to_str().unwrap(),
+            "failed to load config: caused by: permission denied"
+        );
+    }
+
+    #[derive(Debug)]
+    struct Wrapper(std::io::Error);
+
+    impl std::fmt::Display for Wrapper {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("failed to load config")
+        }
+    }
+
+    impl std::error::Error for Wrapper {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    extern "system" fn system_export(fail: bool, out_error: *mut *mut c_char) -> bool {
+        ffi_boundary_system(out_error, false, || {
+            if fail {
+                Err(FfiError::custom("system call failed"))
+            } else {
+                Ok(true)
+            }
+        })
+    }
+
+    #[test]
+    fn test_ffi_boundary_system() {
+        assert!(system_export(false, ptr::null_mut()));
+
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        assert!(!system_export(true, &mut error_ptr));
+        let msg = unsafe { std::ffi::CString::from_raw(error_ptr) };
+        assert_eq!(msg.to_str().unwrap(), "system call failed");
+    }
+
+    #[test]
+    fn test_ffi_boundary_buf_error() {
+        let mut buf = [0 as c_char; 32];
+        let result = ffi_boundary_buf(buf.as_mut_ptr(), buf.len(), -1, || Err(FfiError::NullPointer));
+        assert_eq!(result, -1);
+        let msg = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+        assert_eq!(msg.to_str().unwrap(), "null pointer");
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_ffi_boundary_buf_panic() {
+        let mut buf = [0 as c_char; 12];
+        let result = ffi_boundary_buf(buf.as_mut_ptr(), buf.len(), -1, || {
+            panic!("boom");
+            #[allow(unreachable_code)]
+            Ok::<i32, String>(1)
+        });
+        assert_eq!(result, -1);
+        // "internal panic: boom" 被截断到 11 字节
+        let msg = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+        assert_eq!(msg.to_str().unwrap(), "internal pa");
+
+        // panic 消息中的 NUL 同样转义
+        let mut buf = [0 as c_char; 32];
+        ffi_boundary_buf(buf.as_mut_ptr(), buf.len(), -1, || -> Result<i32, String> {
+            panic!("x\0y")
+        });
+        let msg = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+        assert_eq!(msg.to_str().unwrap(), "internal panic: x\\0y");
+    }
+
+    #[test]
+    fn test_ffi_boundary_buf_no_buffer() {
+        let result = ffi_boundary_buf(ptr::null_mut(), 0, 0, || Err("ignored"));
+        assert_eq!(result, 0);
+    }
 }
@@ -0,0 +1,82 @@
+//! C 侧的 panic 回调
+//!
+//! 宿主有自己的崩溃上报流程时，可以注册回调，在边界每次捕获 panic 时得到消息和位置，
+//! 而不必解析 stderr 或逐个查询 `vimo_ffi_last_panic_message`。
+
+use std::cell::Cell;
+use std::ffi::{c_char, c_void};
+use std::ptr;
+use std::sync::RwLock;
+
+/// C 侧的 panic 回调，见 `vimo_ffi_set_panic_handler`
+pub type CPanicHandler =
+    extern "C" fn(message: *const c_char, location: *const c_char, user_data: *mut c_void);
+
+#[derive(Clone, Copy)]
+struct Handler {
+    callback: CPanicHandler,
+    user_data: *mut c_void,
+}
+
+// SAFETY: 按 `vimo_ffi_set_panic_handler` 的约定，user_data 可以在任意线程上使用
+unsafe impl Send for Handler {}
+unsafe impl Sync for Handler {}
+
+static PANIC_HANDLER: RwLock<Option<Handler>> = RwLock::new(None);
+
+thread_local! {
+    static IN_HANDLER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// 注册 panic 回调，传入 null 则移除，对所有线程生效
+///
+/// 所有捕获 panic 的边界函数在记录日志后、通知错误观察者之前调用回调：
+/// `message` 是 panic 消息，`location` 是 `文件:行号:列号`（未安装 `vimo_ffi_install_panic_hook`
+/// 时为 null）。两个字符串都是临时的，只在回调期间有效，需要保留时自行复制。
+///
+/// 回调在 panic 的线程上同步调用，替换回调与调用可以并发：正在进行的调用会用替换前的
+/// `user_data` 完成，因此 `user_data` 在替换后仍需保持有效，直到正在进行的回调返回。
+/// `user_data` 必须可以在任意线程上使用。回调内部触发的 panic 不会再次调用回调。
+///
+/// # 示例
+///
+/// ```c
+/// void on_panic(const char *message, const char *location, void *user_data) {
+///     crash_reporter_log((CrashReporter *)user_data, message, location ? location : "?");
+/// }
+/// vimo_ffi_set_panic_handler(on_panic, reporter);
+/// ```
+#[no_mangle]
+pub extern "C" fn vimo_ffi_set_panic_handler(
+    handler: Option<CPanicHandler>,
+    user_data: *mut c_void,
+) {
+    let handler = handler.map(|callback| Handler {
+        callback,
+        user_data,
+    });
+    *PANIC_HANDLER.write().unwrap_or_else(|e| e.into_inner()) = handler;
+}
+
+/// 把边界捕获的 panic 交给 C 侧回调
+///
+/// 回调是 `extern "C"` 函数，不会向这里 unwind（Rust 实现的回调 panic 时运行时直接终止进程），
+/// 因此不需要 `catch_unwind`。这里只保证回调内的重入不会递归。
+pub(crate) fn notify_panic_handler(message: &str, location: Option<&str>) {
+    // 先复制出回调再调用，回调内部替换回调时不会死锁
+    let handler = *PANIC_HANDLER.read().unwrap_or_else(|e| e.into_inner());
+    let Some(handler) = handler else {
+        return;
+    };
+    if IN_HANDLER.with(|flag| flag.replace(true)) {
+        return;
+    }
+    let message = crate::string::sanitized_cstring(&crate::redact::redact(message.into()));
+    let location = location.map(crate::string::sanitized_cstring);
+    (handler.callback)(
+        message.as_ptr(),
+        location.as_deref().map_or(ptr::null(), |l| l.as_ptr()),
+        handler.user_data,
+    );
+    IN_HANDLER.with(|flag| flag.set(false));
+}
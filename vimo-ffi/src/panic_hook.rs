@@ -0,0 +1,92 @@
+//! 记录 panic 位置的 panic hook
+//!
+//! panic 的载荷只有消息，没有位置。`install_ffi_panic_hook` 安装的 hook 在 panic 时
+//! 把 `Location` 记录到线程局部，边界函数捕获 panic 后据此写出
+//! `"internal panic at src/codec.rs:217:13: index out of bounds"`。
+
+use std::cell::RefCell;
+use std::panic::PanicHookInfo;
+use std::sync::Once;
+
+/// panic 发生的位置，以及用于和载荷核对的消息（非字符串载荷为 None）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PanicLocation {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub message: Option<String>,
+}
+
+impl std::fmt::Display for PanicLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+thread_local! {
+    static LAST_PANIC: RefCell<Option<PanicLocation>> = const { RefCell::new(None) };
+}
+
+static INSTALL: Once = Once::new();
+
+/// 安装记录 panic 位置的 hook，之后边界函数的 panic 消息带上 `文件:行号:列号`
+///
+/// 新 hook 记录位置后调用安装前的 hook（默认 hook 会继续打印到 stderr），
+/// 因此应在应用自己的 `std::panic::set_hook` 之后调用。重复调用只安装一次。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// #[no_mangle]
+/// pub extern "C" fn vimo_init() {
+///     vimo_ffi::install_ffi_panic_hook();
+/// }
+/// ```
+pub fn install_ffi_panic_hook() {
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            record_panic(info);
+            previous(info);
+        }));
+    });
+}
+
+/// 同 `install_ffi_panic_hook`，供 C 侧在初始化时调用
+#[no_mangle]
+pub extern "C" fn vimo_ffi_install_panic_hook() {
+    install_ffi_panic_hook();
+}
+
+fn record_panic(info: &PanicHookInfo<'_>) {
+    let Some(location) = info.location() else {
+        return;
+    };
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned());
+    let record = PanicLocation {
+        file: location.file().to_owned(),
+        line: location.line(),
+        column: location.column(),
+        message,
+    };
+    // hook 中不能再 panic：线程局部正在销毁时放弃记录
+    let _ = LAST_PANIC.try_with(|last| {
+        if let Ok(mut last) = last.try_borrow_mut() {
+            *last = Some(record);
+        }
+    });
+}
+
+/// 边界进入闭包前清空，避免沿用边界外被捕获的 panic 的位置
+pub(crate) fn clear_panic_location() {
+    let _ = LAST_PANIC.try_with(|last| last.borrow_mut().take());
+}
+
+/// 当前线程最近一次 panic 的位置，未安装 hook 时为 None
+pub(crate) fn panic_location() -> Option<PanicLocation> {
+    LAST_PANIC.with(|last| last.borrow().clone())
+}
@@ -0,0 +1,97 @@
+//! 最近一次 panic 的记录
+//!
+//! 所有边界函数捕获 panic 时都会记录，包括没有 `out_error` 的 `ffi_boundary_simple` 等，
+//! 宿主可以事后查询，而不必依赖输出到 stderr 的日志。
+//! 每个线程保留自己的最近一次记录，另外保留一份进程级的最近一次记录和累计次数。
+
+use std::cell::RefCell;
+use std::ffi::c_char;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 一次被边界捕获的 panic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicInfoRecord {
+    /// panic 消息（载荷不是字符串时为 `"unknown panic"`）
+    pub message: String,
+    /// `文件:行号:列号`，未安装 `install_ffi_panic_hook` 时为空字符串
+    pub location: String,
+    /// 线程名，未命名的线程为 `"<unnamed>"`
+    pub thread: String,
+    /// 捕获时间，Unix 时间戳（毫秒）
+    pub timestamp: u64,
+}
+
+/// 传给 `ffi_boundary_with_log_event` 回调的 panic 描述，只在回调期间有效
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PanicEvent<'a> {
+    /// panic 消息（载荷不是字符串时为 `"unknown panic"`）
+    pub message: &'a str,
+    /// `文件:行号:列号`，未安装 `install_ffi_panic_hook` 时为 None
+    pub location: Option<&'a str>,
+    /// 线程名，未命名的线程为 `"<unnamed>"`
+    pub thread: &'a str,
+}
+
+thread_local! {
+    static THREAD_PANIC: RefCell<Option<PanicInfoRecord>> = const { RefCell::new(None) };
+}
+
+static GLOBAL_PANIC: Mutex<Option<PanicInfoRecord>> = Mutex::new(None);
+static PANIC_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// 记录一次被边界捕获的 panic
+pub(crate) fn record_panic_info(message: &str, location: String) {
+    let record = PanicInfoRecord {
+        message: message.to_owned(),
+        location,
+        thread: std::thread::current().name().unwrap_or("<unnamed>").to_owned(),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64),
+    };
+    PANIC_COUNT.fetch_add(1, Ordering::Relaxed);
+    THREAD_PANIC.with(|last| *last.borrow_mut() = Some(record.clone()));
+    *GLOBAL_PANIC.lock().unwrap_or_else(|e| e.into_inner()) = Some(record);
+}
+
+/// 当前线程最近一次被捕获的 panic
+pub fn last_panic_info() -> Option<PanicInfoRecord> {
+    THREAD_PANIC.with(|last| last.borrow().clone())
+}
+
+/// 进程中（任意线程）最近一次被捕获的 panic
+pub fn last_global_panic_info() -> Option<PanicInfoRecord> {
+    GLOBAL_PANIC.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// 最近一次被捕获的 panic 的消息，没有记录时返回 null
+///
+/// 优先返回当前线程的记录，当前线程没有 panic 过时返回进程中最近一次的记录
+/// （如工作线程 panic 后在主线程查询）。
+/// 返回的字符串必须由调用者使用 `vimo_ffi_free_string` 释放。
+#[no_mangle]
+pub extern "C" fn vimo_ffi_last_panic_message() -> *mut c_char {
+    last_panic_info()
+        .or_else(last_global_panic_info)
+        .map_or(ptr::null_mut(), |record| {
+            let message = crate::redact::redact(record.message.into());
+            crate::string::sanitized_cstring(&message).into_raw()
+        })
+}
+
+/// 进程启动（或上次 `vimo_ffi_clear_panic_info`）以来被边界捕获的 panic 次数
+#[no_mangle]
+pub extern "C" fn vimo_ffi_panic_count() -> u64 {
+    PANIC_COUNT.load(Ordering::Relaxed)
+}
+
+/// 清空当前线程和进程级的 panic 记录，并将次数归零
+#[no_mangle]
+pub extern "C" fn vimo_ffi_clear_panic_info() {
+    THREAD_PANIC.with(|last| last.borrow_mut().take());
+    GLOBAL_PANIC.lock().unwrap_or_else(|e| e.into_inner()).take();
+    PANIC_COUNT.store(0, Ordering::Relaxed);
+}
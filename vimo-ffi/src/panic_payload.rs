@@ -0,0 +1,151 @@
+//! panic 载荷的消息提取
+//!
+//! `panic!` 的载荷是 `&str` 或 `String`，`panic_any` 可以抛出任意类型。
+//! 除内置支持的几种字符串类型外，应用可以为自己的载荷类型注册提取函数，
+//! 避免边界处只剩下 `"unknown panic"`。
+
+use std::any::Any;
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::RwLock;
+
+/// 从 panic 载荷中提取消息，不认识的载荷返回 None
+pub type PanicExtractor = fn(&(dyn Any + Send)) -> Option<String>;
+
+static EXTRACTORS: RwLock<Vec<PanicExtractor>> = RwLock::new(Vec::new());
+
+/// 注册 panic 载荷的消息提取函数
+///
+/// 内置类型（`&str`、`String`、`Cow<str>`、`&String`、`Box<dyn Display>`）都不匹配时，
+/// 按注册顺序依次尝试，第一个返回 `Some` 的结果作为 panic 消息。
+/// 线程安全；同一个函数重复注册只保留一份，便于初始化函数被多次调用。
+/// 提取函数自身 panic 时视为不匹配。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// register_panic_extractor(|payload| {
+///     let diag = payload.downcast_ref::<Diagnostics>()?;
+///     Some(format!("{} ({} errors)", diag.summary, diag.errors.len()))
+/// });
+/// std::panic::panic_any(Diagnostics::collect());
+/// ```
+pub fn register_panic_extractor(f: PanicExtractor) {
+    let mut extractors = EXTRACTORS.write().unwrap_or_else(|e| e.into_inner());
+    if !extractors.iter().any(|&g| g as usize == f as usize) {
+        extractors.push(f);
+    }
+}
+
+/// 提取 panic 载荷的消息：先尝试内置类型，再按注册顺序尝试提取函数
+pub(crate) fn payload_message(payload: &(dyn Any + Send)) -> Option<String> {
+    builtin_message(payload).or_else(|| {
+        // 复制一份再调用，提取函数中再注册也不会死锁
+        let extractors = EXTRACTORS.read().unwrap_or_else(|e| e.into_inner()).clone();
+        extractors
+            .into_iter()
+            .find_map(|f| catch_unwind(AssertUnwindSafe(|| f(payload))).ok().flatten())
+    })
+}
+
+fn builtin_message(payload: &(dyn Any + Send)) -> Option<String> {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        Some(s.to_string())
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        Some(s.clone())
+    } else if let Some(s) = payload.downcast_ref::<Cow<'static, str>>() {
+        Some(s.to_string())
+    } else if let Some(s) = payload.downcast_ref::<&'static String>() {
+        Some(s.to_string())
+    } else if let Some(d) = payload.downcast_ref::<Box<dyn Display + Send>>() {
+        Some(d.to_string())
+    } else {
+        payload
+            .downcast_ref::<Box<dyn Display + Send + Sync>>()
+            .map(|d| d.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::panic_any;
+
+    fn payload_of(f: impl FnOnce()) -> Box<dyn Any + Send> {
+        catch_unwind(AssertUnwindSafe(f)).unwrap_err()
+    }
+
+    #[test]
+    fn test_builtin_payloads() {
+        let cases: [(Box<dyn Any + Send>, &str); 5] = [
+            (payload_of(|| panic!("literal")), "literal"),
+            (payload_of(|| panic!("formatted {}", 1)), "formatted 1"),
+            (payload_of(|| panic_any(Cow::<'static, str>::Borrowed("cow"))), "cow"),
+            (payload_of(|| panic_any(&*Box::leak(Box::new("leaked".to_string())))), "leaked"),
+            (payload_of(|| panic_any(Box::new(42) as Box<dyn Display + Send>)), "42"),
+        ];
+        for (payload, expected) in cases {
+            assert_eq!(payload_message(payload.as_ref()).as_deref(), Some(expected));
+        }
+    }
+
+    struct Diagnostics {
+        stage: &'static str,
+        errors: usize,
+    }
+
+    fn extract_diagnostics(payload: &(dyn Any + Send)) -> Option<String> {
+        let diag = payload.downcast_ref::<Diagnostics>()?;
+        Some(format!("{} failed with {} errors", diag.stage, diag.errors))
+    }
+
+    struct Opaque;
+
+    fn panicking_extractor(payload: &(dyn Any + Send)) -> Option<String> {
+        if payload.is::<Opaque>() {
+            panic!("extractor bug");
+        }
+        None
+    }
+
+    #[test]
+    fn test_registered_extractor() {
+        register_panic_extractor(panicking_extractor);
+        register_panic_extractor(extract_diagnostics);
+        register_panic_extractor(extract_diagnostics);
+        let count = EXTRACTORS.read().unwrap().len();
+        register_panic_extractor(extract_diagnostics);
+        assert_eq!(EXTRACTORS.read().unwrap().len(), count);
+
+        let payload = payload_of(|| {
+            panic_any(Diagnostics {
+                stage: "parse",
+                errors: 1,
+            })
+        });
+        assert_eq!(payload_message(payload.as_ref()).unwrap(), "parse failed with 1 errors");
+
+        // test-mode 下边界函数会重新抛出 panic
+        #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+        {
+            use crate::{ffi_boundary_code, FfiError, FfiErrorCode};
+            use std::ffi::{c_char, CString};
+
+            let mut error_ptr: *mut c_char = std::ptr::null_mut();
+            let rc = ffi_boundary_code(&mut error_ptr, || -> Result<(), FfiError> {
+                panic_any(Diagnostics {
+                    stage: "link",
+                    errors: 3,
+                })
+            });
+            assert_eq!(rc, FfiErrorCode::Panic as i32);
+            let msg = unsafe { CString::from_raw(error_ptr) }.into_string().unwrap();
+            assert_eq!(msg, "internal panic: link failed with 3 errors");
+        }
+
+        // 提取函数 panic 时视为不匹配
+        let payload = payload_of(|| panic_any(Opaque));
+        assert_eq!(payload_message(payload.as_ref()), None);
+    }
+}
@@ -0,0 +1,52 @@
+//! 边界处的 panic 策略
+//!
+//! 默认情况下边界函数把 panic 转换为错误。服务端部署可能更希望快速失败：
+//! panic 直接终止进程，由进程管理器重启，而不是交给可能忽略错误的 C 代码。
+//! 策略是进程级的原子设置，所有捕获 panic 的边界函数都会读取。
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 边界函数捕获到 panic 后的处理方式
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// 转换为错误（默认）
+    Catch = 0,
+    /// 记录后输出到 stderr，然后调用 `std::process::abort`
+    Abort = 1,
+    /// 记录后通过 `resume_unwind` 继续展开
+    ///
+    /// 只有调用方是 Rust，或者导出函数声明为 `extern "C-unwind"` 时才是安全的：
+    /// panic 展开穿过普通的 `extern "C"` 函数时，运行时会直接终止进程。
+    Rethrow = 2,
+}
+
+static PANIC_POLICY: AtomicU8 = AtomicU8::new(PanicPolicy::Catch as u8);
+
+/// 设置全局 panic 策略，对所有线程生效
+///
+/// 无论哪种策略，panic 都会先记录到最近一次错误和 panic 记录中，并通知错误观察者。
+/// 不捕获 panic 的 `ffi_boundary_no_catch` 不受影响。
+pub fn set_panic_policy(policy: PanicPolicy) {
+    PANIC_POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+/// 当前的全局 panic 策略
+pub fn panic_policy() -> PanicPolicy {
+    match PANIC_POLICY.load(Ordering::Relaxed) {
+        x if x == PanicPolicy::Abort as u8 => PanicPolicy::Abort,
+        x if x == PanicPolicy::Rethrow as u8 => PanicPolicy::Rethrow,
+        _ => PanicPolicy::Catch,
+    }
+}
+
+/// 从 C 侧设置全局 panic 策略：`0` 为 `Catch`，`1` 为 `Abort`，`2` 为 `Rethrow`，其余值忽略
+#[no_mangle]
+pub extern "C" fn vimo_ffi_set_panic_policy(policy: i32) {
+    match policy {
+        0 => set_panic_policy(PanicPolicy::Catch),
+        1 => set_panic_policy(PanicPolicy::Abort),
+        2 => set_panic_policy(PanicPolicy::Rethrow),
+        _ => {}
+    }
+}
@@ -0,0 +1,138 @@
+//! 从中毒的锁中恢复
+//!
+//! 边界捕获 panic 后进程继续运行，但闭包 panic 时持有的 `Mutex` / `RwLock` 已经中毒：
+//! 之后每次 `lock().unwrap()` 都会失败，导出函数从此只返回令人困惑的 `PoisonError`。
+//! 这里的函数在锁中毒时取回其中的数据并清除中毒标记。
+//!
+//! 恢复并不修复数据：panic 时的修改可能只完成了一半。第一次恢复时通过错误观察者
+//! （以及启用 `log` feature 时的 warn 日志）提示一次，避免这种情况被静默忽略。
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+use crate::observer::notify_error;
+use crate::FfiErrorCode;
+
+/// 第一次从中毒的锁中恢复时通知错误观察者的消息
+pub const POISON_RECOVERED_MESSAGE: &str =
+    "recovered a poisoned lock, its data may be partially updated";
+
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// 加锁，锁已中毒时清除中毒标记并照常返回守卫
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// static CACHE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+///
+/// ffi_boundary(out_error, false, || {
+///     lock_recover(&CACHE).push(name.to_owned());
+///     Ok::<_, FfiError>(true)
+/// })
+/// ```
+pub fn lock_recover<T: ?Sized>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|poisoned| {
+        m.clear_poison();
+        warn_recovered();
+        poisoned.into_inner()
+    })
+}
+
+/// 同 `lock_recover`，但不阻塞：锁被其它线程持有时返回 None
+pub fn try_lock_recover<T: ?Sized>(m: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    match m.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(poisoned)) => {
+            m.clear_poison();
+            warn_recovered();
+            Some(poisoned.into_inner())
+        }
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+/// `RwLock` 的读锁版本，见 `lock_recover`
+pub fn read_recover<T: ?Sized>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|poisoned| {
+        lock.clear_poison();
+        warn_recovered();
+        poisoned.into_inner()
+    })
+}
+
+/// `RwLock` 的写锁版本，见 `lock_recover`
+pub fn write_recover<T: ?Sized>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|poisoned| {
+        lock.clear_poison();
+        warn_recovered();
+        poisoned.into_inner()
+    })
+}
+
+/// 进程中第一次恢复时提示，之后不再重复
+fn warn_recovered() {
+    if WARNED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    #[cfg(feature = "log")]
+    log::warn!("[vimo-ffi] {}", POISON_RECOVERED_MESSAGE);
+    notify_error(FfiErrorCode::Unknown as i32, POISON_RECOVERED_MESSAGE, false);
+}
+
+#[cfg(all(test, feature = "catch-unwind", not(feature = "test-mode")))]
+mod tests {
+    use super::*;
+    use crate::{ffi_boundary, FfiError};
+    use std::ptr;
+
+    /// 在边界内持锁 panic，让锁中毒
+    fn poison<T>(f: impl FnOnce() -> T) {
+        let ok = ffi_boundary(ptr::null_mut(), false, || {
+            let _guard = f();
+            panic!("panicked while holding the lock");
+            #[allow(unreachable_code)]
+            Ok::<_, FfiError>(true)
+        });
+        assert!(!ok);
+    }
+
+    #[test]
+    fn test_lock_recover() {
+        static COUNTER: Mutex<u32> = Mutex::new(0);
+        poison(|| lock_recover(&COUNTER));
+        assert!(COUNTER.is_poisoned());
+
+        let bump = || {
+            ffi_boundary(ptr::null_mut(), 0, || {
+                let mut counter = lock_recover(&COUNTER);
+                *counter += 1;
+                Ok::<_, FfiError>(*counter)
+            })
+        };
+        assert_eq!(bump(), 1);
+        assert_eq!(bump(), 2);
+        assert!(!COUNTER.is_poisoned());
+
+        poison(|| lock_recover(&COUNTER));
+        assert_eq!(try_lock_recover(&COUNTER).map(|c| *c), Some(2));
+        let held = lock_recover(&COUNTER);
+        assert!(try_lock_recover(&COUNTER).is_none());
+        drop(held);
+    }
+
+    #[test]
+    fn test_rwlock_recover() {
+        static NAMES: RwLock<Vec<&str>> = RwLock::new(Vec::new());
+        poison(|| write_recover(&NAMES));
+        assert!(NAMES.is_poisoned());
+
+        let ok = ffi_boundary(ptr::null_mut(), false, || {
+            write_recover(&NAMES).push("vimo");
+            Ok::<_, FfiError>(true)
+        });
+        assert!(ok);
+        assert_eq!(*read_recover(&NAMES), ["vimo"]);
+        assert!(!NAMES.is_poisoned());
+    }
+}
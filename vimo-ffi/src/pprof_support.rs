@@ -0,0 +1,116 @@
+//! `pprof` 集成
+//!
+//! `set_pprof_enabled(true)` 之后，边界函数执行期间启动 pprof 采样，
+//! 把当前线程的样本按导出函数名归类累积起来，嵌入方无需改动 C 侧就能得到 Rust 代码的火焰图。
+//! `ffi_boundary_named` 的调用以函数名为火焰图的根，其余边界调用归入 `"anonymous"`。
+//! 每次采样都要启停定时器并分配内存，默认关闭，只在排查性能问题时打开。
+//!
+//! pprof 的采样器是进程级的：嵌套的边界调用、以及其它线程同时进行的边界调用不会重复启动，
+//! 它们的耗时只出现在（或不出现在）外层调用的样本中。采样依赖 `SIGPROF`，
+//! 宿主自己也使用该信号时不要启用此 feature。
+
+use std::ffi::c_char;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use pprof::{ProfilerGuard, Report};
+
+/// 采样频率（Hz）
+pub const PPROF_SAMPLE_FREQUENCY: i32 = 1000;
+
+/// 未通过 `ffi_boundary_named` 调用的边界在火焰图中的标签
+pub const PPROF_ANONYMOUS_LABEL: &str = "anonymous";
+
+static PPROF_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 累积的样本，`timing.duration` 为各次采样时长之和
+static PROFILES: Mutex<Option<Report>> = Mutex::new(None);
+
+/// 一次边界调用的采样，丢弃时把当前线程的样本合并到累积结果中
+pub(crate) struct BoundarySample {
+    guard: ProfilerGuard<'static>,
+    label: &'static str,
+    thread_id: u64,
+}
+
+/// 开启或关闭边界调用的采样，对所有线程生效；关闭不清空已累积的样本
+pub fn set_pprof_enabled(enabled: bool) {
+    PPROF_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// 边界调用的采样是否开启
+pub fn pprof_enabled() -> bool {
+    PPROF_ENABLED.load(Ordering::Relaxed)
+}
+
+/// 同 `set_pprof_enabled`，供 C 侧调用
+#[no_mangle]
+pub extern "C" fn vimo_ffi_pprof_set_enabled(enabled: bool) {
+    set_pprof_enabled(enabled);
+}
+
+/// 开始采样；未开启，或采样器已经在运行（嵌套调用或其它线程的调用）时返回 None
+pub(crate) fn start_sample() -> Option<BoundarySample> {
+    if !pprof_enabled() {
+        return None;
+    }
+    let guard = ProfilerGuard::new(PPROF_SAMPLE_FREQUENCY).ok()?;
+    Some(BoundarySample {
+        guard,
+        label: crate::last_error::current_function().unwrap_or(PPROF_ANONYMOUS_LABEL),
+        thread_id: unsafe { libc::pthread_self() } as u64,
+    })
+}
+
+impl Drop for BoundarySample {
+    fn drop(&mut self) {
+        let Ok(mut report) = self.guard.report().build() else {
+            return;
+        };
+        // 火焰图以 thread_name 为根，这里换成导出函数名
+        let samples = std::mem::take(&mut report.data).into_iter().filter_map(|(mut frames, n)| {
+            (frames.thread_id == self.thread_id).then(|| {
+                frames.thread_name = self.label.to_owned();
+                (frames, n)
+            })
+        });
+        let mut profiles = PROFILES.lock().unwrap_or_else(|e| e.into_inner());
+        match profiles.as_mut() {
+            Some(profiles) => {
+                profiles.timing.duration += report.timing.duration;
+                for (frames, n) in samples {
+                    *profiles.data.entry(frames).or_insert(0) += n;
+                }
+            }
+            None => {
+                report.data = samples.collect();
+                *profiles = Some(report);
+            }
+        }
+    }
+}
+
+/// 把累积的样本渲染为火焰图 SVG，没有样本或渲染失败时返回 null
+///
+/// 返回的字符串必须由调用者使用 `vimo_ffi_free_string` 释放。
+#[no_mangle]
+pub extern "C" fn vimo_ffi_pprof_report() -> *mut c_char {
+    let mut svg = Vec::new();
+    {
+        let profiles = PROFILES.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(report) = profiles.as_ref().filter(|r| !r.data.is_empty()) else {
+            return ptr::null_mut();
+        };
+        if report.flamegraph(&mut svg).is_err() {
+            return ptr::null_mut();
+        }
+    }
+    crate::string::sanitized_cstring(&String::from_utf8_lossy(&svg)).into_raw()
+}
+
+/// 清空累积的样本
+#[no_mangle]
+pub extern "C" fn vimo_ffi_pprof_reset() {
+    PROFILES.lock().unwrap_or_else(|e| e.into_inner()).take();
+}
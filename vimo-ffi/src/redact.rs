@@ -0,0 +1,52 @@
+//! 错误消息脱敏钩子
+//!
+//! panic 消息和错误消息可能带有用户的文件路径、令牌等敏感信息（例如对 URL 解析结果 `unwrap()`），
+//! 而宿主常常把 FFI 错误上报给第三方服务。安装脱敏函数后，消息在交给 C 侧之前先经过它；
+//! 默认不做任何处理（恒等函数）。
+
+use std::borrow::Cow;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::RwLock;
+
+/// 脱敏函数：接收即将写出的消息，返回脱敏后的消息
+pub type MessageRedactor = fn(&str) -> String;
+
+/// 脱敏函数 panic 时代替整条消息写出的文本
+pub const REDACTION_FAILED_MESSAGE: &str = "redacted: formatting failed";
+
+// None 即恒等函数，不安装时不分配
+static REDACTOR: RwLock<Option<MessageRedactor>> = RwLock::new(None);
+
+/// 安装全局脱敏函数，对所有线程生效，可以随时替换
+///
+/// 作用于所有写出的错误消息（`set_error`、`set_error_from`、边界函数的错误和 panic 等）、
+/// 线程局部的最近一次错误、错误观察者和 panic 回调收到的消息，以及 `vimo_ffi_last_panic_message`。
+/// 在翻译函数之后、按 `set_max_error_len` 截断之前执行。
+/// 脱敏函数 panic 时整条消息替换为 `REDACTION_FAILED_MESSAGE`，不会回退到原文。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// set_message_redactor(|msg| msg.replace(home_dir(), "~"));
+/// ```
+pub fn set_message_redactor(redactor: MessageRedactor) {
+    *REDACTOR.write().unwrap_or_else(|e| e.into_inner()) = Some(redactor);
+}
+
+/// 移除全局脱敏函数，恢复为原样写出
+pub fn clear_message_redactor() {
+    REDACTOR.write().unwrap_or_else(|e| e.into_inner()).take();
+}
+
+/// 按当前脱敏函数处理消息，未安装时原样返回
+pub(crate) fn redact(message: Cow<'_, str>) -> Cow<'_, str> {
+    // 先复制出函数指针再调用，脱敏函数内部替换脱敏函数时不会死锁
+    let redactor = *REDACTOR.read().unwrap_or_else(|e| e.into_inner());
+    let Some(redactor) = redactor else {
+        return message;
+    };
+    match catch_unwind(AssertUnwindSafe(|| redactor(&message))) {
+        Ok(redacted) => Cow::Owned(redacted),
+        Err(_) => Cow::Borrowed(REDACTION_FAILED_MESSAGE),
+    }
+}
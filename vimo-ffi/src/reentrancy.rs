@@ -0,0 +1,137 @@
+//! 不可重入导出函数的重入检测
+//!
+//! 有些导出函数会触发宿主的回调，回调中再调用同一个函数时通常会在函数持有的锁上死锁，
+//! 而且没有任何提示。`ReentrancyGuard::enter` 在线程局部记录正在执行的入口，
+//! 同一线程上重复进入时返回 `FfiError::ReentrantCall`，把死锁变成可诊断的错误。
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+
+use crate::FfiError;
+
+thread_local! {
+    static ACTIVE: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+/// 不可重入入口的检测器，见 `ReentrancyGuard::enter`
+pub struct ReentrancyGuard;
+
+impl ReentrancyGuard {
+    /// 进入名为 `name` 的入口，当前线程已经在该入口中时返回 `FfiError::ReentrantCall`
+    ///
+    /// 返回的令牌在丢弃时退出入口，闭包 panic 展开时同样会退出。
+    /// 只检测同一线程上的重入，其它线程同时调用不受影响。
+    ///
+    /// # 示例
+    ///
+    /// ```rust,ignore
+    /// #[no_mangle]
+    /// pub extern "C" fn vimo_sync(out_error: *mut *mut c_char) -> bool {
+    ///     ffi_boundary(out_error, false, || {
+    ///         let _token = ReentrancyGuard::enter("vimo_sync")?;
+    ///         engine().sync_and_notify()?;
+    ///         Ok(true)
+    ///     })
+    /// }
+    /// ```
+    pub fn enter(name: &'static str) -> Result<ReentrancyToken, FfiError> {
+        ACTIVE.with(|active| {
+            let mut active = active.borrow_mut();
+            if active.contains(&name) {
+                return Err(FfiError::ReentrantCall(name.to_owned()));
+            }
+            active.push(name);
+            Ok(ReentrancyToken {
+                name,
+                _not_send: PhantomData,
+            })
+        })
+    }
+
+    /// 当前线程是否正在名为 `name` 的入口中
+    pub fn is_active(name: &str) -> bool {
+        ACTIVE.with(|active| active.borrow().contains(&name))
+    }
+}
+
+/// 已进入的入口，丢弃时退出；只能在进入的线程上丢弃
+#[must_use = "令牌被丢弃时立即退出入口"]
+pub struct ReentrancyToken {
+    name: &'static str,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ReentrancyToken {
+    fn drop(&mut self) {
+        // 线程局部正在销毁时无需清理
+        let _ = ACTIVE.try_with(|active| {
+            let mut active = active.borrow_mut();
+            if let Some(index) = active.iter().rposition(|&name| name == self.name) {
+                active.remove(index);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ffi_boundary_nonreentrant, FfiErrorCode};
+    use std::ffi::{c_char, CString};
+    use std::ptr;
+
+    #[test]
+    fn test_enter_and_release() {
+        let token = ReentrancyGuard::enter("open").unwrap();
+        assert!(ReentrancyGuard::is_active("open"));
+        assert_eq!(
+            ReentrancyGuard::enter("open").err(),
+            Some(FfiError::ReentrantCall("open".into()))
+        );
+        // 不同的入口互不影响
+        let other = ReentrancyGuard::enter("close").unwrap();
+        drop(token);
+        assert!(!ReentrancyGuard::is_active("open"));
+        assert!(ReentrancyGuard::is_active("close"));
+        drop(other);
+        assert!(ReentrancyGuard::enter("open").is_ok());
+    }
+
+    /// 模拟触发宿主回调的导出函数，回调中再次调用自身
+    fn vimo_sync(depth: u32, out_error: *mut *mut c_char) -> bool {
+        ffi_boundary_nonreentrant("vimo_sync", out_error, false, || {
+            if depth > 0 {
+                let mut inner_error: *mut c_char = ptr::null_mut();
+                assert!(!vimo_sync(depth - 1, &mut inner_error));
+                let msg = unsafe { CString::from_raw(inner_error) }.into_string().unwrap();
+                assert_eq!(msg, "reentrant call to 'vimo_sync'");
+                assert_eq!(crate::vimo_ffi_last_error_code(), FfiErrorCode::ReentrantCall as i32);
+            }
+            Ok::<_, FfiError>(true)
+        })
+    }
+
+    #[test]
+    fn test_ffi_boundary_nonreentrant() {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        assert!(vimo_sync(1, &mut error_ptr));
+        assert!(error_ptr.is_null());
+        assert!(!ReentrancyGuard::is_active("vimo_sync"));
+        assert!(vimo_sync(0, ptr::null_mut()));
+    }
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    #[test]
+    fn test_token_released_on_panic() {
+        let ok = ffi_boundary_nonreentrant("render", ptr::null_mut(), false, || {
+            panic!("render failed");
+            #[allow(unreachable_code)]
+            Ok::<_, FfiError>(true)
+        });
+        assert!(!ok);
+        assert!(!ReentrancyGuard::is_active("render"));
+        assert!(ffi_boundary_nonreentrant("render", ptr::null_mut(), false, || {
+            Ok::<_, FfiError>(true)
+        }));
+    }
+}
@@ -0,0 +1,195 @@
+//! 应用自定义错误码注册表
+//!
+//! 下游库通过 `FfiError::custom_with_code` 使用自己的错误码时，先在初始化阶段登记名称，
+//! 宿主就可以通过 `vimo_ffi_error_code_name` 得到可读的诊断信息。
+
+use std::collections::BTreeMap;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+use std::sync::RwLock;
+
+use thiserror::Error;
+
+use crate::FfiErrorCode;
+
+/// 注册错误码失败的原因
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    /// `100` 及以下的错误码保留给内置错误
+    #[error("error code {0} is reserved for built-in errors")]
+    Reserved(i32),
+
+    /// 错误码已经以其它名称注册
+    #[error("error code {code} is already registered as '{name}'")]
+    AlreadyRegistered { code: i32, name: &'static str },
+
+    /// 名称中含有 NUL，无法作为 C 字符串返回
+    #[error("error code name contains null byte")]
+    InvalidName,
+}
+
+struct Entry {
+    name: &'static str,
+    c_name: &'static CStr,
+}
+
+static REGISTRY: RwLock<BTreeMap<i32, Entry>> = RwLock::new(BTreeMap::new());
+
+/// 注册应用自定义错误码的名称
+///
+/// 可以在任何边界调用之前（如库的初始化函数中）调用，线程安全。
+/// 以相同名称重复注册同一错误码视为成功，便于初始化函数被多次调用。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// register_error_code(1001, "QuotaExceeded")?;
+/// return Err(FfiError::custom_with_code(1001, "upload quota exceeded"));
+/// ```
+pub fn register_error_code(code: i32, name: &'static str) -> Result<(), RegistryError> {
+    if code <= FfiErrorCode::Custom as i32 {
+        return Err(RegistryError::Reserved(code));
+    }
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    if let Some(entry) = registry.get(&code) {
+        return if entry.name == name {
+            Ok(())
+        } else {
+            Err(RegistryError::AlreadyRegistered {
+                code,
+                name: entry.name,
+            })
+        };
+    }
+    let c_name = CString::new(name).map_err(|_| RegistryError::InvalidName)?;
+    // 注册表只增不减，名称的 C 字符串在进程生命周期内有效
+    let c_name: &'static CStr = Box::leak(c_name.into_boxed_c_str());
+    registry.insert(code, Entry { name, c_name });
+    Ok(())
+}
+
+/// 错误码是否是内置错误码或已注册的应用错误码
+pub fn is_registered_error_code(code: i32) -> bool {
+    FfiErrorCode::from_i32(code).is_some() || lookup(code).is_some()
+}
+
+/// 错误码的名称：内置错误码见 `FfiErrorCode::name`，应用错误码为注册时的名称
+pub fn error_code_name(code: i32) -> Option<&'static str> {
+    match FfiErrorCode::from_i32(code) {
+        Some(builtin) => Some(builtin.name()),
+        None => lookup(code).map(|entry| entry.0),
+    }
+}
+
+fn lookup(code: i32) -> Option<(&'static str, &'static CStr)> {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry.get(&code).map(|entry| (entry.name, entry.c_name))
+}
+
+/// 从名称查找错误码：内置名称（见 `FfiErrorCode::from_name`）或注册时的名称
+pub fn error_code_from_name(name: &str) -> Option<i32> {
+    if let Some(builtin) = FfiErrorCode::from_name(name) {
+        return Some(builtin as i32);
+    }
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry
+        .iter()
+        .find(|(_, entry)| entry.name == name)
+        .map(|(code, _)| *code)
+}
+
+/// 错误码的名称，用于诊断输出和按名称保存错误码的配置
+///
+/// 内置错误码返回其稳定名称（如 `"NULL_POINTER"`），已注册的应用错误码返回注册时的名称，
+/// 未知错误码返回 null。返回的字符串由本库持有，在进程生命周期内有效，**不要释放**。
+#[no_mangle]
+pub extern "C" fn vimo_ffi_error_code_name(code: i32) -> *const c_char {
+    match FfiErrorCode::from_i32(code) {
+        Some(builtin) => builtin.c_name().as_ptr(),
+        None => lookup(code).map_or(ptr::null(), |entry| entry.1.as_ptr()),
+    }
+}
+
+/// 从名称查找错误码，`vimo_ffi_error_code_name` 的逆操作
+///
+/// `name` 为 null、不是 UTF-8 或未知时返回 `-1`（与 `"UNKNOWN"` 相同）。
+///
+/// # Safety
+/// `name` 为 null 或指向以 null 结尾的 C 字符串
+#[no_mangle]
+pub unsafe extern "C" fn vimo_ffi_error_code_from_name(name: *const c_char) -> i32 {
+    let name = match crate::cstr_to_str(name) {
+        Ok(name) => name,
+        Err(_) => return FfiErrorCode::Unknown as i32,
+    };
+    error_code_from_name(name).unwrap_or(FfiErrorCode::Unknown as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name_of(code: i32) -> Option<String> {
+        let ptr = vimo_ffi_error_code_name(code);
+        if ptr.is_null() {
+            return None;
+        }
+        Some(unsafe { CStr::from_ptr(ptr) }.to_str().unwrap().to_owned())
+    }
+
+    #[test]
+    fn test_register_rejects_builtin_codes() {
+        for code in [-1000, -1, 0, 1, 7, 50, 100] {
+            assert_eq!(register_error_code(code, "Mine"), Err(RegistryError::Reserved(code)));
+        }
+        assert_eq!(name_of(1).as_deref(), Some("NULL_POINTER"));
+    }
+
+    #[test]
+    fn test_register_collision() {
+        assert_eq!(register_error_code(9001, "QuotaExceeded"), Ok(()));
+        assert_eq!(register_error_code(9001, "QuotaExceeded"), Ok(()));
+        assert_eq!(
+            register_error_code(9001, "RateLimited"),
+            Err(RegistryError::AlreadyRegistered {
+                code: 9001,
+                name: "QuotaExceeded"
+            })
+        );
+        assert_eq!(register_error_code(9002, "Bad\0Name"), Err(RegistryError::InvalidName));
+    }
+
+    #[test]
+    fn test_lookup_registered_and_unknown() {
+        register_error_code(9100, "DiskFull").unwrap();
+        assert_eq!(name_of(9100).as_deref(), Some("DiskFull"));
+        assert_eq!(error_code_name(9100), Some("DiskFull"));
+        assert!(is_registered_error_code(9100));
+
+        assert_eq!(name_of(9199), None);
+        assert_eq!(error_code_name(9199), None);
+        assert!(!is_registered_error_code(9199));
+        assert_eq!(error_code_name(-1000), Some("PANIC"));
+    }
+
+    #[test]
+    fn test_code_from_name() {
+        let from_name = |name: &CStr| unsafe { vimo_ffi_error_code_from_name(name.as_ptr()) };
+        assert_eq!(from_name(c"NULL_POINTER"), 1);
+        assert_eq!(from_name(c"PANIC"), -1000);
+        assert_eq!(from_name(c"OK"), 0);
+        assert_eq!(from_name(c"NO_SUCH_NAME"), -1);
+        assert_eq!(unsafe { vimo_ffi_error_code_from_name(ptr::null()) }, -1);
+
+        register_error_code(9200, "QUOTA_EXCEEDED").unwrap();
+        assert_eq!(from_name(c"QUOTA_EXCEEDED"), 9200);
+        assert_eq!(error_code_from_name("QUOTA_EXCEEDED"), Some(9200));
+        assert_eq!(error_code_from_name("NoSuchName"), None);
+
+        // 名称和错误码双向一致
+        for code in [1, 7, 15, 100, 9200] {
+            let name = unsafe { CStr::from_ptr(vimo_ffi_error_code_name(code)) };
+            assert_eq!(from_name(name), code);
+        }
+    }
+}
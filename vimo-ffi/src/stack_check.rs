@@ -0,0 +1,117 @@
+//! 调试构建中检查指向已返回栈帧的指针
+//!
+//! 常见的错误是把函数局部缓冲区的指针返回给调用方，之后再转换成字符串。
+//! 线程栈向低地址增长，低于当前栈指针的部分不属于任何存活的栈帧；
+//! 指针落在这段区域时一定是悬垂的。只检查这一种情况，调用方栈上的缓冲区
+//! （如 C 侧的 `char buf[64]`）位于当前栈指针之上，不会被误报。
+
+#[cfg(any(target_os = "linux", windows))]
+use std::cell::Cell;
+use std::ffi::c_char;
+
+/// 栈指针以下仍可能被当前函数使用的区域（x86-64 System V 的 red zone）
+#[cfg(any(target_os = "linux", windows))]
+const RED_ZONE: usize = 128;
+
+#[cfg(any(target_os = "linux", windows))]
+thread_local! {
+    static STACK_BOUNDS: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+/// `ptr` 指向当前线程栈上已经返回的栈帧时触发 `debug_assert!`
+#[cfg(any(target_os = "linux", windows))]
+#[inline(never)]
+pub(crate) fn debug_check_stack_pointer(ptr: *const c_char) {
+    let Some((low, high)) = stack_bounds() else {
+        return;
+    };
+    let marker = 0u8;
+    let sp = std::hint::black_box(&marker) as *const u8 as usize;
+    // 运行在备用信号栈等其它栈上时无法判断
+    if !(low..high).contains(&sp) {
+        return;
+    }
+    let addr = ptr as usize;
+    debug_assert!(
+        !(low..sp.saturating_sub(RED_ZONE)).contains(&addr),
+        "pointer {:p} points into a stack frame that has already returned; \
+         it was probably a local buffer of a function that has returned",
+        ptr
+    );
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub(crate) fn debug_check_stack_pointer(_ptr: *const c_char) {}
+
+/// 当前线程栈的 `[low, high)`，每个线程只查询一次
+#[cfg(any(target_os = "linux", windows))]
+fn stack_bounds() -> Option<(usize, usize)> {
+    STACK_BOUNDS
+        .try_with(|bounds| {
+            if bounds.get().is_none() {
+                bounds.set(query_stack_bounds());
+            }
+            bounds.get()
+        })
+        .ok()
+        .flatten()
+}
+
+#[cfg(target_os = "linux")]
+fn query_stack_bounds() -> Option<(usize, usize)> {
+    unsafe {
+        let mut attr: libc::pthread_attr_t = std::mem::zeroed();
+        if libc::pthread_getattr_np(libc::pthread_self(), &mut attr) != 0 {
+            return None;
+        }
+        let mut addr = std::ptr::null_mut();
+        let mut size = 0;
+        let rc = libc::pthread_attr_getstack(&attr, &mut addr, &mut size);
+        libc::pthread_attr_destroy(&mut attr);
+        (rc == 0).then(|| (addr as usize, addr as usize + size))
+    }
+}
+
+#[cfg(windows)]
+fn query_stack_bounds() -> Option<(usize, usize)> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThreadStackLimits(low_limit: *mut usize, high_limit: *mut usize);
+    }
+    let (mut low, mut high) = (0, 0);
+    unsafe { GetCurrentThreadStackLimits(&mut low, &mut high) };
+    (low < high).then_some((low, high))
+}
+
+#[cfg(all(test, any(target_os = "linux", windows)))]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    /// 返回自身局部缓冲区的指针，缓冲区足够大，保证指针远低于调用方之后的栈指针
+    #[inline(never)]
+    fn dangling_local() -> *const c_char {
+        let mut buf = [b'x'; 16 * 1024];
+        buf[1] = 0;
+        std::hint::black_box(buf.as_ptr()).cast()
+    }
+
+    #[test]
+    fn test_live_pointers_pass() {
+        let heap = CString::new("heap").unwrap();
+        debug_check_stack_pointer(heap.as_ptr());
+        debug_check_stack_pointer(c"static".as_ptr());
+        // 调用方栈帧中的缓冲区仍然存活
+        let local = *b"local\0";
+        debug_check_stack_pointer(local.as_ptr().cast());
+    }
+
+    #[test]
+    fn test_returned_frame_is_detected() {
+        let ptr = dangling_local();
+        let result = std::panic::catch_unwind(|| debug_check_stack_pointer(ptr));
+        let payload = result.unwrap_err();
+        let message = payload.downcast_ref::<String>().unwrap();
+        assert!(message.contains("stack frame that has already returned"), "{message}");
+    }
+}
@@ -1,28 +1,110 @@
 //! C 字符串转换工具
 
+use std::borrow::Cow;
 use std::ffi::{c_char, CStr, CString};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
 
 use crate::FfiError;
 
+static MAX_CSTRING_LEN: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// 设置 `str_to_cstring` 的全局长度上限（字节，不含结尾 NUL），对所有线程生效
+///
+/// 默认为 `usize::MAX`，即不限制。同样作用于基于 `str_to_cstring` 的
+/// `str_to_u8ptr` 和 `path_to_cstring`。
+pub fn set_max_cstring_len(max_bytes: usize) {
+    MAX_CSTRING_LEN.store(max_bytes, Ordering::Relaxed);
+}
+
+/// 当前 `str_to_cstring` 的全局长度上限
+pub fn max_cstring_len() -> usize {
+    MAX_CSTRING_LEN.load(Ordering::Relaxed)
+}
+
 /// 将 C 字符串指针转换为 Rust &str
 ///
+/// 返回值的生命周期 `'a` 由调用者任意选择，编译器无法检查它是否超出了
+/// 底层内存的存活期。只做一次性转换时优先使用 `cstr_borrow`。
+///
+/// 调试构建（Linux、Windows）中，指针指向当前线程栈上已经返回的栈帧时触发 `debug_assert!`，
+/// 用于发现返回了局部缓冲区指针的错误。只是启发式检查，不能发现堆上的悬垂指针。
+///
 /// # Safety
-/// 调用者必须确保指针有效且指向以 null 结尾的 UTF-8 字符串
+/// 调用者必须确保指针有效且指向以 null 结尾的 UTF-8 字符串，
+/// 并且在返回的 `&str` 使用期间底层内存保持有效
 ///
 /// # 示例
 ///
 /// ```rust,ignore
 /// let rust_str = unsafe { cstr_to_str(c_ptr)? };
 /// ```
+#[must_use = "cstr_to_str 只做转换，忽略结果没有意义"]
 pub unsafe fn cstr_to_str<'a>(ptr: *const c_char) -> Result<&'a str, FfiError> {
     if ptr.is_null() {
         return Err(FfiError::NullPointer);
     }
+    #[cfg(debug_assertions)]
+    crate::stack_check::debug_check_stack_pointer(ptr);
     CStr::from_ptr(ptr)
         .to_str()
         .map_err(|_| FfiError::InvalidUtf8)
 }
 
+/// 在闭包内借用 C 字符串
+///
+/// `&str` 只在闭包内有效，无法逃逸到闭包之外，避免了 `cstr_to_str` 返回值
+/// 比底层内存活得更久的问题：
+///
+/// ```compile_fail
+/// # use std::ffi::CString;
+/// let owner = CString::new("hello").unwrap();
+/// let escaped: &str = unsafe { vimo_ffi::cstr_borrow(owner.as_ptr(), |s| s) }.unwrap();
+/// ```
+///
+/// # Safety
+/// 调用者必须确保指针有效且指向以 null 结尾的 UTF-8 字符串
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// let len = unsafe { cstr_borrow(c_ptr, |s| s.len())? };
+/// ```
+pub unsafe fn cstr_borrow<F, R>(ptr: *const c_char, f: F) -> Result<R, FfiError>
+where
+    F: FnOnce(&str) -> R,
+{
+    cstr_to_str(ptr).map(f)
+}
+
+/// 在编译期把以 NUL 结尾的字节串字面量转换为 `&'static str`，结果不含结尾的 NUL
+///
+/// 静态数据不需要 unsafe，也不需要运行时检查：字节串不以 NUL 结尾、中间含 NUL
+/// 或不是有效 UTF-8 时，常量求值失败（编译错误）；在运行时调用则 panic。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// const GLOBAL_LABEL: &str = cstr_to_str_literal(b"my_label\0");
+/// ```
+pub const fn cstr_to_str_literal(bytes: &'static [u8]) -> &'static str {
+    let Some((&0, content)) = bytes.split_last() else {
+        panic!("cstr_to_str_literal: missing trailing NUL");
+    };
+    let mut i = 0;
+    while i < content.len() {
+        if content[i] == 0 {
+            panic!("cstr_to_str_literal: interior NUL");
+        }
+        i += 1;
+    }
+    match std::str::from_utf8(content) {
+        Ok(s) => s,
+        Err(_) => panic!("cstr_to_str_literal: invalid UTF-8"),
+    }
+}
+
 /// 将 C 字符串指针转换为 Rust String
 ///
 /// # Safety
@@ -31,26 +113,287 @@ pub unsafe fn cstr_to_string(ptr: *const c_char) -> Result<String, FfiError> {
     cstr_to_str(ptr).map(|s| s.to_string())
 }
 
+/// 将 C 字符串指针转换为 `Cow<str>`，非法 UTF-8 替换为 U+FFFD
+///
+/// 合法的 UTF-8（常见情况）借用原内存，不分配；需要修复时才分配新的 `String`。
+/// 只会因 null 指针失败。
+///
+/// # Safety
+/// 调用者必须确保指针有效且指向以 null 结尾的字符串，
+/// 并且在返回的 `Cow::Borrowed` 使用期间底层内存保持有效
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// let name = unsafe { cstr_to_cow(c_name)? };
+/// ```
+pub unsafe fn cstr_to_cow<'a>(ptr: *const c_char) -> Result<Cow<'a, str>, FfiError> {
+    if ptr.is_null() {
+        return Err(FfiError::NullPointer);
+    }
+    #[cfg(debug_assertions)]
+    crate::stack_check::debug_check_stack_pointer(ptr);
+    Ok(CStr::from_ptr(ptr).to_string_lossy())
+}
+
 /// 将 Rust 字符串转换为 C 字符串（堆分配）
 ///
+/// 超过全局上限（见 `set_max_cstring_len`）时返回 `FfiError::Custom("string too long")`，
+/// 不分配内存。
 /// 返回的指针必须由调用者释放（使用 `free_cstring`）
 pub fn str_to_cstring(s: &str) -> Result<*mut c_char, FfiError> {
+    str_to_cstring_max(s, max_cstring_len())
+}
+
+/// 同 `str_to_cstring`，`s` 超过 `max_bytes` 字节时返回 `FfiError::Custom("string too long")`
+///
+/// 用于长度来自不可信输入的场景，避免意外的超大分配。
+pub fn str_to_cstring_max(s: &str, max_bytes: usize) -> Result<*mut c_char, FfiError> {
+    check_cstring_len(s.len(), max_bytes)?;
     CString::new(s)
         .map(|cs| cs.into_raw())
         .map_err(|_| FfiError::StringContainsNull)
 }
 
+/// 生成 C 字符串前的长度检查，超过上限时返回 `FfiError::Custom("string too long")`
+fn check_cstring_len(len: usize, max_bytes: usize) -> Result<(), FfiError> {
+    if len > max_bytes {
+        return Err(FfiError::Custom("string too long".into()));
+    }
+    Ok(())
+}
+
+/// 分段拼接 C 字符串，直接写入最终的字节缓冲区，省去中间的 `String`
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// let mut msg = CStringBuilder::new();
+/// msg.push_str("open failed: ").push_fmt(format_args!("{} (errno {})", path, errno));
+/// unsafe { msg.push_cstr(detail)? };
+/// let ptr = msg.finish()?; // 使用 vimo_ffi_free_string 释放
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CStringBuilder(Vec<u8>);
+
+impl CStringBuilder {
+    /// 创建空的构建器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加字符串
+    pub fn push_str(&mut self, s: &str) -> &mut Self {
+        self.0.extend_from_slice(s.as_bytes());
+        self
+    }
+
+    /// 以 `format_args!` 的结果追加，参数直接格式化进缓冲区
+    ///
+    /// 某个参数的 `Display` 实现报错时，保留已经写入的部分。
+    pub fn push_fmt(&mut self, args: std::fmt::Arguments<'_>) -> &mut Self {
+        let _ = std::fmt::Write::write_fmt(self, args);
+        self
+    }
+
+    /// 追加 C 字符串（不含结尾 NUL）
+    ///
+    /// `ptr` 为 null 时返回 `FfiError::NullPointer`，不是有效 UTF-8 时返回
+    /// `FfiError::InvalidUtf8`，两种情况都不修改缓冲区。
+    ///
+    /// # Safety
+    /// 同 `cstr_to_str`
+    pub unsafe fn push_cstr(&mut self, ptr: *const c_char) -> Result<&mut Self, FfiError> {
+        let s = cstr_to_str(ptr)?;
+        Ok(self.push_str(s))
+    }
+
+    /// 结束拼接，返回堆分配的 C 字符串
+    ///
+    /// 与 `str_to_cstring` 相同：超过全局上限时返回 `FfiError::Custom("string too long")`，
+    /// 含有 NUL 时返回 `FfiError::StringContainsNull`。
+    /// 返回的指针必须由调用者使用 `vimo_ffi_free_string` 释放。
+    pub fn finish(self) -> Result<*mut c_char, FfiError> {
+        check_cstring_len(self.0.len(), max_cstring_len())?;
+        CString::new(self.0)
+            .map(CString::into_raw)
+            .map_err(|_| FfiError::StringContainsNull)
+    }
+}
+
+impl std::fmt::Write for CStringBuilder {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+/// 将 `uint8_t*` / `unsigned char*` 字符串转换为 Rust &str
+///
+/// 同 `cstr_to_str`，省去调用方的指针类型转换。
+///
+/// # Safety
+/// 同 `cstr_to_str`
+pub unsafe fn u8ptr_to_str<'a>(ptr: *const u8) -> Result<&'a str, FfiError> {
+    cstr_to_str(ptr as *const c_char)
+}
+
+/// 将带长度的 `uint8_t*` 缓冲区转换为 Rust &str
+///
+/// 恰好读取 `len` 字节，不要求也不查找结尾 NUL（如 OpenSSL 的 `data, len` 参数）。
+///
+/// # Safety
+/// 调用者必须确保 `ptr` 非 null 时至少 `len` 字节可读，
+/// 并且在返回的 `&str` 使用期间底层内存保持有效
+pub unsafe fn u8ptr_to_str_n<'a>(ptr: *const u8, len: usize) -> Result<&'a str, FfiError> {
+    if ptr.is_null() {
+        return Err(FfiError::NullPointer);
+    }
+    std::str::from_utf8(std::slice::from_raw_parts(ptr, len)).map_err(|_| FfiError::InvalidUtf8)
+}
+
+/// 将 Rust 字符串转换为 `uint8_t*` 字符串（堆分配）
+///
+/// 同 `str_to_cstring`，返回的指针必须由调用者释放（使用 `vimo_ffi_free_string`）。
+pub fn str_to_u8ptr(s: &str) -> Result<*mut u8, FfiError> {
+    str_to_cstring(s).map(|ptr| ptr as *mut u8)
+}
+
 /// 释放由本库分配的 C 字符串
 ///
+/// 分配失败时写出的静态 `OOM_MESSAGE` 会被识别并跳过。静态消息的指针不带标记位，
+/// 调用方需要区分时使用 `vimo_ffi_is_static_error`。
+///
 /// # Safety
 /// 指针必须是由 `str_to_cstring` 或类似函数返回的
 #[no_mangle]
 pub unsafe extern "C" fn vimo_ffi_free_string(ptr: *mut c_char) {
-    if !ptr.is_null() {
+    if !ptr.is_null() && ptr != crate::error::oom_message_ptr() {
         let _ = CString::from_raw(ptr);
     }
 }
 
+/// 复制 C 字符串，最多复制 `max_len` 字节（不含结尾 NUL），行为同 POSIX `strndup`
+///
+/// 只读取到第一个 NUL 或第 `max_len` 个字节为止，因此 `ptr` 不要求以 NUL 结尾。
+/// 按字节截断，不保证结果是完整的 UTF-8。`ptr` 为 null 时返回 null。
+/// 返回的指针由本库分配，必须使用 `vimo_ffi_free_string` 释放，不能使用 C 的 `free`。
+///
+/// # Safety
+/// `ptr` 为 null，或者从 `ptr` 起至少 `max_len` 字节（或到第一个 NUL 为止）均可读
+#[no_mangle]
+pub unsafe extern "C" fn vimo_ffi_strndup(ptr: *const c_char, max_len: usize) -> *mut c_char {
+    if ptr.is_null() {
+        return std::ptr::null_mut();
+    }
+    let mut len = 0;
+    while len < max_len && *ptr.add(len) != 0 {
+        len += 1;
+    }
+    let bytes = std::slice::from_raw_parts(ptr as *const u8, len);
+    // bytes 中不含 NUL，不会失败
+    CString::new(bytes).map_or(std::ptr::null_mut(), CString::into_raw)
+}
+
+/// 复制 C 字符串，行为同 POSIX `strdup`
+///
+/// `ptr` 为 null 时返回 null。返回的指针必须使用 `vimo_ffi_free_string` 释放。
+///
+/// # Safety
+/// `ptr` 为 null，或指向有效的以 NUL 结尾的字符串
+#[no_mangle]
+pub unsafe extern "C" fn vimo_ffi_strdup(ptr: *const c_char) -> *mut c_char {
+    if ptr.is_null() {
+        return std::ptr::null_mut();
+    }
+    CStr::from_ptr(ptr).to_owned().into_raw()
+}
+
+/// 释放由本库分配的 `char**` 字符串数组（连同其中的字符串）
+///
+/// # Safety
+/// `ptr` 必须是由 `set_errors` 等函数写出的数组，`count` 必须是同时写出的元素个数；
+/// `ptr` 为 null 时不做任何事
+#[no_mangle]
+pub unsafe extern "C" fn vimo_ffi_free_string_array(ptr: *mut *mut c_char, count: usize) {
+    if ptr.is_null() {
+        return;
+    }
+    let array = Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, count));
+    for &s in array.iter() {
+        vimo_ffi_free_string(s);
+    }
+}
+
+/// 将 C 字符串转换为 &str，并去掉开头的 UTF-8 BOM（`EF BB BF`）
+///
+/// 部分 Windows API（如 COM 字符串输出）会在 UTF-8 文本前加 BOM。
+/// 只去掉开头的一个 BOM，不含 BOM 的字符串原样返回。
+///
+/// # Safety
+/// 同 `cstr_to_str`
+pub unsafe fn cstr_to_str_strip_bom<'a>(ptr: *const c_char) -> Result<&'a str, FfiError> {
+    let s = cstr_to_str(ptr)?;
+    Ok(s.strip_prefix('\u{feff}').unwrap_or(s))
+}
+
+/// `cstr_to_str_encoding` 支持的输入编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8，非法序列返回 `FfiError::InvalidUtf8`
+    Utf8,
+    /// ISO-8859-1，每个字节就是对应的 Unicode 码点
+    Latin1,
+    /// Windows-1252，`0x80..=0x9F` 按 Windows 映射表转换，其余同 Latin-1
+    Cp1252,
+}
+
+/// Windows-1252 中 `0x80..=0x9F` 对应的字符
+///
+/// 未定义的 `0x81`、`0x8D`、`0x8F`、`0x90`、`0x9D` 按 WHATWG 的做法映射为同值的 C1 控制字符。
+const CP1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// 按指定编码将 C 字符串转换为 Rust 字符串
+///
+/// 用于包装输出 Latin-1 / Windows-1252 文本的旧 API，无需引入完整的编码库。
+/// UTF-8 输入以及只含 ASCII 的输入借用原字节，不分配；其余按字节查表转换。
+/// 单字节编码的每个字节都有对应字符，因此 `Latin1` 和 `Cp1252` 只会因 null 指针失败。
+///
+/// # Safety
+/// 同 `cstr_to_str`
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// // "caf\xe9" → "café"
+/// let name = unsafe { cstr_to_str_encoding(legacy_name, Encoding::Latin1)? };
+/// ```
+pub unsafe fn cstr_to_str_encoding<'a>(
+    ptr: *const c_char,
+    encoding: Encoding,
+) -> Result<Cow<'a, str>, FfiError> {
+    if ptr.is_null() {
+        return Err(FfiError::NullPointer);
+    }
+    let bytes = CStr::from_ptr(ptr).to_bytes();
+    if encoding == Encoding::Utf8 || bytes.is_ascii() {
+        return std::str::from_utf8(bytes).map(Cow::Borrowed).map_err(|_| FfiError::InvalidUtf8);
+    }
+    let decoded = bytes
+        .iter()
+        .map(|&b| match (encoding, b) {
+            (Encoding::Cp1252, 0x80..=0x9F) => CP1252_HIGH[usize::from(b - 0x80)],
+            _ => char::from(b),
+        })
+        .collect();
+    Ok(Cow::Owned(decoded))
+}
+
 /// 可选的 C 字符串转换 - null 返回 None
 ///
 /// # Safety
@@ -67,7 +410,7 @@ pub unsafe fn cstr_to_option_str<'a>(ptr: *const c_char) -> Result<Option<&'a st
 ///
 /// # Safety
 /// 如果指针非 null，必须指向有效的 UTF-8 字符串
-pub unsafe fn cstr_to_str_or<'a>(ptr: *const c_char, default: &'a str) -> &'a str {
+pub unsafe fn cstr_to_str_or(ptr: *const c_char, default: &str) -> &str {
     if ptr.is_null() {
         default
     } else {
@@ -75,10 +418,430 @@ pub unsafe fn cstr_to_str_or<'a>(ptr: *const c_char, default: &'a str) -> &'a st
     }
 }
 
+/// 将 C 字符串解析为 `f64`
+///
+/// 按 `str::parse` 的规则解析：不去除首尾空白，接受 `"NaN"`、`"inf"` 等特殊值
+/// （需要有限值时配合 `check_range` 使用）。
+/// 解析失败返回 `FfiError::Custom("parse error: …")`。
+///
+/// # Safety
+/// 同 `cstr_to_str`
+pub unsafe fn cstr_to_f64(ptr: *const c_char) -> Result<f64, FfiError> {
+    cstr_to_str(ptr)?.parse().map_err(parse_error)
+}
+
+/// 将 C 字符串解析为 `i64`
+///
+/// 不去除首尾空白，溢出或格式错误返回 `FfiError::Custom("parse error: …")`。
+///
+/// # Safety
+/// 同 `cstr_to_str`
+pub unsafe fn cstr_to_i64(ptr: *const c_char) -> Result<i64, FfiError> {
+    cstr_to_str(ptr)?.parse().map_err(parse_error)
+}
+
+/// 将 C 字符串解析为 `u64`
+///
+/// 不去除首尾空白，负数、溢出或格式错误返回 `FfiError::Custom("parse error: …")`。
+///
+/// # Safety
+/// 同 `cstr_to_str`
+pub unsafe fn cstr_to_u64(ptr: *const c_char) -> Result<u64, FfiError> {
+    cstr_to_str(ptr)?.parse().map_err(parse_error)
+}
+
+/// 将 C 字符串解析为 `bool`
+///
+/// 只接受 `"true"`、`"false"`、`"1"`、`"0"`（区分大小写），
+/// 其余返回 `FfiError::Custom("parse error: …")`。
+///
+/// # Safety
+/// 同 `cstr_to_str`
+pub unsafe fn cstr_to_bool(ptr: *const c_char) -> Result<bool, FfiError> {
+    match cstr_to_str(ptr)? {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        other => Err(parse_error(format_args!(
+            "invalid bool {other:?}, expected true, false, 1 or 0"
+        ))),
+    }
+}
+
+/// 数值和布尔解析函数的统一错误：`FfiError::Custom("parse error: …")`
+fn parse_error(err: impl std::fmt::Display) -> FfiError {
+    FfiError::Custom(format!("parse error: {err}"))
+}
+
+/// 将 C 字符串解析为 `Duration`
+///
+/// 接受两种写法：
+/// - 数值加单位：`"5s"`、`"100ms"`、`"250us"`、`"10ns"`，数值可以带小数（`"1.5s"`）
+/// - ISO 8601：`"PT1H30M"`、`"PT0.5S"`、`"P1DT12H"`，支持 `D`、`H`、`M`、`S`
+///   （月和年长度不固定，不支持）
+///
+/// 精度为纳秒，更小的小数位被截断。格式错误返回 `FfiError::Parse`，
+/// 超出 `Duration` 范围返回 `FfiError::OutOfRange`。
+///
+/// # Safety
+/// 同 `cstr_to_str`
+pub unsafe fn cstr_to_duration(ptr: *const c_char) -> Result<Duration, FfiError> {
+    let s = cstr_to_str(ptr)?;
+    let nanos = match s.strip_prefix('P') {
+        Some(iso) => iso_duration_nanos(iso),
+        None => unit_duration_nanos(s),
+    };
+    let out_of_range = || FfiError::OutOfRange(format!("duration out of range: {s:?}"));
+    match nanos {
+        Ok(nanos) => {
+            let secs = u64::try_from(nanos / NANOS_PER_SEC).map_err(|_| out_of_range())?;
+            Ok(Duration::new(secs, (nanos % NANOS_PER_SEC) as u32))
+        }
+        Err(DurationError::Overflow) => Err(out_of_range()),
+        Err(DurationError::Invalid) => Err(FfiError::Parse(format!(
+            "parse error: invalid duration {s:?}, expected e.g. 5s, 100ms or PT1H30M"
+        ))),
+    }
+}
+
+/// 将 `Duration` 格式化为 `"{N}s"` 形式的 C 字符串
+///
+/// 整秒输出为 `"90s"`，否则带上去掉末尾 0 的小数部分（`"0.25s"`），
+/// 结果可以由 `cstr_to_duration` 无损解析回来。
+/// 返回的指针必须由调用者释放（使用 `vimo_ffi_free_string`）。
+pub fn duration_to_cstring(d: Duration) -> Result<*mut c_char, FfiError> {
+    let text = match d.subsec_nanos() {
+        0 => format!("{}s", d.as_secs()),
+        nanos => {
+            let frac = format!("{nanos:09}");
+            format!("{}.{}s", d.as_secs(), frac.trim_end_matches('0'))
+        }
+    };
+    str_to_cstring(&text)
+}
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+enum DurationError {
+    Invalid,
+    Overflow,
+}
+
+/// `"5s"`、`"100ms"` 等写法的纳秒数
+fn unit_duration_nanos(s: &str) -> Result<u128, DurationError> {
+    // "s" 放在最后，避免 "ms" 等被当作秒
+    let (number, unit) = [("ms", 1_000_000), ("us", 1_000), ("ns", 1), ("s", NANOS_PER_SEC)]
+        .into_iter()
+        .find_map(|(suffix, unit)| s.strip_suffix(suffix).map(|number| (number, unit)))
+        .ok_or(DurationError::Invalid)?;
+    decimal_nanos(number, unit)
+}
+
+/// ISO 8601 时长（已去掉开头的 `P`）的纳秒数
+fn iso_duration_nanos(s: &str) -> Result<u128, DurationError> {
+    let (date, time) = match s.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (s, None),
+    };
+    // "P" 和 "PT" 都没有任何分量
+    if time.map_or(date.is_empty(), str::is_empty) {
+        return Err(DurationError::Invalid);
+    }
+    let days = iso_components(date, &[('D', 86_400 * NANOS_PER_SEC)])?;
+    let units = [('H', 3_600 * NANOS_PER_SEC), ('M', 60 * NANOS_PER_SEC), ('S', NANOS_PER_SEC)];
+    let time = iso_components(time.unwrap_or_default(), &units)?;
+    days.checked_add(time).ok_or(DurationError::Overflow)
+}
+
+/// 按 `units` 的顺序依次解析 `数值+标识符`，每个标识符最多出现一次
+fn iso_components(mut s: &str, mut units: &[(char, u128)]) -> Result<u128, DurationError> {
+    let mut total: u128 = 0;
+    while !s.is_empty() {
+        let end = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or(DurationError::Invalid)?;
+        let designator = s[end..].chars().next().ok_or(DurationError::Invalid)?;
+        let index = units
+            .iter()
+            .position(|&(d, _)| d == designator)
+            .ok_or(DurationError::Invalid)?;
+        let nanos = decimal_nanos(&s[..end], units[index].1)?;
+        total = total.checked_add(nanos).ok_or(DurationError::Overflow)?;
+        units = &units[index + 1..];
+        s = &s[end + designator.len_utf8()..];
+    }
+    Ok(total)
+}
+
+/// `"12"`、`"1.5"` 等十进制数乘以 `unit` 纳秒，小数部分截断到纳秒
+fn decimal_nanos(number: &str, unit: u128) -> Result<u128, DurationError> {
+    let (int, frac) = number.split_once('.').unwrap_or((number, "0"));
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(int) || !is_digits(frac) {
+        return Err(DurationError::Invalid);
+    }
+    // 只含数字时解析失败只可能是溢出
+    let int: u128 = int.parse().map_err(|_| DurationError::Overflow)?;
+    let mut nanos = int.checked_mul(unit).ok_or(DurationError::Overflow)?;
+    let mut scale = unit;
+    for digit in frac.bytes() {
+        scale /= 10;
+        nanos = nanos
+            .checked_add(u128::from(digit - b'0') * scale)
+            .ok_or(DurationError::Overflow)?;
+    }
+    Ok(nanos)
+}
+
+/// 将 C 字符串转换为只含可打印 ASCII（`0x20..=0x7E`）的 &str
+///
+/// 用于认证令牌、API key 等安全敏感的参数：控制字符和非 ASCII 字节返回
+/// `FfiError::Custom("non-printable character at offset N")`，`N` 为字节偏移。
+///
+/// # Safety
+/// 同 `cstr_to_str`
+pub unsafe fn cstr_to_ascii_printable<'a>(ptr: *const c_char) -> Result<&'a str, FfiError> {
+    let s = cstr_to_str(ptr)?;
+    match s.bytes().position(|b| !(0x20..=0x7E).contains(&b)) {
+        Some(offset) => Err(FfiError::custom(format!(
+            "non-printable character at offset {offset}"
+        ))),
+        None => Ok(s),
+    }
+}
+
+/// 将 C 字符串转换为标识符（非空，只含 `[A-Za-z0-9_-]`）
+///
+/// 先按 `cstr_to_ascii_printable` 检查，其余不允许的字符返回
+/// `FfiError::Custom("invalid identifier character at offset N")`，空字符串返回
+/// `FfiError::Custom("empty identifier")`。
+///
+/// # Safety
+/// 同 `cstr_to_str`
+pub unsafe fn cstr_to_identifier<'a>(ptr: *const c_char) -> Result<&'a str, FfiError> {
+    let s = cstr_to_ascii_printable(ptr)?;
+    if s.is_empty() {
+        return Err(FfiError::custom("empty identifier"));
+    }
+    match s.bytes().position(|b| !(b.is_ascii_alphanumeric() || b == b'_' || b == b'-')) {
+        Some(offset) => Err(FfiError::custom(format!(
+            "invalid identifier character at offset {offset}"
+        ))),
+        None => Ok(s),
+    }
+}
+
+/// 将 C 字符串指针转换为 `Path`
+///
+/// 返回的是借用而非分配的 `Path`。Unix 上路径是任意字节，原样接受；
+/// 其它平台上路径必须是 UTF-8（Windows 的 `OsStr` 内部是 WTF-8，只有 UTF-8 字节
+/// 能零拷贝地视为路径），否则返回 `FfiError::InvalidUtf8`。
+///
+/// # Safety
+/// 同 `cstr_to_str`（Unix 上不要求 UTF-8）
+pub unsafe fn cstr_to_path<'a>(ptr: *const c_char) -> Result<&'a Path, FfiError> {
+    #[cfg(unix)]
+    {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        if ptr.is_null() {
+            return Err(FfiError::NullPointer);
+        }
+        #[cfg(debug_assertions)]
+        crate::stack_check::debug_check_stack_pointer(ptr);
+        Ok(Path::new(OsStr::from_bytes(CStr::from_ptr(ptr).to_bytes())))
+    }
+    #[cfg(not(unix))]
+    cstr_to_str(ptr).map(Path::new)
+}
+
+/// 将 `Path` 转换为 C 字符串（堆分配）
+///
+/// 非 UTF-8 路径（Unix 上的任意字节、Windows 上的孤立代理项）
+/// 返回 `FfiError::InvalidUtf8`。返回的指针必须由调用者释放（使用 `vimo_ffi_free_string`）。
+pub fn path_to_cstring(p: &Path) -> Result<*mut c_char, FfiError> {
+    let s = p.to_str().ok_or(FfiError::InvalidUtf8)?;
+    str_to_cstring(s)
+}
+
+/// 计算以 null 结尾的 `char**` 数组长度（不含结尾的 null）
+///
+/// 只遍历外层指针，不读取字符串内容。`ptr` 为 null 视为空数组。
+///
+/// # Safety
+/// `ptr` 为 null，或指向以 null 指针结尾的指针数组。
+/// 输入来源不可信时使用 `cstr_array_len_max`，避免在缺少结尾 null 时越界读取。
+pub unsafe fn cstr_array_len(ptr: *const *const c_char) -> usize {
+    if ptr.is_null() {
+        return 0;
+    }
+    let mut len = 0;
+    while !(*ptr.add(len)).is_null() {
+        len += 1;
+    }
+    len
+}
+
+/// 同 `cstr_array_len`，但最多检查 `max` 个元素
+///
+/// 前 `max` 个元素中没有找到结尾的 null 时返回 `FfiError::Custom("array too long")`。
+///
+/// # Safety
+/// `ptr` 为 null，或指向至少 `min(实际长度 + 1, max)` 个可读的指针
+pub unsafe fn cstr_array_len_max(ptr: *const *const c_char, max: usize) -> Result<usize, FfiError> {
+    if ptr.is_null() {
+        return Ok(0);
+    }
+    (0..max)
+        .find(|&i| (*ptr.add(i)).is_null())
+        .ok_or_else(|| FfiError::Custom("array too long".to_string()))
+}
+
+/// 按字节截断字符串，且不拆开多字节字符
+pub(crate) fn truncate_at_char_boundary(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+    let mut end = max_len;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// 转换为 C 字符串，内部的 NUL 替换为字面量 `\0`
+///
+/// 用于错误消息：消息可能嵌入了文件中的原始字节或 panic 负载，
+/// 直接丢弃会让调用方拿到失败却没有任何说明。
+pub(crate) fn sanitized_cstring(s: &str) -> CString {
+    let escaped;
+    let s = if s.contains('\0') {
+        escaped = s.replace('\0', "\\0");
+        &escaped
+    } else {
+        s
+    };
+    // 已经不含 NUL，不会失败
+    CString::new(s).unwrap_or_default()
+}
+
+/// 同 `sanitized_cstring`，但内存分配失败时返回 None 而不是终止进程
+pub(crate) fn try_sanitized_cstring(s: &str) -> Option<CString> {
+    let nuls = s.bytes().filter(|&b| b == 0).count();
+    let mut bytes = Vec::new();
+    bytes.try_reserve_exact(s.len() + nuls + 1).ok()?;
+    for b in s.bytes() {
+        match b {
+            0 => bytes.extend_from_slice(b"\\0"),
+            b => bytes.push(b),
+        }
+    }
+    bytes.push(0);
+    CString::from_vec_with_nul(bytes).ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_sanitized_cstring() {
+        assert_eq!(sanitized_cstring("plain").as_bytes(), b"plain");
+        assert_eq!(sanitized_cstring("a\0b\0").as_bytes(), b"a\\0b\\0");
+        assert_eq!(try_sanitized_cstring("a\0b").unwrap().as_bytes(), b"a\\0b");
+    }
+
+    #[test]
+    fn test_cstr_to_str_encoding() {
+        let decode = |bytes: &[u8], encoding| {
+            let c = CString::new(bytes).unwrap();
+            unsafe { cstr_to_str_encoding(c.as_ptr(), encoding) }.map(Cow::into_owned)
+        };
+        assert_eq!(decode(b"caf\xe9", Encoding::Latin1).unwrap(), "café");
+        assert_eq!(decode(b"caf\xe9", Encoding::Cp1252).unwrap(), "café");
+        assert_eq!(decode(b"caf\xe9", Encoding::Utf8), Err(FfiError::InvalidUtf8));
+        assert_eq!(decode("café".as_bytes(), Encoding::Utf8).unwrap(), "café");
+
+        // 0x80..=0x9F：Latin-1 是 C1 控制字符，Windows-1252 是印刷字符
+        assert_eq!(decode(b"\x80\x93\x9f", Encoding::Latin1).unwrap(), "\u{80}\u{93}\u{9f}");
+        assert_eq!(decode(b"\x80\x93\x96\x9f", Encoding::Cp1252).unwrap(), "€“–Ÿ");
+        assert_eq!(decode(b"\x81\x9d", Encoding::Cp1252).unwrap(), "\u{81}\u{9d}");
+        assert_eq!(decode(b"\xff", Encoding::Cp1252).unwrap(), "ÿ");
+
+        let result = unsafe { cstr_to_str_encoding(std::ptr::null(), Encoding::Latin1) };
+        assert_eq!(result, Err(FfiError::NullPointer));
+    }
+
+    #[test]
+    fn test_cstr_to_str_encoding_ascii_borrows() {
+        let c = CString::new("plain ascii").unwrap();
+        for encoding in [Encoding::Utf8, Encoding::Latin1, Encoding::Cp1252] {
+            let s = unsafe { cstr_to_str_encoding(c.as_ptr(), encoding) }.unwrap();
+            assert!(matches!(s, Cow::Borrowed("plain ascii")));
+        }
+    }
+
+    #[test]
+    fn test_cstr_to_str_strip_bom() {
+        let with_bom = CString::new("\u{feff}\u{feff}title").unwrap();
+        assert_eq!(unsafe { cstr_to_str_strip_bom(with_bom.as_ptr()) }, Ok("\u{feff}title"));
+
+        let plain = CString::new("title").unwrap();
+        assert_eq!(unsafe { cstr_to_str_strip_bom(plain.as_ptr()) }, Ok("title"));
+        assert_eq!(
+            unsafe { cstr_to_str_strip_bom(std::ptr::null()) },
+            Err(FfiError::NullPointer)
+        );
+    }
+
+    #[test]
+    fn test_u8ptr_conversions() {
+        let cs = CString::new("hello").unwrap();
+        let ptr = cs.as_ptr() as *const u8;
+        assert_eq!(unsafe { u8ptr_to_str(ptr) }, Ok("hello"));
+        assert_eq!(unsafe { u8ptr_to_str_n(ptr, 4) }, Ok("hell"));
+        assert_eq!(unsafe { u8ptr_to_str(std::ptr::null()) }, Err(FfiError::NullPointer));
+        assert_eq!(unsafe { u8ptr_to_str_n(std::ptr::null(), 0) }, Err(FfiError::NullPointer));
+
+        let bad = [b'a', 0xff];
+        assert_eq!(unsafe { u8ptr_to_str_n(bad.as_ptr(), 2) }, Err(FfiError::InvalidUtf8));
+
+        let owned = str_to_u8ptr("world").unwrap();
+        assert_eq!(unsafe { u8ptr_to_str(owned) }, Ok("world"));
+        unsafe { vimo_ffi_free_string(owned as *mut c_char) };
+    }
+
+    #[test]
+    fn test_strndup() {
+        let cs = CString::new("hello").unwrap();
+        let copy = unsafe { vimo_ffi_strndup(cs.as_ptr(), 3) };
+        assert_eq!(unsafe { CStr::from_ptr(copy) }.to_bytes(), b"hel");
+        unsafe { vimo_ffi_free_string(copy) };
+
+        let copy = unsafe { vimo_ffi_strndup(cs.as_ptr(), 100) };
+        assert_eq!(unsafe { CStr::from_ptr(copy) }.to_bytes(), b"hello");
+        unsafe { vimo_ffi_free_string(copy) };
+
+        // 未以 NUL 结尾的缓冲区，只读取 max_len 字节
+        let raw = *b"abcd";
+        let copy = unsafe { vimo_ffi_strndup(raw.as_ptr() as *const c_char, raw.len()) };
+        assert_eq!(unsafe { CStr::from_ptr(copy) }.to_bytes(), b"abcd");
+        unsafe { vimo_ffi_free_string(copy) };
+
+        assert!(unsafe { vimo_ffi_strndup(std::ptr::null(), 5) }.is_null());
+    }
+
+    #[test]
+    fn test_strdup() {
+        let cs = CString::new("hello").unwrap();
+        let copy = unsafe { vimo_ffi_strdup(cs.as_ptr()) };
+        assert_ne!(copy as *const c_char, cs.as_ptr());
+        assert_eq!(unsafe { CStr::from_ptr(copy) }, cs.as_c_str());
+        unsafe { vimo_ffi_free_string(copy) };
+
+        assert!(unsafe { vimo_ffi_strdup(std::ptr::null()) }.is_null());
+    }
+
     #[test]
     fn test_cstr_to_str() {
         let cs = CString::new("hello").unwrap();
@@ -86,12 +849,55 @@ mod tests {
         assert_eq!(result.unwrap(), "hello");
     }
 
+    #[test]
+    fn test_cstr_to_str_literal() {
+        const LABEL: &str = cstr_to_str_literal(b"my_label\0");
+        const UNICODE: &str = cstr_to_str_literal("héllo\0".as_bytes());
+        assert_eq!(LABEL, "my_label");
+        assert_eq!(UNICODE, "héllo");
+        assert_eq!(cstr_to_str_literal(b"\0"), "");
+
+        // 运行时调用时非法输入 panic（常量中则是编译错误）
+        for (bytes, expected) in [
+            (&b"no_nul"[..], "cstr_to_str_literal: missing trailing NUL"),
+            (&b""[..], "cstr_to_str_literal: missing trailing NUL"),
+            (&b"a\0b\0"[..], "cstr_to_str_literal: interior NUL"),
+            (&b"caf\xe9\0"[..], "cstr_to_str_literal: invalid UTF-8"),
+        ] {
+            let payload = std::panic::catch_unwind(|| cstr_to_str_literal(bytes)).unwrap_err();
+            assert_eq!(payload.downcast_ref::<&str>(), Some(&expected));
+        }
+    }
+
+    #[test]
+    fn test_cstr_to_cow() {
+        let clean = CString::new("héllo").unwrap();
+        let s = unsafe { cstr_to_cow(clean.as_ptr()) }.unwrap();
+        assert!(matches!(s, Cow::Borrowed("héllo")));
+
+        let broken = CString::new(b"caf\xe9!".to_vec()).unwrap();
+        let s = unsafe { cstr_to_cow(broken.as_ptr()) }.unwrap();
+        assert!(matches!(&s, Cow::Owned(owned) if owned == "caf\u{fffd}!"));
+
+        assert_eq!(unsafe { cstr_to_cow(std::ptr::null()) }, Err(FfiError::NullPointer));
+    }
+
     #[test]
     fn test_cstr_to_str_null() {
         let result = unsafe { cstr_to_str(std::ptr::null()) };
         assert!(matches!(result, Err(FfiError::NullPointer)));
     }
 
+    #[test]
+    fn test_cstr_borrow() {
+        let cs = CString::new("hello").unwrap();
+        let upper = unsafe { cstr_borrow(cs.as_ptr(), |s| s.to_uppercase()) };
+        assert_eq!(upper.unwrap(), "HELLO");
+
+        let result = unsafe { cstr_borrow(std::ptr::null(), |s| s.len()) };
+        assert_eq!(result, Err(FfiError::NullPointer));
+    }
+
     #[test]
     fn test_str_to_cstring() {
         let ptr = str_to_cstring("hello").unwrap();
@@ -99,6 +905,43 @@ mod tests {
         assert_eq!(back.to_str().unwrap(), "hello");
     }
 
+    #[test]
+    fn test_str_to_cstring_max() {
+        let ptr = str_to_cstring_max("hello", 5).unwrap();
+        assert_eq!(unsafe { CString::from_raw(ptr) }.to_str().unwrap(), "hello");
+
+        let err = str_to_cstring_max("hello!", 5).unwrap_err();
+        assert_eq!(err, FfiError::Custom("string too long".into()));
+        assert_eq!(str_to_cstring_max("a\0b", 5), Err(FfiError::StringContainsNull));
+        // 长度检查先于 NUL 检查
+        assert!(matches!(str_to_cstring_max("a\0bcdef", 5), Err(FfiError::Custom(_))));
+    }
+
+    #[test]
+    fn test_cstring_builder() {
+        let detail = CString::new("permission denied").unwrap();
+        let mut builder = CStringBuilder::new();
+        builder.push_str("open ").push_fmt(format_args!("'{}' (errno {}): ", "a.db", 13));
+        unsafe { builder.push_cstr(detail.as_ptr()) }.unwrap();
+        let ptr = builder.finish().unwrap();
+        let back = unsafe { CString::from_raw(ptr) };
+        assert_eq!(back.to_str().unwrap(), "open 'a.db' (errno 13): permission denied");
+
+        let mut builder = CStringBuilder::new();
+        builder.push_str("kept");
+        let result = unsafe { builder.push_cstr(std::ptr::null()) }.map(|_| ());
+        assert_eq!(result, Err(FfiError::NullPointer));
+        let invalid = CString::new(b"\xff".to_vec()).unwrap();
+        let result = unsafe { builder.push_cstr(invalid.as_ptr()) }.map(|_| ());
+        assert_eq!(result, Err(FfiError::InvalidUtf8));
+        let ptr = builder.finish().unwrap();
+        assert_eq!(unsafe { CString::from_raw(ptr) }.to_str().unwrap(), "kept");
+
+        let mut builder = CStringBuilder::new();
+        builder.push_str("a\0b");
+        assert_eq!(builder.finish(), Err(FfiError::StringContainsNull));
+    }
+
     #[test]
     fn test_cstr_to_option_str() {
         let cs = CString::new("hello").unwrap();
@@ -108,4 +951,236 @@ mod tests {
         let result = unsafe { cstr_to_option_str(std::ptr::null()) };
         assert_eq!(result.unwrap(), None);
     }
+
+    #[test]
+    fn test_path_roundtrip() {
+        for path in ["/usr/local/bin", "relative/dir/file.txt", "/tmp/日本語/ファイル"] {
+            let ptr = path_to_cstring(Path::new(path)).unwrap();
+            let back = unsafe { cstr_to_path(ptr) }.unwrap();
+            assert_eq!(back, Path::new(path));
+            unsafe { vimo_ffi_free_string(ptr) };
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_to_cstring_non_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"/tmp/\xff\xfe"));
+        assert_eq!(path_to_cstring(path), Err(FfiError::InvalidUtf8));
+    }
+
+    fn parse<T>(
+        s: &str,
+        f: unsafe fn(*const c_char) -> Result<T, FfiError>,
+    ) -> Result<T, FfiError> {
+        let c = CString::new(s).unwrap();
+        unsafe { f(c.as_ptr()) }
+    }
+
+    #[test]
+    fn test_cstr_to_f64() {
+        assert_eq!(parse("1.5", cstr_to_f64), Ok(1.5));
+        assert_eq!(parse("-2e3", cstr_to_f64), Ok(-2000.0));
+        assert!(parse("NaN", cstr_to_f64).unwrap().is_nan());
+        assert_eq!(parse("inf", cstr_to_f64), Ok(f64::INFINITY));
+        assert_eq!(
+            parse("", cstr_to_f64),
+            Err(FfiError::Custom("parse error: cannot parse float from empty string".into()))
+        );
+        assert!(matches!(parse("1.5 ", cstr_to_f64), Err(FfiError::Custom(_))));
+        assert!(matches!(parse("nan?", cstr_to_f64), Err(FfiError::Custom(_))));
+        assert_eq!(unsafe { cstr_to_f64(std::ptr::null()) }, Err(FfiError::NullPointer));
+    }
+
+    #[test]
+    fn test_cstr_to_i64() {
+        assert_eq!(parse("-42", cstr_to_i64), Ok(-42));
+        assert_eq!(parse("9223372036854775807", cstr_to_i64), Ok(i64::MAX));
+        assert_eq!(
+            parse("9223372036854775808", cstr_to_i64),
+            Err(FfiError::Custom("parse error: number too large to fit in target type".into()))
+        );
+        assert_eq!(
+            parse("", cstr_to_i64),
+            Err(FfiError::Custom("parse error: cannot parse integer from empty string".into()))
+        );
+        assert!(matches!(parse("42\n", cstr_to_i64), Err(FfiError::Custom(_))));
+        assert!(matches!(parse(" 42", cstr_to_i64), Err(FfiError::Custom(_))));
+    }
+
+    #[test]
+    fn test_cstr_to_u64() {
+        assert_eq!(parse("18446744073709551615", cstr_to_u64), Ok(u64::MAX));
+        assert!(matches!(parse("18446744073709551616", cstr_to_u64), Err(FfiError::Custom(_))));
+        assert!(matches!(parse("-1", cstr_to_u64), Err(FfiError::Custom(_))));
+        assert!(matches!(parse("", cstr_to_u64), Err(FfiError::Custom(_))));
+    }
+
+    #[test]
+    fn test_cstr_to_duration() {
+        let ms = Duration::from_millis;
+        assert_eq!(parse("5s", cstr_to_duration), Ok(Duration::from_secs(5)));
+        assert_eq!(parse("100ms", cstr_to_duration), Ok(ms(100)));
+        assert_eq!(parse("250us", cstr_to_duration), Ok(Duration::from_micros(250)));
+        assert_eq!(parse("10ns", cstr_to_duration), Ok(Duration::from_nanos(10)));
+        assert_eq!(parse("1.5s", cstr_to_duration), Ok(ms(1500)));
+        assert_eq!(parse("0.0000000019s", cstr_to_duration), Ok(Duration::from_nanos(1)));
+
+        assert_eq!(parse("PT1H30M", cstr_to_duration), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse("PT0.5S", cstr_to_duration), Ok(ms(500)));
+        assert_eq!(parse("P1DT12H", cstr_to_duration), Ok(Duration::from_secs(129_600)));
+        assert_eq!(parse("P2D", cstr_to_duration), Ok(Duration::from_secs(172_800)));
+        assert_eq!(parse("PT1M1.25S", cstr_to_duration), Ok(ms(61_250)));
+
+        let invalid = [
+            "", "5", "s", "5 s", "-5s", "+5s", "1.s", ".5s", "5m", "5S", "P", "PT", "P1H", "PT1S1M",
+            "PT1H1H", "P1Y", "PT1.5", "pt1h",
+        ];
+        for bad in invalid {
+            let err = parse(bad, cstr_to_duration).unwrap_err();
+            assert!(matches!(err, FfiError::Parse(_)), "{bad:?}: {err:?}");
+        }
+        assert_eq!(unsafe { cstr_to_duration(std::ptr::null()) }, Err(FfiError::NullPointer));
+    }
+
+    #[test]
+    fn test_cstr_to_duration_overflow() {
+        let max = format!("{}s", u64::MAX);
+        assert_eq!(parse(&max, cstr_to_duration), Ok(Duration::from_secs(u64::MAX)));
+        for big in [
+            format!("{}s", u128::from(u64::MAX) + 1),
+            format!("{}ms", u128::MAX),
+            "1".repeat(60) + "ns",
+            format!("PT{}H", u64::MAX),
+            format!("P{}DT8H", u64::MAX / 86_400),
+        ] {
+            let err = parse(&big, cstr_to_duration).unwrap_err();
+            assert!(matches!(err, FfiError::OutOfRange(_)), "{big:?}: {err:?}");
+        }
+    }
+
+    #[test]
+    fn test_duration_to_cstring() {
+        let format = |d| {
+            let ptr = duration_to_cstring(d).unwrap();
+            unsafe { CString::from_raw(ptr) }.into_string().unwrap()
+        };
+        assert_eq!(format(Duration::from_secs(90)), "90s");
+        assert_eq!(format(Duration::ZERO), "0s");
+        assert_eq!(format(Duration::from_millis(250)), "0.25s");
+        assert_eq!(format(Duration::new(3, 1)), "3.000000001s");
+
+        for d in [Duration::from_micros(1_500_250), Duration::MAX, Duration::from_nanos(7)] {
+            let text = CString::new(format(d)).unwrap();
+            assert_eq!(unsafe { cstr_to_duration(text.as_ptr()) }, Ok(d));
+        }
+    }
+
+    #[test]
+    fn test_cstr_to_bool() {
+        assert_eq!(parse("true", cstr_to_bool), Ok(true));
+        assert_eq!(parse("1", cstr_to_bool), Ok(true));
+        assert_eq!(parse("false", cstr_to_bool), Ok(false));
+        assert_eq!(parse("0", cstr_to_bool), Ok(false));
+        assert_eq!(
+            parse("yes", cstr_to_bool).unwrap_err().to_string(),
+            "parse error: invalid bool \"yes\", expected true, false, 1 or 0"
+        );
+        assert!(matches!(parse("", cstr_to_bool), Err(FfiError::Custom(_))));
+        assert!(parse("True", cstr_to_bool).is_err());
+        assert!(parse("true ", cstr_to_bool).is_err());
+    }
+
+    /// 在 `CString` 释放前复制转换结果
+    fn validate(
+        s: &str,
+        f: unsafe fn(*const c_char) -> Result<&'static str, FfiError>,
+    ) -> Result<String, FfiError> {
+        let c = CString::new(s).unwrap();
+        unsafe { f(c.as_ptr()) }.map(str::to_owned)
+    }
+
+    #[test]
+    fn test_cstr_to_ascii_printable() {
+        let printable = |s: &str| validate(s, cstr_to_ascii_printable);
+        assert_eq!(printable("sk-live_1234 ~!"), Ok("sk-live_1234 ~!".into()));
+        assert_eq!(printable(""), Ok("".into()));
+        assert_eq!(
+            printable("token\tvalue"),
+            Err(FfiError::custom("non-printable character at offset 5"))
+        );
+        assert_eq!(printable("\x7f"), Err(FfiError::custom("non-printable character at offset 0")));
+        // 非 ASCII 字符按字节偏移报告
+        let err = FfiError::custom("non-printable character at offset 3");
+        assert_eq!(printable("abcé"), Err(err));
+        let null = unsafe { cstr_to_ascii_printable(std::ptr::null()) };
+        assert_eq!(null, Err(FfiError::NullPointer));
+    }
+
+    #[test]
+    fn test_cstr_to_identifier() {
+        let identifier = |s: &str| validate(s, cstr_to_identifier);
+        assert_eq!(identifier("user_id-42"), Ok("user_id-42".into()));
+        assert_eq!(
+            identifier("user id"),
+            Err(FfiError::custom("invalid identifier character at offset 4"))
+        );
+        assert_eq!(
+            identifier("a.b"),
+            Err(FfiError::custom("invalid identifier character at offset 1"))
+        );
+        // 不可打印字符优先按 cstr_to_ascii_printable 报告
+        assert_eq!(identifier("a\n"), Err(FfiError::custom("non-printable character at offset 1")));
+        assert_eq!(identifier(""), Err(FfiError::custom("empty identifier")));
+    }
+
+    #[test]
+    fn test_cstr_to_path_null() {
+        let result = unsafe { cstr_to_path(std::ptr::null()) };
+        assert_eq!(result, Err(FfiError::NullPointer));
+    }
+
+    #[test]
+    fn test_cstr_to_path_bytes() {
+        let utf8 = CString::new("/tmp/vimo.db").unwrap();
+        assert_eq!(unsafe { cstr_to_path(utf8.as_ptr()) }, Ok(Path::new("/tmp/vimo.db")));
+
+        // Unix 上路径可以是任意字节
+        let latin1 = CString::new(b"/tmp/caf\xe9".to_vec()).unwrap();
+        let result = unsafe { cstr_to_path(latin1.as_ptr()) };
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            assert_eq!(result.unwrap().as_os_str().as_bytes(), b"/tmp/caf\xe9");
+        }
+        #[cfg(not(unix))]
+        assert_eq!(result, Err(FfiError::InvalidUtf8));
+    }
+
+    #[test]
+    fn test_cstr_array_len() {
+        let a = CString::new("a").unwrap();
+        let b = CString::new("b").unwrap();
+        let array = [a.as_ptr(), b.as_ptr(), std::ptr::null()];
+        assert_eq!(unsafe { cstr_array_len(array.as_ptr()) }, 2);
+        assert_eq!(unsafe { cstr_array_len(std::ptr::null()) }, 0);
+
+        let empty = [std::ptr::null::<c_char>()];
+        assert_eq!(unsafe { cstr_array_len(empty.as_ptr()) }, 0);
+    }
+
+    #[test]
+    fn test_cstr_array_len_max() {
+        let a = CString::new("a").unwrap();
+        let array = [a.as_ptr(), a.as_ptr(), a.as_ptr(), std::ptr::null()];
+        assert_eq!(unsafe { cstr_array_len_max(array.as_ptr(), 4) }, Ok(3));
+        assert_eq!(
+            unsafe { cstr_array_len_max(array.as_ptr(), 3) },
+            Err(FfiError::Custom("array too long".into()))
+        );
+        assert_eq!(unsafe { cstr_array_len_max(std::ptr::null(), 0) }, Ok(0));
+    }
 }
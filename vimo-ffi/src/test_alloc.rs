@@ -0,0 +1,53 @@
+//! 测试用的分配追踪器
+//!
+//! 以全局分配器的形式统计当前线程的存活分配数和累计分配次数，
+//! 用于验证 FFI 内存没有泄漏、热路径没有多余的分配。
+//! 计数是线程局部的，并行运行的其它测试不会互相干扰。
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct TrackingAllocator;
+
+thread_local! {
+    static LIVE: Cell<isize> = const { Cell::new(0) };
+    static TOTAL: Cell<usize> = const { Cell::new(0) };
+}
+
+fn adjust(delta: isize) {
+    // const 初始化且无析构的 thread_local 在线程退出时仍可访问，try_with 只是保险
+    let _ = LIVE.try_with(|live| live.set(live.get() + delta));
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            adjust(1);
+            let _ = TOTAL.try_with(|total| total.set(total.get() + 1));
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        adjust(-1);
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: TrackingAllocator = TrackingAllocator;
+
+/// 当前线程的存活分配数
+pub fn live_allocations() -> isize {
+    LIVE.with(Cell::get)
+}
+
+/// 当前线程累计的分配次数（不计 `realloc`）
+pub fn allocation_count() -> usize {
+    TOTAL.with(Cell::get)
+}
@@ -0,0 +1,114 @@
+//! 报告 panic 的工作线程
+//!
+//! 边界代码中启动的工作线程 panic 时，默认只会在 stderr 打印一行，宿主无从得知原因。
+//! `spawn_ffi_thread` 启动的线程和边界函数一样捕获 panic：通知 panic 处理函数和错误观察者，
+//! 记录到 panic 记录（带线程名）并增加 panic 计数。
+
+use std::any::Any;
+use std::thread::{Builder, JoinHandle, Thread};
+
+use crate::panic::{describe_panic, run_guarded};
+use crate::{FfiError, FfiErrorCode, Severity};
+
+/// 启动命名的工作线程，线程中的 panic 按边界函数的方式报告
+///
+/// 与 `std::thread::spawn` 一样，系统无法创建线程时 panic。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// spawn_ffi_thread("vimo-indexer", move || indexer.run());
+/// ```
+pub fn spawn_ffi_thread<F>(name: &str, f: F) -> JoinHandle<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    spawn_named(name, move || {
+        let _ = run_guarded(f);
+    })
+}
+
+/// 同 `spawn_ffi_thread`，`join` 返回线程的结果，panic 时为 `FfiError`
+///
+/// panic 转换为错误码 `FfiErrorCode::Panic`、严重级别 `Severity::Fatal` 的错误，
+/// 消息与边界函数写出的相同（`"internal panic: ..."`）。
+pub fn spawn_ffi_thread_with_result<T, F>(name: &str, f: F) -> FfiJoinHandle<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let inner = spawn_named(name, move || run_guarded(f).map_err(|panic| panic_error(&panic)));
+    FfiJoinHandle { inner }
+}
+
+/// `spawn_ffi_thread_with_result` 返回的句柄
+#[derive(Debug)]
+pub struct FfiJoinHandle<T> {
+    inner: JoinHandle<Result<T, FfiError>>,
+}
+
+impl<T> FfiJoinHandle<T> {
+    /// 等待线程结束，返回其结果或 panic 转换成的错误
+    pub fn join(self) -> Result<T, FfiError> {
+        // 关闭 catch-unwind 时 panic 没有在线程内被捕获，由 join 取回
+        self.inner.join().unwrap_or_else(|panic| Err(panic_error(&panic)))
+    }
+
+    /// 线程是否已经结束
+    pub fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
+
+    /// 底层线程
+    pub fn thread(&self) -> &Thread {
+        self.inner.thread()
+    }
+}
+
+fn spawn_named<T, F>(name: &str, f: F) -> JoinHandle<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    Builder::new()
+        .name(name.to_owned())
+        .spawn(f)
+        .expect("failed to spawn thread")
+}
+
+fn panic_error(panic: &Box<dyn Any + Send>) -> FfiError {
+    FfiError::CustomCode {
+        code: FfiErrorCode::Panic as i32,
+        message: describe_panic(panic),
+    }
+    .with_severity(Severity::Fatal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawn_ffi_thread_with_result() {
+        let handle = spawn_ffi_thread_with_result("vimo-sum", || (1..=10).sum::<i32>());
+        assert_eq!(handle.thread().name(), Some("vimo-sum"));
+        assert_eq!(handle.join(), Ok(55));
+
+        spawn_ffi_thread("vimo-noop", || {}).join().unwrap();
+    }
+
+    #[test]
+    fn test_spawn_ffi_thread_panic() {
+        let handle = spawn_ffi_thread_with_result("vimo-worker", || -> u32 {
+            panic!("worker {} failed", 7)
+        });
+        let err = handle.join().unwrap_err();
+        assert_eq!(err.code(), FfiErrorCode::Panic as i32);
+        assert_eq!(err.severity(), Severity::Fatal);
+        assert_eq!(err.to_string(), "internal panic: worker 7 failed");
+
+        // 线程内的 panic 不会传播到 join
+        #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+        assert!(spawn_ffi_thread("vimo-panics", || panic!("ignored")).join().is_ok());
+    }
+}
@@ -0,0 +1,35 @@
+//! `tokio` 集成
+//!
+//! `ffi_boundary_async` 在调用线程上阻塞运行 future，绑定 crate 不必各自搭建 `block_on`。
+//! 每个线程惰性创建一个 current-thread 运行时，之后的调用复用它。
+
+use std::cell::RefCell;
+use std::future::Future;
+
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::FfiError;
+
+/// 在 tokio 运行时内调用 `ffi_boundary_async` 时返回的错误消息
+///
+/// 运行时内再 `block_on` 会 panic，单线程运行时上还可能死锁，因此直接报错。
+pub const NESTED_RUNTIME_MESSAGE: &str = "ffi_boundary_async called from within a tokio runtime";
+
+thread_local! {
+    static RUNTIME: RefCell<Option<Runtime>> = const { RefCell::new(None) };
+}
+
+/// 在当前线程的运行时上运行 future，已经处于运行时内或创建运行时失败时返回错误
+pub(crate) fn block_on<F: Future>(fut: F) -> Result<F::Output, FfiError> {
+    if Handle::try_current().is_ok() {
+        return Err(FfiError::custom(NESTED_RUNTIME_MESSAGE));
+    }
+    // 运行期间不持有 RefCell 的借用；future panic 时运行时随展开释放，下次调用重新创建
+    let runtime = match RUNTIME.with(|slot| slot.borrow_mut().take()) {
+        Some(runtime) => runtime,
+        None => Builder::new_current_thread().enable_all().build()?,
+    };
+    let output = runtime.block_on(fut);
+    RUNTIME.with(|slot| *slot.borrow_mut() = Some(runtime));
+    Ok(output)
+}
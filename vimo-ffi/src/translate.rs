@@ -0,0 +1,102 @@
+//! 错误消息本地化钩子
+//!
+//! 宿主直接向最终用户展示错误消息时，可以安装一个翻译函数，
+//! 在消息写出前按错误码替换为本地化文本。未安装、返回 None 或翻译函数 panic 时使用默认英文消息。
+//!
+//! 线程局部的最近一次错误始终记录原始英文消息（仍会经过 `set_message_redactor` 的脱敏），
+//! 便于日志和遥测。
+
+use std::borrow::Cow;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::RwLock;
+
+/// Rust 侧的翻译函数：接收错误码和默认消息，返回 None 表示使用默认消息
+pub type ErrorTranslator = fn(code: i32, default_msg: &str) -> Option<String>;
+
+/// C 侧的翻译函数，见 `vimo_ffi_set_error_translator`
+pub type CErrorTranslator = extern "C" fn(code: i32, default_msg: *const c_char) -> *mut c_char;
+
+#[derive(Clone, Copy)]
+enum Translator {
+    Rust(ErrorTranslator),
+    C(CErrorTranslator),
+}
+
+static TRANSLATOR: RwLock<Option<Translator>> = RwLock::new(None);
+
+fn install(translator: Option<Translator>) {
+    *TRANSLATOR.write().unwrap_or_else(|e| e.into_inner()) = translator;
+}
+
+/// 安装全局翻译函数，对所有线程生效
+///
+/// `set_error`、`set_error_from` 和所有边界函数在写出消息前都会调用它。
+/// `set_error` 只有消息没有错误码，调用时错误码为 `FfiErrorCode::Unknown`。
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// set_error_translator(|code, _| match code {
+///     1 => Some("指针为空".to_string()),
+///     _ => None,
+/// });
+/// ```
+pub fn set_error_translator(translator: ErrorTranslator) {
+    install(Some(Translator::Rust(translator)));
+}
+
+/// 移除全局翻译函数，恢复默认英文消息
+pub fn clear_error_translator() {
+    install(None);
+}
+
+/// 从 C 侧安装全局翻译函数，传入 null 则移除
+///
+/// 回调接收错误码和默认消息（以 NUL 结尾的 UTF-8，仅在回调期间有效），
+/// 返回 null 表示使用默认消息。返回的字符串**必须由本库分配**（如 `vimo_ffi_strdup`），
+/// 所有权随返回值转移给本库，由本库释放；回调不得返回静态字符串或 C `malloc` 分配的内存。
+///
+/// 回调不得跨越 FFI 边界 unwind。
+///
+/// # 示例
+///
+/// ```c
+/// char *translate(int32_t code, const char *msg) {
+///     if (code == 1) return vimo_ffi_strdup("指针为空");
+///     return NULL;
+/// }
+/// vimo_ffi_set_error_translator(translate);
+/// ```
+#[no_mangle]
+pub extern "C" fn vimo_ffi_set_error_translator(translator: Option<CErrorTranslator>) {
+    install(translator.map(Translator::C));
+}
+
+/// 按当前翻译函数翻译消息，没有翻译时借用原消息
+pub(crate) fn translate(code: i32, message: &str) -> Cow<'_, str> {
+    // 先复制出函数指针再调用，翻译函数内部再写错误时不会重入锁
+    let translator = *TRANSLATOR.read().unwrap_or_else(|e| e.into_inner());
+    let Some(translator) = translator else {
+        return Cow::Borrowed(message);
+    };
+    let translated = catch_unwind(AssertUnwindSafe(|| match translator {
+        Translator::Rust(f) => f(code, message),
+        Translator::C(f) => call_c_translator(f, code, message),
+    }));
+    match translated {
+        Ok(Some(translated)) => Cow::Owned(translated),
+        _ => Cow::Borrowed(message),
+    }
+}
+
+fn call_c_translator(f: CErrorTranslator, code: i32, message: &str) -> Option<String> {
+    let default_msg = CString::new(message).ok()?;
+    let translated = f(code, default_msg.as_ptr());
+    if translated.is_null() {
+        return None;
+    }
+    // SAFETY: 按约定，返回值由本库分配且所有权已转移
+    let translated = unsafe { CString::from_raw(translated) };
+    Some(CStr::to_string_lossy(&translated).into_owned())
+}
@@ -0,0 +1,82 @@
+//! `url` 集成
+//!
+//! 通过 FFI 暴露的 HTTP / WebSocket 客户端通常以 C 字符串接收 URL，
+//! 这里在边界处完成解析，闭包内直接拿到 `url::Url`。
+
+use std::ffi::c_char;
+
+use url::Url;
+
+use crate::{cstr_to_str, str_to_cstring, FfiError};
+
+/// 将 C 字符串解析为绝对 URL
+///
+/// 解析失败（包括没有 scheme 的相对 URL）时返回 `FfiError::Custom("invalid URL: ...")`。
+///
+/// # Safety
+/// 同 `cstr_to_str`
+///
+/// # 示例
+///
+/// ```rust,ignore
+/// let url = unsafe { cstr_to_url(endpoint)? };
+/// client.connect(url.host_str().unwrap_or_default(), url.port_or_known_default())?;
+/// ```
+pub unsafe fn cstr_to_url(ptr: *const c_char) -> Result<Url, FfiError> {
+    let s = cstr_to_str(ptr)?;
+    Url::parse(s).map_err(|e| FfiError::Custom(format!("invalid URL: {e}")))
+}
+
+/// 将 URL 转换为 C 字符串（堆分配），输出为规范化后的形式
+///
+/// 返回的指针必须由调用者使用 `vimo_ffi_free_string` 释放。
+pub fn url_to_cstring(u: &Url) -> Result<*mut c_char, FfiError> {
+    str_to_cstring(u.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{vimo_ffi_free_string, FfiErrorCode};
+    use std::ffi::{CStr, CString};
+    use std::ptr;
+
+    fn parse(s: &str) -> Result<Url, FfiError> {
+        let input = CString::new(s).unwrap();
+        unsafe { cstr_to_url(input.as_ptr()) }
+    }
+
+    #[test]
+    fn test_cstr_to_url_absolute() {
+        let url = parse("wss://api.vimo.ai:8443/v1/stream?session=42").unwrap();
+        assert_eq!(url.scheme(), "wss");
+        assert_eq!(url.host_str(), Some("api.vimo.ai"));
+        assert_eq!(url.port(), Some(8443));
+        assert_eq!(url.path(), "/v1/stream");
+        assert_eq!(url.query(), Some("session=42"));
+    }
+
+    #[test]
+    fn test_cstr_to_url_relative_and_malformed() {
+        let err = parse("/v1/stream").unwrap_err();
+        assert_eq!(err.to_string(), "invalid URL: relative URL without a base");
+        assert_eq!(err.code(), FfiErrorCode::Custom as i32);
+
+        let err = parse("http://").unwrap_err();
+        assert_eq!(err.to_string(), "invalid URL: empty host");
+        assert!(parse("https://[::1").is_err());
+
+        assert!(matches!(unsafe { cstr_to_url(ptr::null()) }, Err(FfiError::NullPointer)));
+    }
+
+    #[test]
+    fn test_url_to_cstring_roundtrip() {
+        let url = parse("HTTPS://Example.COM/a b").unwrap();
+        let ptr = url_to_cstring(&url).unwrap();
+        let s = unsafe { CStr::from_ptr(ptr) }.to_str().unwrap().to_owned();
+        assert_eq!(s, "https://example.com/a%20b");
+        let back = unsafe { cstr_to_url(ptr) }.unwrap();
+        assert_eq!(back, url);
+        unsafe { vimo_ffi_free_string(ptr) };
+    }
+}
@@ -0,0 +1,60 @@
+//! 错误详细程度
+//!
+//! 开发时希望跨边界的错误文本带上变体名、错误链等细节，生产环境则只需要简短、
+//! 可以展示给用户的文本。详细程度是进程级的原子设置，宿主可以在运行时切换。
+
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// 写出错误时的详细程度
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorVerbosity {
+    /// 只输出 `Display` 文本（默认）
+    Terse = 0,
+    /// 在 `Display` 文本后附加 `[Debug 表示]`，panic 附加线程名和负载类型
+    Verbose = 1,
+}
+
+static ERROR_VERBOSITY: AtomicU8 = AtomicU8::new(ErrorVerbosity::Terse as u8);
+
+/// 设置全局错误详细程度，对所有线程生效
+///
+/// 影响 `set_error_from`、`set_error_chain` 和边界函数的 panic 消息；
+/// 线程局部的最近一次错误和错误观察者始终收到简短文本。
+/// 详细文本在翻译之前生成，翻译函数收到的默认消息即为详细文本。
+pub fn set_error_verbosity(verbosity: ErrorVerbosity) {
+    ERROR_VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+/// 当前的全局错误详细程度
+pub fn error_verbosity() -> ErrorVerbosity {
+    match ERROR_VERBOSITY.load(Ordering::Relaxed) {
+        x if x == ErrorVerbosity::Verbose as u8 => ErrorVerbosity::Verbose,
+        _ => ErrorVerbosity::Terse,
+    }
+}
+
+/// 从 C 侧设置全局错误详细程度：`0` 为 `Terse`，`1` 为 `Verbose`，其余值忽略
+#[no_mangle]
+pub extern "C" fn vimo_ffi_set_error_verbosity(verbosity: i32) {
+    match verbosity {
+        0 => set_error_verbosity(ErrorVerbosity::Terse),
+        1 => set_error_verbosity(ErrorVerbosity::Verbose),
+        _ => {}
+    }
+}
+
+/// `Verbose` 时在消息后附加 `[detail]`，`Terse` 时原样返回且不生成 `detail`
+pub(crate) fn with_detail<'a>(
+    message: &'a str,
+    detail: impl FnOnce() -> Option<String>,
+) -> Cow<'a, str> {
+    if error_verbosity() == ErrorVerbosity::Terse {
+        return Cow::Borrowed(message);
+    }
+    match detail() {
+        Some(detail) => Cow::Owned(format!("{message} [{detail}]")),
+        None => Cow::Borrowed(message),
+    }
+}
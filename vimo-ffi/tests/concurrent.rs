@@ -0,0 +1,217 @@
+//! 多线程同时调用边界函数的测试
+//!
+//! 100 个线程同时以伪随机的顺序触发成功、错误和 panic，检查：
+//! - 每个线程拿到的错误消息和最近一次错误都是自己的；
+//! - 全局分配器没有发现重复释放，释放全部错误消息后没有泄漏；
+//! - 启用 `metrics` feature 时计数与实际结果一致。
+//!
+//! 全局分配器和 panic hook 对整个进程生效，放在独立的测试二进制里。
+
+#![cfg(feature = "catch-unwind")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::ffi::{c_char, CString};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier};
+
+use vimo_ffi::*;
+
+/// 记录存活分配地址的全局分配器，释放未记录的地址视为重复释放
+struct TrackingAllocator;
+
+const SLOTS: usize = 1 << 18;
+const EMPTY: usize = 0;
+const REMOVED: usize = 1;
+
+static LIVE: [AtomicUsize; SLOTS] = [const { AtomicUsize::new(EMPTY) }; SLOTS];
+static LIVE_COUNT: AtomicUsize = AtomicUsize::new(0);
+static DOUBLE_FREES: AtomicUsize = AtomicUsize::new(0);
+/// 表满时放弃记录，之后的释放不再判断
+static UNTRACKED: AtomicUsize = AtomicUsize::new(0);
+
+fn slot_of(addr: usize) -> usize {
+    (addr >> 4).wrapping_mul(0x9E37_79B9_7F4A_7C15) % SLOTS
+}
+
+fn track(addr: usize) {
+    let start = slot_of(addr);
+    for i in 0..SLOTS {
+        let slot = &LIVE[(start + i) % SLOTS];
+        let current = slot.load(Ordering::Acquire);
+        if (current == EMPTY || current == REMOVED)
+            && slot
+                .compare_exchange(current, addr, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        {
+            LIVE_COUNT.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    }
+    UNTRACKED.fetch_add(1, Ordering::Relaxed);
+}
+
+fn untrack(addr: usize) {
+    let start = slot_of(addr);
+    for i in 0..SLOTS {
+        let slot = &LIVE[(start + i) % SLOTS];
+        match slot.load(Ordering::Acquire) {
+            EMPTY => break,
+            current if current == addr => {
+                if slot
+                    .compare_exchange(addr, REMOVED, Ordering::AcqRel, Ordering::Acquire)
+                    .is_ok()
+                {
+                    LIVE_COUNT.fetch_sub(1, Ordering::Relaxed);
+                    return;
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+    if UNTRACKED.load(Ordering::Relaxed) == 0 {
+        DOUBLE_FREES.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            track(ptr as usize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // 先移出记录再释放，地址被其它线程重新分配时记录已经清除
+        untrack(ptr as usize);
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: TrackingAllocator = TrackingAllocator;
+
+const THREADS: usize = 100;
+const CALLS: usize = 50;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Outcomes {
+    ok: u64,
+    errors: u64,
+    panics: u64,
+}
+
+/// 每个线程独立的 xorshift 序列，不依赖随机数 crate
+fn outcomes_for(thread: usize) -> impl Iterator<Item = u64> {
+    let mut state = 0x2545_F491_4F6C_DD1D ^ (thread as u64 + 1).wrapping_mul(0x9E37_79B9);
+    std::iter::repeat_with(move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % 3
+    })
+}
+
+fn run_thread(thread: usize) -> Outcomes {
+    let mut outcomes = Outcomes::default();
+    for (call, kind) in outcomes_for(thread).take(CALLS).enumerate() {
+        let tag = format!("thread {thread} call {call}");
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            ffi_boundary(&mut error_ptr, -1, || match kind {
+                0 => Ok(call as i64),
+                1 => Err(FfiError::custom(tag.clone())),
+                _ => panic!("{tag}"),
+            })
+        }));
+        match kind {
+            0 => {
+                assert_eq!(result.unwrap(), call as i64);
+                assert!(error_ptr.is_null());
+                assert_eq!(last_error(), None);
+                outcomes.ok += 1;
+            }
+            1 => {
+                assert_eq!(result.unwrap(), -1);
+                let msg = unsafe { CString::from_raw(error_ptr) }.into_string().unwrap();
+                assert_eq!(msg, tag);
+                assert_eq!(last_error().unwrap().message, tag);
+                outcomes.errors += 1;
+            }
+            _ => {
+                let expected = format!("internal panic: {tag}");
+                // test-mode 下边界函数记录后重新抛出，不写出错误
+                if cfg!(feature = "test-mode") {
+                    assert!(result.is_err());
+                    assert!(error_ptr.is_null());
+                } else {
+                    assert_eq!(result.unwrap(), -1);
+                    let msg = unsafe { CString::from_raw(error_ptr) }.into_string().unwrap();
+                    assert_eq!(msg, expected);
+                }
+                assert_eq!(last_error().unwrap().message, expected);
+                outcomes.panics += 1;
+            }
+        }
+    }
+    outcomes
+}
+
+/// 所有线程同时开始，返回汇总的结果
+fn run_all_threads() -> Outcomes {
+    let barrier = Arc::new(Barrier::new(THREADS));
+    let workers: Vec<_> = (0..THREADS)
+        .map(|thread| {
+            let barrier = Arc::clone(&barrier);
+            std::thread::spawn(move || {
+                barrier.wait();
+                run_thread(thread)
+            })
+        })
+        .collect();
+    let mut total = Outcomes::default();
+    for worker in workers {
+        let outcomes = worker.join().unwrap();
+        total.ok += outcomes.ok;
+        total.errors += outcomes.errors;
+        total.panics += outcomes.panics;
+    }
+    total
+}
+
+#[test]
+fn test_concurrent_boundaries() {
+    // 5000 次调用中约三分之一会 panic，不输出默认的 panic 信息
+    std::panic::set_hook(Box::new(|_| {}));
+    // 先完整运行一轮再取基线：测试框架在测试开始时（与本线程并发）的分配
+    // 以及首次使用时的延迟初始化都不计入泄漏
+    run_all_threads();
+    #[cfg(feature = "metrics")]
+    vimo_ffi_reset_metrics();
+    let live_before = LIVE_COUNT.load(Ordering::Relaxed);
+
+    let total = run_all_threads();
+    let _ = std::panic::take_hook();
+
+    assert_eq!(total.ok + total.errors + total.panics, (THREADS * CALLS) as u64);
+    assert!(total.ok > 0 && total.errors > 0 && total.panics > 0, "{total:?}");
+    assert_eq!(UNTRACKED.load(Ordering::Relaxed), 0);
+    assert_eq!(DOUBLE_FREES.load(Ordering::Relaxed), 0);
+
+    #[cfg(feature = "metrics")]
+    assert_eq!(
+        vimo_ffi_get_metrics(),
+        FfiMetricsSnapshot {
+            total_calls: (THREADS * CALLS) as u64,
+            panics: total.panics,
+            errors: total.errors,
+        }
+    );
+
+    // 线程退出时释放了各自的最近一次错误，所有错误消息也已经释放
+    vimo_ffi_clear_panic_info();
+    assert!(LIVE_COUNT.load(Ordering::Relaxed) <= live_before, "leaked allocations");
+}
@@ -0,0 +1,889 @@
+//! 修改进程级全局配置的测试
+//!
+//! 这些配置对所有线程生效，放在独立的测试二进制里，
+//! 并用 `CONFIG_LOCK` 串行执行，避免干扰其它测试。
+
+use std::ffi::{c_char, CString};
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+use vimo_ffi::*;
+
+static CONFIG_LOCK: Mutex<()> = Mutex::new(());
+
+fn lock_config() -> MutexGuard<'static, ()> {
+    CONFIG_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn take_error(ptr: *mut c_char) -> String {
+    assert!(!ptr.is_null());
+    unsafe { CString::from_raw(ptr) }.into_string().unwrap()
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_ffi_boundary_json_format() {
+    let _lock = lock_config();
+    set_error_format(ErrorFormat::Json);
+
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary(&mut error_ptr, false, || Err(FfiError::NullPointer));
+    let value: serde_json::Value = serde_json::from_str(&take_error(error_ptr)).unwrap();
+    assert_eq!(value["code"], 1);
+    assert_eq!(value["message"], "null pointer");
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        ffi_boundary_code(&mut error_ptr, || {
+            panic!("line one\nline \"two\"");
+            #[allow(unreachable_code)]
+            Ok::<(), String>(())
+        });
+        let value: serde_json::Value = serde_json::from_str(&take_error(error_ptr)).unwrap();
+        assert_eq!(value["code"], -1000);
+        assert_eq!(value["message"], "internal panic: line one\nline \"two\"");
+    }
+
+    set_error_format(ErrorFormat::Plain);
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary(&mut error_ptr, false, || Err(FfiError::NullPointer));
+    assert_eq!(take_error(error_ptr), "null pointer");
+}
+
+#[cfg(all(feature = "miette", feature = "serde"))]
+#[test]
+fn test_ffi_boundary_miette_json() {
+    let _lock = lock_config();
+    set_error_format(ErrorFormat::Json);
+
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary_miette(&mut error_ptr, false, || {
+        Err(miette::miette!(help = "check the path", "config \"{}\" is invalid", "a.toml"))
+    });
+    let value: serde_json::Value = serde_json::from_str(&take_error(error_ptr)).unwrap();
+    assert_eq!(value["message"], "config \"a.toml\" is invalid");
+    assert_eq!(value["help"], "check the path");
+
+    set_error_format(ErrorFormat::Plain);
+}
+
+#[cfg(feature = "backtrace")]
+#[test]
+fn test_ffi_boundary_backtrace_capture() {
+    let _lock = lock_config();
+
+    set_backtrace_capture(true);
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary(&mut error_ptr, false, || Err(FfiError::NullPointer));
+    let msg = take_error(error_ptr);
+    assert!(msg.starts_with("null pointer\n\nbacktrace:\n"));
+    assert!(msg.contains("test_ffi_boundary_backtrace_capture"), "{msg}");
+
+    set_backtrace_capture(false);
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary(&mut error_ptr, false, || Err(FfiError::NullPointer));
+    let msg = take_error(error_ptr);
+    assert_eq!(msg, "null pointer");
+    assert!(!msg.contains("test_ffi_boundary_backtrace_capture"));
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn test_boundary_metrics() {
+    let _lock = lock_config();
+    vimo_ffi_reset_metrics();
+
+    ffi_boundary(ptr::null_mut(), false, || Ok::<_, FfiError>(true));
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary(&mut error_ptr, false, || Err(FfiError::NullPointer));
+    assert_eq!(take_error(error_ptr), "null pointer");
+    ffi_boundary_code(ptr::null_mut(), || Err("failed"));
+    // test-mode 下 panic 会重新抛出，两种情况都应计数
+    let _ = std::panic::catch_unwind(|| ffi_boundary_simple(0, || -> i32 { panic!("boom") }));
+
+    let metrics = vimo_ffi_get_metrics();
+    assert_eq!(
+        metrics,
+        FfiMetricsSnapshot {
+            total_calls: 4,
+            panics: 1,
+            errors: 2,
+        }
+    );
+
+    vimo_ffi_reset_metrics();
+    assert_eq!(vimo_ffi_get_metrics(), FfiMetricsSnapshot::default());
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn test_named_boundary_stats() {
+    let _lock = lock_config();
+    vimo_ffi_stats_reset();
+    function_metrics("stats_idle");
+
+    ffi_boundary_named("stats_open", ptr::null_mut(), false, || Ok::<_, FfiError>(true));
+    ffi_boundary_named("stats_open", ptr::null_mut(), false, || Ok::<_, FfiError>(true));
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary_named("stats_open", &mut error_ptr, false, || Err(FfiError::Timeout));
+    assert_eq!(take_error(error_ptr), "operation timed out");
+    // test-mode 下 panic 会重新抛出，两种情况都应计数
+    let _ = std::panic::catch_unwind(|| {
+        ffi_boundary_named("stats_close", ptr::null_mut(), (), || -> Result<(), FfiError> {
+            panic!("boom")
+        })
+    });
+
+    let stats: serde_json::Value =
+        serde_json::from_str(&take_error(vimo_ffi_stats_json())).unwrap();
+    assert_eq!(stats["stats_open"], serde_json::json!({"calls": 3, "errors": 1, "panics": 0}));
+    assert_eq!(stats["stats_close"], serde_json::json!({"calls": 1, "errors": 0, "panics": 1}));
+    assert_eq!(stats["stats_idle"], serde_json::json!({"calls": 0, "errors": 0, "panics": 0}));
+
+    vimo_ffi_stats_reset();
+    let stats: serde_json::Value =
+        serde_json::from_str(&take_error(vimo_ffi_stats_json())).unwrap();
+    assert_eq!(stats["stats_open"]["calls"], 0);
+}
+
+fn translate_zh(code: i32, _default_msg: &str) -> Option<String> {
+    match code {
+        1 => Some("指针为空".to_string()),
+        -1000 => panic!("translator bug"),
+        _ => None,
+    }
+}
+
+extern "C" fn translate_from_c(code: i32, default_msg: *const c_char) -> *mut c_char {
+    if code != 1 {
+        return ptr::null_mut();
+    }
+    let default_msg = unsafe { std::ffi::CStr::from_ptr(default_msg) }.to_str().unwrap();
+    let translated = CString::new(format!("[zh] {default_msg}")).unwrap();
+    unsafe { vimo_ffi_strdup(translated.as_ptr()) }
+}
+
+#[test]
+fn test_error_translator() {
+    let _lock = lock_config();
+    set_error_translator(translate_zh);
+
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary(&mut error_ptr, false, || Err(FfiError::NullPointer));
+    assert_eq!(take_error(error_ptr), "指针为空");
+
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_error_from(&mut error_ptr, &FfiError::NullPointer) };
+    assert_eq!(take_error(error_ptr), "指针为空");
+
+    // 没有对应翻译时使用默认消息
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary(&mut error_ptr, false, || Err(FfiError::InvalidUtf8));
+    assert_eq!(take_error(error_ptr), "invalid UTF-8 string");
+
+    // 翻译函数 panic 时回退到默认消息
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        ffi_boundary_code(&mut error_ptr, || -> Result<(), FfiError> { panic!("boom") });
+        assert_eq!(take_error(error_ptr), "internal panic: boom");
+    }
+
+    clear_error_translator();
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary(&mut error_ptr, false, || Err(FfiError::NullPointer));
+    assert_eq!(take_error(error_ptr), "null pointer");
+}
+
+#[test]
+fn test_c_error_translator() {
+    let _lock = lock_config();
+    vimo_ffi_set_error_translator(Some(translate_from_c));
+
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary(&mut error_ptr, false, || Err(FfiError::NullPointer));
+    assert_eq!(take_error(error_ptr), "[zh] null pointer");
+
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary(&mut error_ptr, false, || Err(FfiError::InvalidUtf8));
+    assert_eq!(take_error(error_ptr), "invalid UTF-8 string");
+
+    vimo_ffi_set_error_translator(None);
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary(&mut error_ptr, false, || Err(FfiError::NullPointer));
+    assert_eq!(take_error(error_ptr), "null pointer");
+}
+
+#[cfg(feature = "log")]
+mod log_capture {
+    use std::sync::Mutex;
+
+    pub static RECORDS: Mutex<Vec<(log::Level, String)>> = Mutex::new(Vec::new());
+
+    struct Capture;
+
+    impl log::Log for Capture {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            let mut records = RECORDS.lock().unwrap_or_else(|e| e.into_inner());
+            records.push((record.level(), record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: Capture = Capture;
+
+    pub fn install() {
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+        RECORDS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+#[cfg(feature = "log")]
+#[test]
+fn test_boundary_logs_failures() {
+    let _lock = lock_config();
+    log_capture::install();
+
+    // out_error 为 null 时，日志是唯一的记录
+    ffi_boundary(ptr::null_mut(), false, || Err(FfiError::NullPointer));
+    FfiBoundaryOptions::log_level(log::Level::Warn);
+    // test-mode 下 panic 在记录日志后重新抛出
+    let _ = std::panic::catch_unwind(|| {
+        ffi_boundary_code(ptr::null_mut(), || -> Result<(), FfiError> { panic!("boom") })
+    });
+    FfiBoundaryOptions::log_level(log::Level::Error);
+
+    let records = log_capture::RECORDS.lock().unwrap().clone();
+    assert_eq!(
+        records,
+        [
+            (log::Level::Error, "[vimo-ffi] error: null pointer".to_string()),
+            (log::Level::Warn, "[vimo-ffi] error: internal panic: boom".to_string()),
+        ]
+    );
+}
+
+#[cfg(feature = "log")]
+#[test]
+fn test_overwriting_out_error_warns() {
+    let _lock = lock_config();
+    log_capture::install();
+
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_error(&mut error_ptr, "first") };
+    let first = error_ptr;
+    unsafe { set_error(&mut error_ptr, "second") };
+    assert_eq!(take_error(error_ptr), "second");
+    assert_eq!(take_error(first), "first");
+
+    // set_ffi_error 的静态消息快速路径同样提示
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_ffi_error(&mut error_ptr, &FfiError::NullPointer) };
+    let first = error_ptr;
+    unsafe { set_ffi_error(&mut error_ptr, &FfiError::Timeout) };
+    assert_eq!(take_error(error_ptr), "operation timed out");
+    assert_eq!(take_error(first), "null pointer");
+
+    let records = log_capture::RECORDS.lock().unwrap().clone();
+    let warning = (
+        log::Level::Warn,
+        "[vimo-ffi] out_error was not null, overwriting it leaks the previous message".to_string(),
+    );
+    assert_eq!(records, [warning.clone(), warning]);
+}
+
+type Events = std::sync::Arc<Mutex<Vec<(i32, String, bool)>>>;
+
+/// 安装只记录当前线程事件的观察者，其他测试线程的错误不会混进来
+fn record_events() -> Events {
+    let events = Events::default();
+    let sink = events.clone();
+    let thread = std::thread::current().id();
+    set_error_observer(Box::new(move |event| {
+        if std::thread::current().id() == thread {
+            let entry = (event.code, event.message.to_string(), event.is_panic);
+            sink.lock().unwrap().push(entry);
+        }
+    }));
+    events
+}
+
+#[cfg(feature = "catch-unwind")]
+#[test]
+fn test_error_observer_error_and_panic() {
+    let _lock = lock_config();
+    let events = record_events();
+
+    let code = ffi_boundary_code(ptr::null_mut(), || Err(FfiError::InvalidUtf8));
+    assert_eq!(code, FfiErrorCode::InvalidUtf8 as i32);
+    // test-mode 下 panic 在通知观察者后重新抛出
+    let _ = std::panic::catch_unwind(|| ffi_boundary_simple(false, || panic!("boom")));
+    clear_error_observer();
+    ffi_boundary_code(ptr::null_mut(), || Err(FfiError::NullPointer));
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            (2, "invalid UTF-8 string".to_string(), false),
+            (-1000, "internal panic: boom".to_string(), true),
+        ]
+    );
+}
+
+#[cfg(feature = "catch-unwind")]
+#[test]
+fn test_poison_recovery_warns_once() {
+    let _lock = lock_config();
+    static STATE: Mutex<u32> = Mutex::new(0);
+    let events = record_events();
+
+    for _ in 0..2 {
+        let _ = std::panic::catch_unwind(|| {
+            ffi_boundary_simple(false, || {
+                let _guard = lock_recover(&STATE);
+                panic!("poisoned")
+            })
+        });
+        assert!(ffi_boundary_simple(false, || {
+            *lock_recover(&STATE) += 1;
+            true
+        }));
+    }
+    clear_error_observer();
+
+    assert_eq!(*lock_recover(&STATE), 2);
+    let panic_event = (-1000, "internal panic: poisoned".to_string(), true);
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            panic_event.clone(),
+            (FfiErrorCode::Unknown as i32, POISON_RECOVERED_MESSAGE.to_string(), false),
+            panic_event,
+        ]
+    );
+}
+
+#[test]
+fn test_error_observer_set_error() {
+    let _lock = lock_config();
+    let events = record_events();
+
+    unsafe { set_error(ptr::null_mut(), "plain") };
+    unsafe { set_error_from(ptr::null_mut(), &FfiError::NullPointer) };
+    clear_error_observer();
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            (FfiErrorCode::Unknown as i32, "plain".to_string(), false),
+            (1, "null pointer".to_string(), false),
+        ]
+    );
+}
+
+#[test]
+fn test_error_observer_reentrancy_and_panic() {
+    let _lock = lock_config();
+    let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = calls.clone();
+    set_error_observer(Box::new(move |_| {
+        counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        // 观察者内部的错误不会再通知观察者，观察者的 panic 被吞掉
+        unsafe { set_error(ptr::null_mut(), "from observer") };
+        panic!("observer panicked");
+    }));
+
+    let code = ffi_boundary_code(ptr::null_mut(), || Err(FfiError::NullPointer));
+    let code_again = ffi_boundary_code(ptr::null_mut(), || Err(FfiError::NullPointer));
+    clear_error_observer();
+
+    assert_eq!((code, code_again), (1, 1));
+    assert_eq!(vimo_ffi_last_error_code(), 1);
+    assert!(calls.load(std::sync::atomic::Ordering::SeqCst) >= 2);
+}
+
+extern "C" fn c_observer(event: *const VimoErrorEvent, user_data: *mut std::ffi::c_void) {
+    let event = unsafe { &*event };
+    let message = unsafe { std::ffi::CStr::from_ptr(event.message) };
+    if message.to_bytes() == b"c observer" {
+        assert!(!event.is_panic);
+        assert!(event.function.is_null());
+        unsafe { *(user_data as *mut i32) = event.code };
+    }
+}
+
+#[test]
+fn test_error_observer_overwrite_warning() {
+    let _lock = lock_config();
+    let events = record_events();
+
+    // 不依赖 log feature，观察者总能收到覆盖提示
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_error(&mut error_ptr, "first") };
+    let first = error_ptr;
+    unsafe { set_ffi_error(&mut error_ptr, &FfiError::Timeout) };
+    clear_error_observer();
+    assert_eq!(take_error(error_ptr), "operation timed out");
+    assert_eq!(take_error(first), "first");
+
+    let unknown = FfiErrorCode::Unknown as i32;
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            (unknown, "first".to_string(), false),
+            (11, "operation timed out".to_string(), false),
+            (unknown, OVERWRITE_WARNING_MESSAGE.to_string(), false),
+        ]
+    );
+}
+
+#[test]
+fn test_c_error_observer() {
+    let _lock = lock_config();
+    let mut seen_code: i32 = 0;
+    vimo_ffi_set_error_observer(Some(c_observer), &mut seen_code as *mut i32 as *mut _);
+    ffi_boundary_code(ptr::null_mut(), || Err(FfiError::custom_with_code(4321, "c observer")));
+    vimo_ffi_set_error_observer(None, ptr::null_mut());
+    assert_eq!(seen_code, 4321);
+}
+
+#[test]
+fn test_custom_with_code_flags_reserved_and_unknown() {
+    let _lock = lock_config();
+    let events = record_events();
+
+    // 错误码 0 不会让 ffi_boundary_code 报告成功
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    let rc = ffi_boundary_code(&mut error_ptr, || Err(FfiError::custom_with_code(0, "quota")));
+    assert_eq!(rc, FfiErrorCode::Custom as i32);
+    assert_eq!(take_error(error_ptr), "quota");
+    ffi_boundary_code(ptr::null_mut(), || Err(FfiError::custom_with_code(98_765, "unknown")));
+    clear_error_observer();
+
+    let unknown = FfiErrorCode::Unknown as i32;
+    let reserved = "custom error code 0 is reserved for built-in errors, using CUSTOM";
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            (unknown, reserved.to_string(), false),
+            (100, "quota".to_string(), false),
+            (unknown, "custom error code 98765 is not registered".to_string(), false),
+            (98_765, "unknown".to_string(), false),
+        ]
+    );
+}
+
+#[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+#[derive(Debug)]
+struct OpenError {
+    path: &'static str,
+    source: std::fmt::Error,
+}
+
+#[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+impl std::fmt::Display for OpenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cannot open {}", self.path)
+    }
+}
+
+#[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+impl std::error::Error for OpenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// 在给定详细程度下依次渲染：嵌套的 FfiError、带 source 的错误链、panic
+#[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+fn render_all(verbosity: ErrorVerbosity) -> [String; 3] {
+    set_error_verbosity(verbosity);
+    let nested = FfiError::NullPointer.context("argument 'title'");
+    let mut from_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_error_from(&mut from_ptr, &nested) };
+
+    let chained = OpenError {
+        path: "a.txt",
+        source: std::fmt::Error,
+    };
+    let mut chain_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_error_chain(&mut chain_ptr, &chained) };
+
+    let mut panic_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary_code(&mut panic_ptr, || -> Result<(), FfiError> { panic!("boom") });
+    set_error_verbosity(ErrorVerbosity::Terse);
+
+    [take_error(from_ptr), take_error(chain_ptr), take_error(panic_ptr)]
+}
+
+#[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+#[test]
+fn test_error_verbosity_renderings() {
+    let _lock = lock_config();
+
+    let [from, chain, panic] = render_all(ErrorVerbosity::Terse);
+    assert_eq!(from, "argument 'title': null pointer");
+    assert_eq!(
+        chain,
+        "cannot open a.txt: caused by: an error occurred when formatting an argument"
+    );
+    assert_eq!(panic, "internal panic: boom");
+
+    let [from, chain, panic] = render_all(ErrorVerbosity::Verbose);
+    assert_eq!(
+        from,
+        "argument 'title': null pointer \
+         [Context { context: \"argument 'title'\", inner: NullPointer }]"
+    );
+    assert_eq!(
+        chain,
+        "cannot open a.txt: caused by: an error occurred when formatting an argument \
+         [OpenError { path: \"a.txt\", source: Error }]"
+    );
+    let thread = std::thread::current();
+    let expected = format!(
+        "internal panic: boom [thread '{}', payload &str]",
+        thread.name().unwrap_or("<unnamed>")
+    );
+    assert_eq!(panic, expected);
+
+    // 不经过 out_error 的边界同样附带细节
+    set_error_verbosity(ErrorVerbosity::Verbose);
+    let mut buf: [c_char; 128] = [0; 128];
+    ffi_boundary_buf(buf.as_mut_ptr(), buf.len(), (), || -> Result<(), FfiError> {
+        panic!("boom")
+    });
+    set_error_verbosity(ErrorVerbosity::Terse);
+    let written = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+    assert_eq!(written.to_str().unwrap(), expected);
+
+    // 最近一次错误始终是简短文本
+    set_error_verbosity(ErrorVerbosity::Verbose);
+    ffi_boundary_code(ptr::null_mut(), || -> Result<(), FfiError> { panic!("boom") });
+    set_error_verbosity(ErrorVerbosity::Terse);
+    assert_eq!(take_error(vimo_ffi_last_error_message()), "internal panic: boom");
+}
+
+#[test]
+fn test_c_set_error_verbosity() {
+    let _lock = lock_config();
+    vimo_ffi_set_error_verbosity(1);
+    assert_eq!(error_verbosity(), ErrorVerbosity::Verbose);
+    vimo_ffi_set_error_verbosity(7);
+    assert_eq!(error_verbosity(), ErrorVerbosity::Verbose);
+    vimo_ffi_set_error_verbosity(0);
+    assert_eq!(error_verbosity(), ErrorVerbosity::Terse);
+}
+
+#[test]
+fn test_max_error_len() {
+    let _lock = lock_config();
+    assert_eq!(max_error_len(), DEFAULT_MAX_ERROR_LEN);
+    set_max_error_len(10);
+
+    // "ü" 占两个字节，第 10 个字节落在它中间
+    let long = "123456789ü tail";
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_error(&mut error_ptr, long) };
+    assert_eq!(take_error(error_ptr), "123456789... (truncated, 16 bytes total)");
+
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_error_from(&mut error_ptr, &FfiError::custom("x".repeat(40_000))) };
+    assert_eq!(take_error(error_ptr), "xxxxxxxxxx... (truncated, 40000 bytes total)");
+
+    // 静态消息的快速路径同样受上限约束
+    set_max_error_len(4);
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_ffi_error(&mut error_ptr, &FfiError::NullPointer) };
+    assert_eq!(take_error(error_ptr), "null... (truncated, 12 bytes total)");
+    set_max_error_len(10);
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        ffi_boundary_code(&mut error_ptr, || -> Result<(), FfiError> {
+            panic!("{}", "é".repeat(20))
+        });
+        // "internal panic: " 已经超过 10 字节
+        assert_eq!(take_error(error_ptr), "internal p... (truncated, 56 bytes total)");
+        assert_eq!(
+            take_error(vimo_ffi_last_error_message()),
+            "internal p... (truncated, 56 bytes total)"
+        );
+    }
+
+    set_max_error_len(0);
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_error(&mut error_ptr, long) };
+    assert_eq!(take_error(error_ptr), long);
+
+    set_max_error_len(DEFAULT_MAX_ERROR_LEN);
+}
+
+fn strip_tokens(message: &str) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(start) = rest.find("token=") {
+        out.push_str(&rest[..start + "token=".len()]);
+        out.push_str("[redacted]");
+        rest = &rest[start + "token=".len()..];
+        let end = rest.find(|c: char| c == '&' || c.is_whitespace()).unwrap_or(rest.len());
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[test]
+fn test_message_redactor() {
+    let _lock = lock_config();
+    set_message_redactor(strip_tokens);
+
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_error(&mut error_ptr, "GET /sync?token=s3cr3t&page=2 failed") };
+    assert_eq!(take_error(error_ptr), "GET /sync?token=[redacted]&page=2 failed");
+
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary(&mut error_ptr, false, || Err(FfiError::custom("bad token=abc")));
+    assert_eq!(take_error(error_ptr), "bad token=[redacted]");
+    assert_eq!(last_error().unwrap().message, "bad token=[redacted]");
+
+    // 静态消息的快速路径同样经过脱敏
+    set_message_redactor(|msg| format!("R[{msg}]"));
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_ffi_error(&mut error_ptr, &FfiError::NullPointer) };
+    assert_eq!(take_error(error_ptr), "R[null pointer]");
+    set_message_redactor(strip_tokens);
+
+    #[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+    {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        ffi_boundary_code(&mut error_ptr, || -> Result<(), FfiError> {
+            panic!("invalid url https://api.vimo.ai/?token=xyz")
+        });
+        let expected = "internal panic: invalid url https://api.vimo.ai/?token=[redacted]";
+        assert_eq!(take_error(error_ptr), expected);
+        assert_eq!(take_error(vimo_ffi_last_error_message()), expected);
+        assert_eq!(
+            take_error(vimo_ffi_last_panic_message()),
+            "invalid url https://api.vimo.ai/?token=[redacted]"
+        );
+        vimo_ffi_clear_panic_info();
+    }
+
+    // 脱敏函数 panic 时不回退到原文；运行时可以替换
+    set_message_redactor(|_| panic!("redactor bug"));
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_error(&mut error_ptr, "token=s3cr3t") };
+    assert_eq!(take_error(error_ptr), REDACTION_FAILED_MESSAGE);
+
+    clear_message_redactor();
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    unsafe { set_error(&mut error_ptr, "token=s3cr3t") };
+    assert_eq!(take_error(error_ptr), "token=s3cr3t");
+}
+
+#[test]
+fn test_max_cstring_len() {
+    let _lock = lock_config();
+    assert_eq!(max_cstring_len(), usize::MAX);
+    set_max_cstring_len(4);
+
+    let too_long = FfiError::Custom("string too long".into());
+    assert_eq!(str_to_cstring("hello"), Err(too_long.clone()));
+    assert_eq!(str_to_u8ptr("hello"), Err(too_long.clone()));
+    let mut builder = CStringBuilder::new();
+    builder.push_str("hello");
+    assert_eq!(builder.finish(), Err(too_long));
+    let ptr = str_to_cstring("hell").unwrap();
+    assert_eq!(take_error(ptr), "hell");
+    // 显式上限不受全局上限影响
+    let ptr = str_to_cstring_max("hello", 16).unwrap();
+    assert_eq!(take_error(ptr), "hello");
+
+    set_max_cstring_len(usize::MAX);
+    let ptr = str_to_cstring("hello").unwrap();
+    assert_eq!(take_error(ptr), "hello");
+}
+
+#[cfg(feature = "catch-unwind")]
+#[test]
+fn test_panic_info_per_thread_and_global() {
+    let _lock = lock_config();
+    vimo_ffi_clear_panic_info();
+    assert_eq!(vimo_ffi_panic_count(), 0);
+    assert!(vimo_ffi_last_panic_message().is_null());
+
+    let workers: Vec<_> = ["worker-a", "worker-b"]
+        .into_iter()
+        .map(|name| {
+            std::thread::Builder::new()
+                .name(name.to_string())
+                .spawn(move || {
+                    // 没有 out_error 的边界同样记录
+                    let _ = std::panic::catch_unwind(|| {
+                        ffi_boundary_simple(0, || -> i32 { panic!("{name} failed") })
+                    });
+                    let record = last_panic_info().unwrap();
+                    assert_eq!(record.message, format!("{name} failed"));
+                    assert_eq!(record.thread, name);
+                    assert!(record.timestamp > 0);
+                    assert_eq!(take_error(vimo_ffi_last_panic_message()), record.message);
+                })
+                .unwrap()
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    assert_eq!(vimo_ffi_panic_count(), 2);
+
+    // 主线程没有自己的记录，返回进程级的最近一次
+    assert_eq!(last_panic_info(), None);
+    let global = last_global_panic_info().unwrap();
+    assert!(global.thread.starts_with("worker-"));
+    assert_eq!(take_error(vimo_ffi_last_panic_message()), global.message);
+
+    vimo_ffi_clear_panic_info();
+    assert_eq!(vimo_ffi_panic_count(), 0);
+    assert_eq!(last_global_panic_info(), None);
+}
+
+#[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+extern "C" fn record_panic(
+    message: *const c_char,
+    location: *const c_char,
+    user_data: *mut std::ffi::c_void,
+) {
+    let seen = unsafe { &*(user_data as *const Mutex<Vec<(String, bool)>>) };
+    let message = unsafe { std::ffi::CStr::from_ptr(message) }.to_string_lossy().into_owned();
+    seen.lock().unwrap().push((message, location.is_null()));
+}
+
+#[cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+#[test]
+fn test_c_panic_handler() {
+    let _lock = lock_config();
+    let seen: Mutex<Vec<(String, bool)>> = Mutex::new(Vec::new());
+    vimo_ffi_set_panic_handler(Some(record_panic), &seen as *const _ as *mut _);
+
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    let rc = ffi_boundary_code(&mut error_ptr, || -> Result<(), FfiError> {
+        panic!("disk {} failed", 3)
+    });
+    assert_eq!(rc, FfiErrorCode::Panic as i32);
+    assert_eq!(take_error(error_ptr), "internal panic: disk 3 failed");
+    ffi_boundary_simple(0, || -> i32 { panic!("simple") });
+    // 普通错误不调用回调
+    ffi_boundary_code(ptr::null_mut(), || Err(FfiError::Timeout));
+
+    vimo_ffi_set_panic_handler(None, ptr::null_mut());
+    ffi_boundary_simple(0, || -> i32 { panic!("after removal") });
+
+    // 本测试二进制没有安装 panic hook，位置为 null
+    assert_eq!(
+        *seen.lock().unwrap(),
+        [("disk 3 failed".to_string(), true), ("simple".to_string(), true)]
+    );
+}
+
+#[cfg(feature = "catch-unwind")]
+#[test]
+fn test_spawn_ffi_thread_reports_panic() {
+    let _lock = lock_config();
+    let events = Events::default();
+    let sink = events.clone();
+    // 事件发生在工作线程上，按消息过滤
+    set_error_observer(Box::new(move |event| {
+        if event.message.contains("indexer crashed") {
+            let entry = (event.code, event.message.to_string(), event.is_panic);
+            sink.lock().unwrap().push(entry);
+        }
+    }));
+    let panics_before = vimo_ffi_panic_count();
+
+    let handle = spawn_ffi_thread_with_result("vimo-indexer", || -> usize {
+        panic!("indexer crashed")
+    });
+    let err = handle.join().unwrap_err();
+    clear_error_observer();
+
+    assert_eq!(err.code(), FfiErrorCode::Panic as i32);
+    assert_eq!(err.to_string(), "internal panic: indexer crashed");
+    assert_eq!(
+        *events.lock().unwrap(),
+        [(-1000, "internal panic: indexer crashed".to_string(), true)]
+    );
+    assert_eq!(vimo_ffi_panic_count(), panics_before + 1);
+    let record = last_global_panic_info().unwrap();
+    assert_eq!(record.message, "indexer crashed");
+    assert_eq!(record.thread, "vimo-indexer");
+}
+
+#[cfg(feature = "catch-unwind")]
+#[test]
+fn test_panic_policy_catch_and_rethrow() {
+    let _lock = lock_config();
+    assert_eq!(panic_policy(), PanicPolicy::Catch);
+
+    set_panic_policy(PanicPolicy::Rethrow);
+    let outer = std::panic::catch_unwind(|| {
+        ffi_boundary_simple(0, || -> i32 { panic!("rethrown") })
+    });
+    let payload = outer.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"rethrown"));
+    // 重新抛出前已经记录
+    assert_eq!(vimo_ffi_last_error_code(), FfiErrorCode::Panic as i32);
+    assert_eq!(last_panic_info().unwrap().message, "rethrown");
+
+    vimo_ffi_set_panic_policy(0);
+    assert_eq!(panic_policy(), PanicPolicy::Catch);
+    vimo_ffi_set_panic_policy(7);
+    assert_eq!(panic_policy(), PanicPolicy::Catch);
+    #[cfg(not(feature = "test-mode"))]
+    {
+        let mut error_ptr: *mut c_char = ptr::null_mut();
+        let rc =
+            ffi_boundary_code(&mut error_ptr, || -> Result<(), FfiError> { panic!("caught") });
+        assert_eq!(rc, FfiErrorCode::Panic as i32);
+        assert_eq!(take_error(error_ptr), "internal panic: caught");
+    }
+}
+
+#[cfg(all(feature = "pprof", unix))]
+#[test]
+fn test_pprof_report_labels_boundaries() {
+    let _lock = lock_config();
+    vimo_ffi_pprof_reset();
+    assert!(!pprof_enabled());
+    ffi_boundary(ptr::null_mut(), 0, || Ok::<_, FfiError>(1));
+    assert!(vimo_ffi_pprof_report().is_null());
+
+    fn spin() -> Result<u64, FfiError> {
+        let start = std::time::Instant::now();
+        let mut x = 0u64;
+        while start.elapsed() < std::time::Duration::from_millis(100) {
+            x = std::hint::black_box(x.wrapping_mul(31).wrapping_add(7));
+        }
+        Ok(x)
+    }
+    set_pprof_enabled(true);
+    ffi_boundary_named("vimo_render", ptr::null_mut(), 0, spin);
+    ffi_boundary(ptr::null_mut(), 0, spin);
+    vimo_ffi_pprof_set_enabled(false);
+
+    let svg = take_error(vimo_ffi_pprof_report());
+    assert!(svg.contains("<svg"));
+    assert!(svg.contains("vimo_render"));
+    assert!(svg.contains(PPROF_ANONYMOUS_LABEL));
+
+    vimo_ffi_pprof_reset();
+    assert!(vimo_ffi_pprof_report().is_null());
+}
@@ -0,0 +1,40 @@
+//! `PanicPolicy::Abort` 的测试
+//!
+//! 进程被终止后无法在进程内断言，测试以子进程重新运行本测试二进制中的
+//! `abort_child`，检查子进程的退出状态。
+//! 关闭 `catch-unwind` feature 时边界不捕获 panic，策略不起作用。
+#![cfg(feature = "catch-unwind")]
+
+use std::process::Command;
+
+use vimo_ffi::*;
+
+const CHILD_ENV: &str = "VIMO_FFI_PANIC_ABORT_CHILD";
+
+#[test]
+fn abort_child() {
+    if std::env::var_os(CHILD_ENV).is_none() {
+        return;
+    }
+    set_panic_policy(PanicPolicy::Abort);
+    ffi_boundary_simple(0, || -> i32 { panic!("fatal in child") });
+    // 不应该执行到这里
+    std::process::exit(0);
+}
+
+#[test]
+fn test_panic_policy_abort() {
+    let output = Command::new(std::env::current_exe().unwrap())
+        .args(["abort_child", "--exact", "--nocapture", "--test-threads=1"])
+        .env(CHILD_ENV, "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        assert_eq!(output.status.signal(), Some(6), "{:?}", output.status);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("[vimo-ffi] internal panic: fatal in child, aborting"), "{stderr}");
+}
@@ -0,0 +1,60 @@
+//! 安装 panic hook 的测试
+//!
+//! hook 对整个进程生效且无法卸载，放在独立的测试二进制里，避免影响其它测试的 panic 消息。
+//! 测试检查边界捕获 panic 后写出的位置，关闭 `catch-unwind` feature 或启用 `test-mode` 时不编译。
+#![cfg(all(feature = "catch-unwind", not(feature = "test-mode")))]
+
+use std::ffi::{c_char, CString};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use vimo_ffi::*;
+
+static PREVIOUS_HOOK_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+fn take_error(ptr: *mut c_char) -> String {
+    assert!(!ptr.is_null());
+    unsafe { CString::from_raw(ptr) }.into_string().unwrap()
+}
+
+#[test]
+fn test_panic_location_in_boundary_error() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        PREVIOUS_HOOK_CALLS.fetch_add(1, Ordering::SeqCst);
+        default_hook(info);
+    }));
+    install_ffi_panic_hook();
+    // 重复安装不会再包一层
+    install_ffi_panic_hook();
+    vimo_ffi_install_panic_hook();
+
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    let line = line!() + 2;
+    ffi_boundary_code(&mut error_ptr, || -> Result<(), FfiError> {
+        panic!("index out of bounds")
+    });
+    let expected = format!("internal panic at {}:{line}:9: index out of bounds", file!());
+    assert_eq!(take_error(error_ptr), expected);
+    assert_eq!(last_error().unwrap().message, expected);
+    assert_eq!(last_panic_info().unwrap().location, format!("{}:{line}:9", file!()));
+    // 安装前的 hook 仍然被调用，且只调用一次
+    assert_eq!(PREVIOUS_HOOK_CALLS.load(Ordering::SeqCst), 1);
+
+    // 闭包内自行捕获的 panic 不会把位置带到后续的 panic 上
+    let mut error_ptr: *mut c_char = ptr::null_mut();
+    ffi_boundary_code(&mut error_ptr, || -> Result<(), FfiError> {
+        let _ = std::panic::catch_unwind(|| panic!("handled"));
+        std::panic::panic_any(42)
+    });
+    let msg = take_error(error_ptr);
+    assert!(msg.starts_with(&format!("internal panic at {}:", file!())), "{msg}");
+    assert!(msg.ends_with(": unknown panic"), "{msg}");
+
+    let mut location = None;
+    let line = line!() + 2;
+    ffi_boundary_with_log_event((), |event| location = event.location.map(str::to_owned), || {
+        panic!("logged")
+    });
+    assert_eq!(location, Some(format!("{}:{line}:9", file!())));
+}